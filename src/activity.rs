@@ -0,0 +1,77 @@
+//! A short log of recent activations, for showing somewhere other than the console.
+
+// Only the Windows tray shows entries as lines so far.
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How many entries are kept.
+const CAPACITY: usize = 20;
+
+static RECENT: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+
+/// How an activation ended.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// Text was pasted, or whatever else the hotkey does was done
+    Success,
+    /// Nothing happened, e.g. because nothing was selected
+    Skipped,
+    /// Something went wrong
+    Failure,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub at: Instant,
+    pub kind: Kind,
+    pub message: String,
+}
+
+impl Entry {
+    /// The entry as one line, e.g. "2 min ago: Failed: Clipboard is locked".
+    pub fn describe(&self) -> String {
+        let prefix = match self.kind {
+            Kind::Success => "",
+            Kind::Skipped => "Skipped: ",
+            Kind::Failure => "Failed: ",
+        };
+        format!("{}: {}{}", ago(self.at.elapsed()), prefix, self.message)
+    }
+}
+
+pub fn record(kind: Kind, message: impl Into<String>) {
+    let mut recent = recent_mut();
+    if recent.len() == CAPACITY {
+        recent.pop_back();
+    }
+    recent.push_front(Entry {
+        at: Instant::now(),
+        kind,
+        message: message.into(),
+    });
+}
+
+/// Every kept entry, newest first.
+pub fn recent() -> Vec<Entry> {
+    recent_mut().iter().cloned().collect()
+}
+
+fn ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        0..60 => format!("{} s ago", elapsed.as_secs()),
+        60..3600 => format!("{} min ago", elapsed.as_secs() / 60),
+        secs => format!("{} h ago", secs / 3600),
+    }
+}
+
+fn recent_mut() -> MutexGuard<'static, VecDeque<Entry>> {
+    // The log is always left consistent, so a panic elsewhere doesn't invalidate it.
+    RECENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
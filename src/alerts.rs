@@ -0,0 +1,123 @@
+//! `--errors-to`: where errors are reported, for when the listener runs minimized or
+//! detached and nobody reads its console.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::events;
+use crate::privacy;
+
+/// Somewhere an error can go.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Destination {
+    /// Printed to stderr, as always
+    Console,
+    /// A notification from the tray icon, which it shows (Windows only)
+    Notification,
+    /// Logged at error level, to --log-file if given
+    Log,
+    /// POSTed as JSON to --error-webhook, with curl
+    Webhook,
+}
+
+struct Routes {
+    destinations: Vec<Destination>,
+    webhook: Option<String>,
+}
+
+/// Until `set`, errors only go to the console.
+static ROUTES: RwLock<Option<Arc<Routes>>> = RwLock::new(None);
+
+/// Sends errors to `destinations` from now on. `webhook` is the URL for `Webhook`.
+pub fn set(destinations: &[Destination], webhook: Option<&str>) -> Result<()> {
+    if destinations.contains(&Destination::Webhook) && webhook.is_none() {
+        return Err(anyhow!("--errors-to webhook needs an --error-webhook URL"));
+    }
+    *ROUTES
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(Routes {
+        destinations: destinations.to_vec(),
+        webhook: webhook.map(str::to_string),
+    }));
+    Ok(())
+}
+
+/// Whether errors are notified, so an activation's failure needn't be again for `--notify`.
+pub fn notifies() -> bool {
+    routes().is_some_and(|routes| routes.destinations.contains(&Destination::Notification))
+}
+
+/// Reports `error` everywhere errors go.
+pub fn error(error: &anyhow::Error) {
+    let Some(routes) = routes() else {
+        eprintln!("ERROR: {:?}", error);
+        return;
+    };
+    // Everywhere but the console, where the whole chain and any backtrace is wanted.
+    let line = privacy::redact(&format!("{:#}", error));
+    for destination in &routes.destinations {
+        match destination {
+            Destination::Console => eprintln!("ERROR: {:?}", error),
+            Destination::Notification => {
+                #[cfg(windows)]
+                crate::tray::notify(crate::activity::Kind::Failure, &line);
+            }
+            Destination::Log => log::error!("{}", line),
+            Destination::Webhook => {
+                if let Some(url) = &routes.webhook {
+                    post(url.clone(), line.clone());
+                }
+            }
+        }
+    }
+}
+
+fn routes() -> Option<Arc<Routes>> {
+    ROUTES
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+// Posts `message` to `url` in the background, so a slow server doesn't hold up the
+// activation. `text` and `content` are what Slack and Discord webhooks show.
+fn post(url: String, message: String) {
+    let body = serde_json::json!({
+        "text": format!("flatten-string: {}", message),
+        "content": format!("flatten-string: {}", message),
+        "message": message,
+        "timestamp": events::timestamp(SystemTime::now()),
+        "pid": std::process::id(),
+    })
+    .to_string();
+    thread::spawn(move || {
+        let sent = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+            .args(["--header", "Content-Type: application/json"])
+            .args(["--data-binary", "@-", &url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(body.as_bytes())?;
+                }
+                child.wait_with_output()
+            });
+        match sent {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => log::warn!(
+                "The error webhook failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => log::warn!("Failed to run curl for the error webhook: {}", e),
+        }
+    });
+}
@@ -0,0 +1,440 @@
+//! `autostart enable/disable`: start the listener at login, with the options it was given.
+//!
+//! Windows uses the per-user Run key, or with `--elevated` a Task Scheduler task that runs
+//! with the highest privileges (the Run key can't start elevated programs). Linux gets an
+//! XDG autostart entry and macOS a launch agent.
+
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::ffi::OsString;
+use std::path::{self, PathBuf};
+
+/// The name of the Run value, scheduled task, autostart entry or launch agent.
+const NAME: &str = "flatten-string";
+
+/// Options that take a path, with their short forms. At login the working directory is a
+/// different one, so their paths are made absolute.
+const PATH_OPTIONS: [(&str, Option<&str>); 4] = [
+    ("--config", Some("-c")),
+    ("--plugin-dir", None),
+    ("--log-file", None),
+    ("--event-log", None),
+];
+
+/// The options to start with: this run's, without `autostart enable` and its own flag.
+pub fn listener_args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    if let Some(at) = args
+        .windows(2)
+        .position(|pair| pair[0] == "autostart" && pair[1] == "enable")
+    {
+        args.drain(at..at + 2);
+    }
+    args.retain(|arg| arg != "--elevated");
+    absolute_paths(args)
+}
+
+/// `args` with the values of `PATH_OPTIONS` made absolute, up to a `--`.
+fn absolute_paths(args: Vec<OsString>) -> Vec<OsString> {
+    let absolute = |path: OsString| path::absolute(&path).map_or(path, PathBuf::into_os_string);
+    let mut out = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let text = arg.to_str().unwrap_or_default();
+        if text == "--" {
+            out.push(arg);
+            out.extend(args);
+            break;
+        }
+        let mut value = None;
+        for (long, short) in PATH_OPTIONS {
+            let attached = text
+                .strip_prefix(long)
+                .and_then(|rest| rest.strip_prefix('='))
+                .or_else(|| {
+                    let rest = text.strip_prefix(short?)?;
+                    (!rest.is_empty()).then(|| rest.strip_prefix('=').unwrap_or(rest))
+                });
+            if text == long || Some(text) == short {
+                value = Some((long, args.next()));
+            } else if let Some(path) = attached {
+                value = Some((long, Some(path.into())));
+            } else {
+                continue;
+            }
+            break;
+        }
+        match value {
+            Some((long, Some(path))) => {
+                out.push(long.into());
+                out.push(absolute(path));
+            }
+            _ => out.push(arg),
+        }
+    }
+    out
+}
+
+/// Registers the running executable to start at login with `args`.
+pub fn enable(args: &[OsString], elevated: bool) -> Result<()> {
+    if elevated && !cfg!(windows) {
+        return Err(anyhow!("--elevated is only supported on Windows"));
+    }
+    let exe = env::current_exe().context("Failed to find the running executable")?;
+    platform::enable(exe, args, elevated)
+}
+
+/// Removes whatever `enable` registered.
+pub fn disable() -> Result<()> {
+    platform::disable()
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::process::Command;
+    use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+    use windows_sys::Win32::System::Registry::{
+        RegDeleteKeyValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ,
+    };
+
+    const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+    pub fn enable(exe: PathBuf, args: &[OsString], elevated: bool) -> Result<()> {
+        let command_line: Vec<String> = std::iter::once(exe.into_os_string())
+            .chain(args.iter().cloned())
+            .map(|arg| quote(&arg.to_string_lossy()))
+            .collect();
+        let command_line = command_line.join(" ");
+        if elevated {
+            schtasks(&[
+                "/Create",
+                "/F",
+                "/TN",
+                NAME,
+                "/SC",
+                "ONLOGON",
+                "/RL",
+                "HIGHEST",
+                "/TR",
+                &command_line,
+            ])
+            .context("Failed to create the scheduled task. Creating an elevated task needs an elevated prompt.")?;
+            // Only one of them should start it.
+            remove_run_value()?;
+            println!("Created the scheduled task {:?}: {}", NAME, command_line);
+            return Ok(());
+        }
+        let data = wide(&command_line);
+        let status = unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                wide(RUN_KEY).as_ptr(),
+                wide(NAME).as_ptr(),
+                REG_SZ,
+                data.as_ptr().cast(),
+                (data.len() * 2) as u32,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(status as i32))
+                .context("Failed to write the Run key");
+        }
+        println!("Added {:?} to HKCU\\{}: {}", NAME, RUN_KEY, command_line);
+        Ok(())
+    }
+
+    pub fn disable() -> Result<()> {
+        match remove_run_value()? {
+            true => println!("Removed {:?} from HKCU\\{}.", NAME, RUN_KEY),
+            false => println!("No {:?} in HKCU\\{}.", NAME, RUN_KEY),
+        }
+        // A missing task isn't worth an error; anything else is reported by schtasks itself.
+        if schtasks(&["/Query", "/TN", NAME]).is_ok() {
+            schtasks(&["/Delete", "/F", "/TN", NAME])
+                .context("Failed to delete the scheduled task. Deleting an elevated task needs an elevated prompt.")?;
+            println!("Deleted the scheduled task {:?}.", NAME);
+        }
+        Ok(())
+    }
+
+    // Whether there was a value to remove.
+    fn remove_run_value() -> Result<bool> {
+        let status = unsafe {
+            RegDeleteKeyValueW(
+                HKEY_CURRENT_USER,
+                wide(RUN_KEY).as_ptr(),
+                wide(NAME).as_ptr(),
+            )
+        };
+        match status {
+            ERROR_SUCCESS => Ok(true),
+            ERROR_FILE_NOT_FOUND => Ok(false),
+            _ => Err(std::io::Error::from_raw_os_error(status as i32))
+                .context("Failed to remove the Run value"),
+        }
+    }
+
+    fn schtasks(args: &[&str]) -> Result<()> {
+        let output = Command::new("schtasks")
+            .args(args)
+            .output()
+            .context("Failed to run schtasks")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    // Quotes `arg` the way programs split their command line back into arguments.
+    fn quote(arg: &str) -> String {
+        if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+            return arg.to_string();
+        }
+        let mut quoted = String::from('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    // Backslashes before a quote are escaped, and so is the quote.
+                    quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                    backslashes = 0;
+                }
+                _ => {
+                    quoted.extend(std::iter::repeat_n('\\', backslashes));
+                    backslashes = 0;
+                }
+            }
+            if c != '\\' {
+                quoted.push(c);
+            }
+        }
+        // So the closing quote isn't escaped.
+        quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+        quoted.push('"');
+        quoted
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::fs;
+
+    // $XDG_CONFIG_HOME/autostart/flatten-string.desktop
+    fn entry_path() -> Result<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME").ok_or_else(|| anyhow!("HOME isn't set"))?)
+                .join(".config"),
+        };
+        Ok(dir.join("autostart").join(format!("{}.desktop", NAME)))
+    }
+
+    pub fn enable(exe: PathBuf, args: &[OsString], _elevated: bool) -> Result<()> {
+        let exec: Vec<String> = std::iter::once(exe.into_os_string())
+            .chain(args.iter().cloned())
+            .map(|arg| quote(&arg.to_string_lossy()))
+            .collect();
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName={}\nComment=Flatten selected text with a hotkey\nExec={}\nTerminal=false\n",
+            NAME,
+            exec.join(" ")
+        );
+        let path = entry_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, entry).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}: {}", path.display(), exec.join(" "));
+        Ok(())
+    }
+
+    pub fn disable() -> Result<()> {
+        let path = entry_path()?;
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}.", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!("No {} to remove.", path.display())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+        }
+        Ok(())
+    }
+
+    // Exec keys take double-quoted arguments, with \ " ` and $ escaped inside. The key's
+    // value is a string as well, whose own escapes are undone first, so each of those
+    // backslashes is doubled again: a literal backslash is written \\\\.
+    pub(super) fn quote(arg: &str) -> String {
+        if !arg.is_empty()
+            && !arg.contains(|c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`%".contains(c))
+        {
+            return arg.to_string();
+        }
+        let mut quoted = String::from('"');
+        for c in arg.chars() {
+            match c {
+                '"' | '`' | '$' => quoted.push_str("\\\\"),
+                '\\' => quoted.push_str("\\\\\\"),
+                _ => {}
+            }
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                // A literal % is written %% in Exec keys.
+                '%' => quoted.push_str("%%"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::fs;
+
+    fn label() -> String {
+        format!("com.github.sloganking.{}", NAME)
+    }
+
+    // ~/Library/LaunchAgents/<label>.plist
+    fn agent_path() -> Result<PathBuf> {
+        let home = env::var_os("HOME").ok_or_else(|| anyhow!("HOME isn't set"))?;
+        Ok(PathBuf::from(home)
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", label())))
+    }
+
+    pub fn enable(exe: PathBuf, args: &[OsString], _elevated: bool) -> Result<()> {
+        let arguments: String = std::iter::once(exe.into_os_string())
+            .chain(args.iter().cloned())
+            .map(|arg| {
+                format!(
+                    "        <string>{}</string>\n",
+                    escape(&arg.to_string_lossy())
+                )
+            })
+            .collect();
+        let agent = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             \x20   <key>Label</key>\n\
+             \x20   <string>{}</string>\n\
+             \x20   <key>ProgramArguments</key>\n\
+             \x20   <array>\n\
+             {}\
+             \x20   </array>\n\
+             \x20   <key>RunAtLoad</key>\n\
+             \x20   <true/>\n\
+             </dict>\n\
+             </plist>\n",
+            label(),
+            arguments
+        );
+        let path = agent_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, agent).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}. It's loaded at the next login.", path.display());
+        Ok(())
+    }
+
+    pub fn disable() -> Result<()> {
+        let path = agent_path()?;
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}.", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!("No {} to remove.", path.display())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+        }
+        Ok(())
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn path_options_become_absolute() {
+        let cwd = env::current_dir().unwrap();
+        let at = |path: &str| cwd.join(path).into_os_string();
+        let made = absolute_paths(args(&[
+            "-c",
+            "my.toml",
+            "--log-file=logs/out.log",
+            "-cother.toml",
+            "--event-log",
+            "/var/events.jsonl",
+            "--pipeline",
+            "flatten",
+        ]));
+        let expected = vec![
+            "--config".into(),
+            at("my.toml"),
+            "--log-file".into(),
+            at("logs/out.log"),
+            "--config".into(),
+            at("other.toml"),
+            "--event-log".into(),
+            "/var/events.jsonl".into(),
+            "--pipeline".into(),
+            "flatten".into(),
+        ];
+        assert_eq!(made, expected);
+    }
+
+    #[test]
+    fn other_args_stay_as_they_are() {
+        let same = args(&[
+            "--join-with",
+            "x.toml",
+            "--plugin-dirs",
+            "--",
+            "--config",
+            "a",
+        ]);
+        assert_eq!(absolute_paths(same.clone()), same);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn desktop_entries_escape_twice() {
+        assert_eq!(platform::quote("plain"), "plain");
+        assert_eq!(platform::quote(r"a\b"), r#""a\\\\b""#);
+        assert_eq!(platform::quote(r#"say "hi" $x"#), r#""say \\"hi\\" \\$x""#);
+        assert_eq!(platform::quote("100%"), r#""100%%""#);
+    }
+}
@@ -0,0 +1,39 @@
+//! Esc-to-cancel for the activation in progress.
+//!
+//! The input hook asks for cancellation; the worker checks between the steps of an
+//! activation and gives up before the next one, so nothing is pasted.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The error an activation stops with once it's been cancelled.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Asks the running activation to stop at its next check.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Forgets an earlier request, for a new activation starting.
+pub fn reset() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Fails with `Cancelled` if cancellation was asked for since the activation started.
+pub fn check() -> Result<(), Cancelled> {
+    match REQUESTED.load(Ordering::SeqCst) {
+        true => Err(Cancelled),
+        false => Ok(()),
+    }
+}
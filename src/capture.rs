@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use rdev::{listen, EventType};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::easy_rdev_key::PTTKey;
+
+/// Waits for the next key press, prints its trigger name and offers to save it to the config.
+pub fn capture_key(config_path: Option<&Path>) -> Result<()> {
+    println!("Press the key you want to use as the trigger...");
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = listen(move |event| {
+            if let EventType::KeyPress(key) = event.event_type {
+                let _ = tx.send(key);
+            }
+        });
+        if let Err(error) = result {
+            log::error!("Failed to set up the global keyboard listener: {:?}", error);
+        }
+    });
+    let key = rx
+        .recv()
+        .map_err(|_| anyhow!("Keyboard listener stopped before a key was pressed"))?;
+
+    let ptt_key = PTTKey::from(key);
+    let Some(value) = ptt_key.to_possible_value() else {
+        println!(
+            "Captured {:?}, which has no trigger name and can't be used as a trigger.",
+            key
+        );
+        return Ok(());
+    };
+    let name = value.get_name();
+    println!("Captured: {}", name);
+    println!("Use it with: --trigger-key {}", name);
+
+    let Some(config_path) = config_path else {
+        println!("Pass --config <FILE> to save it as the trigger_key in a config file.");
+        return Ok(());
+    };
+
+    print!(
+        "Save '{}' as the trigger_key in {}? [y/N] ",
+        name,
+        config_path.display()
+    );
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        println!("Not saved.");
+        return Ok(());
+    }
+
+    save_trigger_key(config_path, name)?;
+    println!("Saved to {}.", config_path.display());
+    Ok(())
+}
+
+/// Sets `trigger_key` in the config file, keeping the rest of the file (and its comments) intact.
+fn save_trigger_key(config_path: &Path, name: &str) -> Result<()> {
+    let contents = match fs::read_to_string(config_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read config file {}", config_path.display()))
+        }
+    };
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .with_context(|| format!("Failed to parse config file {}", config_path.display()))?;
+    document["trigger_key"] = toml_edit::value(name);
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create config directory {}", dir.display()))?;
+    }
+    fs::write(config_path, document.to_string())
+        .with_context(|| format!("Failed to write config file {}", config_path.display()))
+}
@@ -0,0 +1,120 @@
+//! Puts things back when an activation is cut short: releases the keys it pressed and
+//! restores the clipboard it saved.
+//!
+//! That happens after a failed or crashed activation, which the listener survives, and on
+//! the way out when the program panics or is told to stop (Ctrl+C, closing its console,
+//! SIGTERM). Otherwise a crash between pressing and releasing Ctrl leaves it logically held
+//! down system-wide.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::clipboard::{self, Snapshot};
+use crate::injected;
+
+/// The clipboard as it was before the running activation, with `--restore-clipboard`, and
+/// how long to try opening the clipboard to put it back.
+static SAVED: Mutex<Option<(Snapshot, Duration)>> = Mutex::new(None);
+
+/// Holds on to `snapshot` while the activation runs, so it can still be restored if the
+/// activation never gets to.
+pub fn keep_clipboard(snapshot: Snapshot, timeout: Duration) {
+    *saved() = Some((snapshot, timeout));
+}
+
+/// Takes back what `keep_clipboard` held, to restore it as usual.
+pub fn take_clipboard() -> Option<Snapshot> {
+    saved().take().map(|(snapshot, _)| snapshot)
+}
+
+/// Releases any keys still held by us and restores a clipboard saved and never restored.
+pub fn run() {
+    let released = injected::release_held();
+    if released > 0 {
+        println!("Released {} key(s) left pressed.", released);
+    }
+    let Some((snapshot, timeout)) = saved().take() else {
+        return;
+    };
+    let restored =
+        clipboard::system(timeout).and_then(|mut clipboard| clipboard.restore_snapshot(&snapshot));
+    match restored {
+        Ok(()) => println!("Restored original clipboard."),
+        Err(e) => log::error!("Failed to restore the original clipboard: {:?}", e),
+    }
+}
+
+/// Runs `run` before the process ends from a panic or a request to stop. Call it before
+/// starting any threads, which on Unix inherit the blocked signals from this one.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        // Only the keys: the panic may have left the clipboard open on this thread.
+        injected::release_held();
+    }));
+    platform::install();
+}
+
+fn saved() -> std::sync::MutexGuard<'static, Option<(Snapshot, Duration)>> {
+    SAVED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    pub fn install() {
+        if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+            log::warn!(
+                "Keys and the clipboard won't be put back if the console is closed: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    // Runs on a thread of its own. Returning FALSE lets the default handler end the process.
+    unsafe extern "system" fn handler(_event: u32) -> BOOL {
+        super::run();
+        0
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{mem, ptr, thread};
+
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    // Blocks the signals in every thread and waits for them on one of its own, where
+    // cleaning up isn't limited to what a signal handler may do.
+    pub fn install() {
+        let set = unsafe {
+            let mut set: libc::sigset_t = mem::zeroed();
+            libc::sigemptyset(&mut set);
+            for signal in SIGNALS {
+                libc::sigaddset(&mut set, signal);
+            }
+            set
+        };
+        if unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut()) } != 0 {
+            log::warn!("Keys and the clipboard won't be put back on Ctrl+C.");
+            return;
+        }
+        thread::Builder::new()
+            .name("signals".to_string())
+            .spawn(move || {
+                let mut signal = 0;
+                if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                    return;
+                }
+                super::run();
+                // The conventional exit status for death by `signal`.
+                std::process::exit(128 + signal);
+            })
+            .expect("failed to spawn the signal thread");
+    }
+}
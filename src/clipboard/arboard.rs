@@ -0,0 +1,190 @@
+//! The Linux and macOS clipboards, through arboard.
+//!
+//! On Linux that's the X11 CLIPBOARD selection, or on Wayland the regular selection via the
+//! wlr/ext data-control protocol when the compositor offers it. Neither has a sequence
+//! number, so waits for a copy to land fall back to a fixed delay. On macOS it's the general
+//! NSPasteboard, whose change count serves as the sequence number.
+//!
+//! Only text survives a snapshot.
+//!
+//! On Linux the contents are served by the process that set them, so they go when it exits,
+//! unless a clipboard manager takes them over. `serve_text` keeps them past a `--once` run.
+
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+use anyhow::{anyhow, Result};
+#[cfg(target_os = "linux")]
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use std::{mem, ptr};
+
+use super::{Clipboard, Snapshot};
+
+/// How long to wait between attempts when the selection owner doesn't answer.
+const RETRY_DELAY: Duration = Duration::from_millis(20);
+
+pub struct ArboardClipboard {
+    inner: arboard::Clipboard,
+    /// How long to keep retrying while the selection owner is busy.
+    timeout: Duration,
+}
+
+impl ArboardClipboard {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        let inner = arboard::Clipboard::new()
+            .map_err(|e| anyhow!("Clipboard error connecting to the display server: {}", e))?;
+        Ok(ArboardClipboard { inner, timeout })
+    }
+
+    /// Runs `op` until it succeeds, it reports there's nothing to read, or the timeout passes.
+    fn retry<T>(
+        &mut self,
+        mut op: impl FnMut(&mut arboard::Clipboard) -> Result<T, arboard::Error>,
+    ) -> Result<T, arboard::Error> {
+        let start = Instant::now();
+        loop {
+            match op(&mut self.inner) {
+                Err(arboard::Error::ClipboardOccupied) if start.elapsed() < self.timeout => {
+                    crate::stats::clipboard_retried();
+                    thread::sleep(RETRY_DELAY)
+                }
+                Err(arboard::Error::ClipboardOccupied) => {
+                    crate::stats::clipboard_timed_out();
+                    return Err(arboard::Error::ClipboardOccupied);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Clipboard for ArboardClipboard {
+    #[cfg(target_os = "macos")]
+    fn sequence_number(&self) -> Option<u32> {
+        let pasteboard = objc2_app_kit::NSPasteboard::generalPasteboard();
+        Some(pasteboard.changeCount() as u32)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn sequence_number(&self) -> Option<u32> {
+        None
+    }
+
+    fn get_text(&mut self, _lossy: bool) -> Result<String> {
+        // arboard already hands back valid UTF-8, replacing anything malformed.
+        self.retry(|clipboard| clipboard.get_text())
+            .map_err(|e| anyhow!("Clipboard error getting string: {}", e))
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        self.retry(|clipboard| clipboard.set_text(text))
+            .map_err(|e| anyhow!("Clipboard error setting string: {}", e))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_primary_text(&mut self) -> Result<String> {
+        use arboard::{GetExtLinux, LinuxClipboardKind};
+        self.retry(|clipboard| {
+            clipboard
+                .get()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text()
+        })
+        .map_err(|e| anyhow!("Clipboard error getting the primary selection: {}", e))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_primary_text(&mut self, text: &str) -> Result<()> {
+        use arboard::{LinuxClipboardKind, SetExtLinux};
+        self.retry(|clipboard| {
+            clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text)
+        })
+        .map_err(|e| anyhow!("Clipboard error setting the primary selection: {}", e))
+    }
+
+    fn take_snapshot(&mut self) -> Result<Snapshot> {
+        let text = match self.retry(|clipboard| clipboard.get_text()) {
+            Ok(text) => Some(text.encode_utf16().collect()),
+            Err(arboard::Error::ContentNotAvailable) => None,
+            Err(e) => return Err(anyhow!("Clipboard error getting string: {}", e)),
+        };
+        Ok(Snapshot {
+            text,
+            formats: Vec::new(),
+        })
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        match snapshot.text {
+            Some(_) => self.set_text(&snapshot.text(true)?),
+            None => self
+                .retry(|clipboard| clipboard.clear())
+                .map_err(|e| anyhow!("Clipboard error clearing clipboard: {}", e)),
+        }
+    }
+}
+
+/// Sets the clipboard to `text` from a forked copy of this process, which keeps serving it
+/// until something else is copied, and returns once the clipboard has taken it.
+///
+/// No other clipboard connection may be open, since the copy doesn't get its thread.
+#[cfg(target_os = "linux")]
+pub fn serve_text(text: &str, timeout: Duration) -> Result<()> {
+    let (mut reader, writer) = io::pipe().context("Failed to create a pipe")?;
+    match unsafe { libc::fork() } {
+        -1 => Err(anyhow!("Failed to fork: {}", io::Error::last_os_error())),
+        0 => {
+            drop(reader);
+            serve(text, timeout, writer)
+        }
+        _ => {
+            drop(writer);
+            let mut error = String::new();
+            reader
+                .read_to_string(&mut error)
+                .context("Failed to hear back from the process serving the clipboard")?;
+            match error.is_empty() {
+                true => Ok(()),
+                false => Err(anyhow!(error)),
+            }
+        }
+    }
+}
+
+/// The forked side of `serve_text`: says through `ready` whether the clipboard took `text`,
+/// by closing it or by writing the error, then serves `text` until another app owns it.
+#[cfg(target_os = "linux")]
+fn serve(text: &str, timeout: Duration, mut ready: io::PipeWriter) -> ! {
+    unsafe {
+        // A session of its own and no stdio, so neither the terminal's hangup nor a `$(...)`
+        // reading stdout waits on it. The signals cleanup.rs blocks are let through again.
+        libc::setsid();
+        let mut set: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &set, ptr::null_mut());
+        let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+        for fd in 0..3 {
+            libc::dup2(null, fd);
+        }
+    }
+    let set = ArboardClipboard::new(timeout).and_then(|mut clipboard| {
+        clipboard.set_text(text)?;
+        Ok(clipboard)
+    });
+    match set {
+        Ok(mut clipboard) => {
+            drop(ready);
+            use arboard::SetExtLinux;
+            let _ = clipboard.inner.set().wait().text(text);
+        }
+        Err(e) => {
+            let _ = write!(ready, "{:#}", e);
+        }
+    }
+    unsafe { libc::_exit(0) }
+}
@@ -0,0 +1,55 @@
+//! An in-memory clipboard for tests, standing in for the system one.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+
+use super::{Clipboard, Snapshot};
+
+/// A clipboard held in memory. Clones share the same contents, like two handles to the
+/// system clipboard.
+#[derive(Clone, Default)]
+pub struct MemoryClipboard {
+    contents: Arc<Mutex<(Snapshot, u32)>>,
+}
+
+impl MemoryClipboard {
+    fn contents(&self) -> std::sync::MutexGuard<'_, (Snapshot, u32)> {
+        self.contents
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn replace(&self, snapshot: Snapshot) {
+        let mut contents = self.contents();
+        contents.0 = snapshot;
+        contents.1 += 1;
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn sequence_number(&self) -> Option<u32> {
+        Some(self.contents().1)
+    }
+
+    fn get_text(&mut self, lossy: bool) -> Result<String> {
+        self.contents().0.text(lossy)
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        self.replace(Snapshot::default().with_text(text));
+        Ok(())
+    }
+
+    fn text_size(&mut self) -> Result<Option<usize>> {
+        Ok(self.contents().0.text.as_ref().map(Vec::len))
+    }
+
+    fn take_snapshot(&mut self) -> Result<Snapshot> {
+        Ok(self.contents().0.clone())
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        self.replace(snapshot.clone());
+        Ok(())
+    }
+}
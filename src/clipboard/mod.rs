@@ -0,0 +1,285 @@
+//! Clipboard access behind a platform-neutral `Clipboard` trait.
+
+use anyhow::{anyhow, Result};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub use self::windows::WindowsClipboard;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod arboard;
+#[cfg(target_os = "linux")]
+pub use self::arboard::serve_text;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub use self::arboard::ArboardClipboard;
+
+#[cfg(test)]
+mod memory;
+#[cfg(test)]
+pub use self::memory::MemoryClipboard;
+
+/// How often the clipboard sequence number is polled while waiting for a change.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A system clipboard, or `MemoryClipboard` in tests.
+pub trait Clipboard: Send {
+    /// A number the system bumps every time the clipboard's contents change, if it has one.
+    fn sequence_number(&self) -> Option<u32>;
+
+    /// The clipboard's text. See `decode_text` for what `lossy` does.
+    fn get_text(&mut self, lossy: bool) -> Result<String>;
+
+    /// Replaces the clipboard contents with `text`.
+    fn set_text(&mut self, text: &str) -> Result<()>;
+
+    /// How many bytes the clipboard's text takes at least in UTF-8, if that can be told
+    /// without reading it. The text read may be larger.
+    fn text_size(&mut self) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Copies every format on the clipboard that the backend can read.
+    fn take_snapshot(&mut self) -> Result<Snapshot>;
+
+    /// Replaces the clipboard contents with `snapshot`.
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<()>;
+
+    /// The text of the primary selection, i.e. whatever is highlighted right now.
+    fn get_primary_text(&mut self) -> Result<String> {
+        Err(anyhow!("This platform has no primary selection"))
+    }
+
+    /// Replaces the primary selection with `text`, for pasting with a middle click.
+    fn set_primary_text(&mut self, _text: &str) -> Result<()> {
+        Err(anyhow!("This platform has no primary selection"))
+    }
+
+    /// Whether the app that copied the contents marked them as a secret, as password
+    /// managers do so clipboard history and sync tools leave them alone.
+    fn is_concealed(&mut self) -> bool {
+        false
+    }
+
+    /// The format id the backend stores CF_HTML-style HTML under, if it has one.
+    fn html_format(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// The clipboard backend for the platform this was built for.
+#[cfg(windows)]
+pub fn system(timeout: Duration) -> Result<Box<dyn Clipboard>> {
+    Ok(Box::new(WindowsClipboard::new(timeout)))
+}
+
+/// The clipboard backend for the platform this was built for.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn system(timeout: Duration) -> Result<Box<dyn Clipboard>> {
+    Ok(Box::new(ArboardClipboard::new(timeout)?))
+}
+
+/// Waits until the clipboard changes from `before` (a `sequence_number()`), or `timeout` passes.
+///
+/// Returns whether the change was seen. Without a sequence number this sleeps for `fallback`
+/// instead and optimistically returns true.
+pub fn wait_for_change(
+    clipboard: &dyn Clipboard,
+    before: Option<u32>,
+    timeout: Duration,
+    fallback: Duration,
+) -> bool {
+    let Some(before) = before else {
+        log::debug!("No sequence number to watch; waiting {:?}", fallback);
+        thread::sleep(fallback);
+        return true;
+    };
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if let Some(now) = clipboard.sequence_number().filter(|&now| now != before) {
+            log::debug!(
+                "Clipboard changed ({} -> {}) after {:?}",
+                before,
+                now,
+                start.elapsed()
+            );
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    log::debug!("Clipboard stayed at {} for {:?}", before, timeout);
+    false
+}
+
+/// A copy of the clipboard: its text plus any other formats the backend could read.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    /// The text as UTF-16 without its terminator, kept undecoded so malformed text survives
+    /// a round trip.
+    text: Option<Vec<u16>>,
+    /// Every other format, keyed by the backend's format ids.
+    formats: Vec<(u32, Vec<u8>)>,
+}
+
+impl Snapshot {
+    /// The text, or an empty string if there is none.
+    pub fn text(&self, lossy: bool) -> Result<String> {
+        match &self.text {
+            Some(units) => decode_text(units, lossy),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// A copy with the text replaced by `text` and every other format kept as is.
+    pub fn with_text(&self, text: &str) -> Snapshot {
+        Snapshot {
+            text: Some(text.encode_utf16().collect()),
+            formats: self.formats.clone(),
+        }
+    }
+
+    /// Replaces (or adds) the data for `format`.
+    pub fn set(&mut self, format: u32, data: Vec<u8>) {
+        match self.formats.iter_mut().find(|(f, _)| *f == format) {
+            Some((_, existing)) => *existing = data,
+            None => self.formats.push((format, data)),
+        }
+    }
+
+    pub fn get(&self, format: u32) -> Option<&[u8]> {
+        self.formats
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, data)| data.as_slice())
+    }
+}
+
+/// Decodes clipboard UTF-16, which ends at the first NUL: CF_UNICODETEXT is NUL-terminated,
+/// and the buffer holding it is often rounded up with junk after the terminator.
+///
+/// Some apps put malformed text on the clipboard with lone surrogates. Those are errors
+/// unless `lossy` is set, in which case they become U+FFFD.
+pub fn decode_text(units: &[u16], lossy: bool) -> Result<String> {
+    let units = match units.iter().position(|unit| *unit == 0) {
+        Some(end) => &units[..end],
+        None => units,
+    };
+
+    let mut text = String::with_capacity(units.len());
+    for (at, decoded) in char::decode_utf16(units.iter().copied()).enumerate() {
+        match decoded {
+            Ok(c) => text.push(c),
+            Err(_) if lossy => text.push(char::REPLACEMENT_CHARACTER),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Clipboard text has a lone surrogate {:#06x} at character {} (use --lossy-utf16 to replace it)",
+                    e.unpaired_surrogate(),
+                    at
+                ))
+            }
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn decodes_surrogate_pairs() {
+        let units = utf16("emoji 😀 and 𝄞");
+        assert_eq!(decode_text(&units, false).unwrap(), "emoji 😀 and 𝄞");
+    }
+
+    #[test]
+    fn decodes_empty_input() {
+        assert_eq!(decode_text(&[], false).unwrap(), "");
+        assert_eq!(decode_text(&[0], false).unwrap(), "");
+    }
+
+    #[test]
+    fn stops_at_the_terminator() {
+        let mut units = utf16("text");
+        units.extend([0, 0, 0]);
+        assert_eq!(decode_text(&units, false).unwrap(), "text");
+    }
+
+    #[test]
+    fn ignores_junk_after_the_terminator() {
+        let mut units = utf16("text");
+        units.push(0);
+        units.extend(utf16("junk"));
+        units.push(0xD800);
+        assert_eq!(decode_text(&units, false).unwrap(), "text");
+    }
+
+    #[test]
+    fn rejects_lone_surrogates_unless_lossy() {
+        for lone in [0xD800, 0xDC00] {
+            let mut units = utf16("a");
+            units.push(lone);
+            units.extend(utf16("b"));
+            assert!(decode_text(&units, false).is_err());
+            assert_eq!(decode_text(&units, true).unwrap(), "a\u{FFFD}b");
+        }
+    }
+
+    #[test]
+    fn rejects_a_high_surrogate_at_the_end() {
+        let mut units = utf16("a");
+        units.extend([0xD83D, 0]);
+        assert!(decode_text(&units, false).is_err());
+        assert_eq!(decode_text(&units, true).unwrap(), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn round_trips_text() {
+        let mut clipboard = MemoryClipboard::default();
+        clipboard.set_text("héllo 😀").unwrap();
+        assert_eq!(clipboard.get_text(false).unwrap(), "héllo 😀");
+    }
+
+    #[test]
+    fn round_trips_snapshots_with_other_formats() {
+        let mut clipboard = MemoryClipboard::default();
+        let mut copied = Snapshot::default().with_text("original");
+        copied.set(49_000, b"<b>original</b>".to_vec());
+        clipboard.restore_snapshot(&copied).unwrap();
+
+        let snapshot = clipboard.take_snapshot().unwrap();
+        clipboard
+            .restore_snapshot(&snapshot.with_text("changed"))
+            .unwrap();
+        assert_eq!(clipboard.get_text(false).unwrap(), "changed");
+        let changed = clipboard.take_snapshot().unwrap();
+        assert_eq!(changed.get(49_000), Some(&b"<b>original</b>"[..]));
+
+        clipboard.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(clipboard.get_text(false).unwrap(), "original");
+    }
+
+    #[test]
+    fn waits_for_a_change() {
+        let clipboard = MemoryClipboard::default();
+        let before = clipboard.sequence_number();
+        let (timeout, fallback) = (Duration::from_millis(50), Duration::ZERO);
+        assert!(!wait_for_change(&clipboard, before, timeout, fallback));
+
+        let mut other = clipboard.clone();
+        let writer = thread::spawn(move || other.set_text("new").unwrap());
+        assert!(wait_for_change(
+            &clipboard,
+            before,
+            Duration::from_secs(5),
+            fallback
+        ));
+        writer.join().unwrap();
+    }
+}
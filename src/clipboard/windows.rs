@@ -0,0 +1,160 @@
+//! The Win32 clipboard, through clipboard-win.
+
+use anyhow::{anyhow, Result};
+use clipboard_win::{formats, raw, Clipboard as OpenClipboard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{decode_text, Clipboard, Snapshot};
+
+/// GDI-handle formats hold no clipboard memory to copy. Windows synthesizes the bitmap and
+/// metafile ones from their memory-backed siblings (e.g. CF_BITMAP from CF_DIB).
+const HANDLE_FORMATS: &[u32] = &[
+    formats::CF_BITMAP,
+    formats::CF_METAFILEPICT,
+    formats::CF_PALETTE,
+    formats::CF_ENHMETAFILE,
+    formats::CF_OWNERDISPLAY,
+    formats::CF_DSPBITMAP,
+    formats::CF_DSPMETAFILEPICT,
+    formats::CF_DSPENHMETAFILE,
+];
+
+/// ANSI text formats that Windows synthesizes from CF_UNICODETEXT. Keeping their old contents
+/// next to transformed Unicode text would hand some apps the untransformed text.
+const SYNTHESIZED_TEXT_FORMATS: &[u32] = &[formats::CF_TEXT, formats::CF_OEMTEXT];
+
+/// Formats whose presence marks the clipboard contents as a secret.
+const CONCEALED_FORMATS: &[&str] = &[
+    "ExcludeClipboardContentFromMonitorProcessing",
+    "Clipboard Viewer Ignore",
+];
+
+/// The first wait between attempts to open the clipboard; it doubles up to `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(5);
+
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+pub struct WindowsClipboard {
+    /// How long to keep retrying while another app has the clipboard open.
+    timeout: Duration,
+}
+
+impl WindowsClipboard {
+    pub fn new(timeout: Duration) -> Self {
+        WindowsClipboard { timeout }
+    }
+
+    /// Opens the clipboard, retrying with exponential backoff while another app holds it.
+    ///
+    /// Gives up with the last error once the timeout has passed.
+    fn open(&self) -> Result<OpenClipboard> {
+        let start = Instant::now();
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match OpenClipboard::new() {
+                Ok(clipboard) => return Ok(clipboard),
+                Err(e) if start.elapsed() >= self.timeout => {
+                    crate::stats::clipboard_timed_out();
+                    return Err(anyhow!(
+                        "Clipboard error opening clipboard (gave up after {:?}): {}",
+                        self.timeout,
+                        e
+                    ));
+                }
+                Err(_) => {
+                    crate::stats::clipboard_retried();
+                    thread::sleep(backoff.min(self.timeout.saturating_sub(start.elapsed())));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
+impl Clipboard for WindowsClipboard {
+    fn sequence_number(&self) -> Option<u32> {
+        raw::seq_num().map(|n| n.get())
+    }
+
+    fn get_text(&mut self, lossy: bool) -> Result<String> {
+        let _clipboard = self.open()?;
+        let mut data = Vec::new();
+        raw::get_vec(formats::CF_UNICODETEXT, &mut data)
+            .map_err(|e| anyhow!("Clipboard error getting string: {}", e))?;
+        decode_text(&to_units(&data), lossy)
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        let _clipboard = self.open()?;
+        clipboard_win::set(formats::Unicode, text)
+            .map_err(|e| anyhow!("Clipboard error setting string: {}", e))
+    }
+
+    fn text_size(&mut self) -> Result<Option<usize>> {
+        let _clipboard = self.open()?;
+        // Bytes of UTF-16 with a NUL at the end. Every unit takes at least one byte in UTF-8.
+        Ok(raw::size(formats::CF_UNICODETEXT).map(|size| (size.get() / 2).saturating_sub(1)))
+    }
+
+    fn take_snapshot(&mut self) -> Result<Snapshot> {
+        let _clipboard = self.open()?;
+
+        let mut text = None;
+        let mut formats = Vec::new();
+        for format in raw::EnumFormats::new() {
+            let is_handle = HANDLE_FORMATS.contains(&format)
+                || (formats::CF_GDIOBJFIRST..=formats::CF_GDIOBJLAST).contains(&format);
+            if is_handle || SYNTHESIZED_TEXT_FORMATS.contains(&format) {
+                continue;
+            }
+            let mut data = Vec::new();
+            // Formats promised with delayed rendering can fail to render; skip those.
+            if raw::get_vec(format, &mut data).is_err() {
+                continue;
+            }
+            match format {
+                formats::CF_UNICODETEXT => text = Some(to_units(&data)),
+                _ => formats.push((format, data)),
+            }
+        }
+        Ok(Snapshot { text, formats })
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let _clipboard = self.open()?;
+        raw::empty().map_err(|e| anyhow!("Clipboard error emptying clipboard: {}", e))?;
+        if let Some(units) = &snapshot.text {
+            let unicode: Vec<u8> = units
+                .iter()
+                .chain(std::iter::once(&0))
+                .flat_map(|unit| unit.to_ne_bytes())
+                .collect();
+            raw::set_without_clear(formats::CF_UNICODETEXT, &unicode)
+                .map_err(|e| anyhow!("Clipboard error setting string: {}", e))?;
+        }
+        for (format, data) in &snapshot.formats {
+            raw::set_without_clear(*format, data)
+                .map_err(|e| anyhow!("Clipboard error setting format {}: {}", format, e))?;
+        }
+        Ok(())
+    }
+
+    fn is_concealed(&mut self) -> bool {
+        // Set by KeePass, 1Password, Bitwarden and the like; checking needs no open clipboard.
+        CONCEALED_FORMATS.iter().any(|name| {
+            raw::register_format(name).is_some_and(|format| raw::is_format_avail(format.get()))
+        })
+    }
+
+    fn html_format(&self) -> Option<u32> {
+        formats::Html::new().map(|html| html.code())
+    }
+}
+
+/// CF_UNICODETEXT bytes are native-endian UTF-16.
+fn to_units(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+        .collect()
+}
@@ -0,0 +1,122 @@
+//! `[[command]]` steps: pipeline steps that pipe the text through an external program.
+
+use anyhow::{anyhow, Result};
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::CommandStep;
+use strflatten::transform::{Transform, TransformStep};
+
+/// How often a running command is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Runs a `[[command]]` with the text on its stdin and takes its stdout as the result. A
+/// command that fails or runs out of time leaves the text unchanged.
+pub struct ExternalCommand {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl ExternalCommand {
+    pub fn new(step: &CommandStep) -> Result<Self> {
+        if TransformStep::from_name(&step.name).is_builtin() {
+            return Err(anyhow!(
+                "[[command]] {:?} is named like a built-in step",
+                step.name
+            ));
+        }
+        let Some((program, args)) = step.run.split_first() else {
+            return Err(anyhow!("[[command]] {:?} has nothing to run", step.name));
+        };
+        Ok(ExternalCommand {
+            name: step.name.clone(),
+            program: program.clone(),
+            args: args.to_vec(),
+            timeout: Duration::from_millis(step.timeout),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self, text: &str) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start {:?}: {}", self.program, e))?;
+
+        // Feed stdin and drain the outputs on their own threads, or a command that writes
+        // before it has read everything could block on a full pipe forever.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = text.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let stdout = read_in_background(child.stdout.take().expect("stdout is piped"));
+        let stderr = read_in_background(child.stderr.take().expect("stderr is piped"));
+
+        let status = wait_timeout(&mut child, self.timeout)?;
+        let stderr = String::from_utf8_lossy(&stderr.join().unwrap_or_default()).into_owned();
+        if !status.success() {
+            let detail = stderr.lines().next().unwrap_or("no error output");
+            return Err(anyhow!("It exited with {} ({})", status, detail));
+        }
+        let output = String::from_utf8(stdout.join().unwrap_or_default())
+            .map_err(|_| anyhow!("Its output isn't valid UTF-8"))?;
+        // Like a shell's $(...), drop the newline most tools end their output with.
+        match text.ends_with('\n') {
+            true => Ok(output),
+            false => Ok(output
+                .strip_suffix('\n')
+                .map(|output| output.strip_suffix('\r').unwrap_or(output))
+                .unwrap_or(&output)
+                .to_string()),
+        }
+    }
+}
+
+impl Transform for ExternalCommand {
+    fn apply(&self, text: &str) -> String {
+        self.run(text).unwrap_or_else(|e| {
+            log::warn!(
+                "[[command]] {:?} failed, leaving the text unchanged: {}",
+                self.name,
+                e
+            );
+            text.to_string()
+        })
+    }
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+// Waits for `child` to exit, killing it once `timeout` has passed.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<std::process::ExitStatus> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "It didn't finish within {:?} and was stopped",
+                timeout
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,504 @@
+//! Shell completion scripts, generated from the clap command so they cover every option.
+//!
+//! Key, pipeline and profile names aren't baked into the scripts: they call the hidden
+//! `complete` command at completion time, so they follow the config file as it changes.
+
+use clap::builder::StyledStr;
+use clap::{Arg, ValueHint};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::{self, Config};
+use crate::list_keys;
+
+/// The shells `completions` can write a script for.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// What the hidden `complete` command lists.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Names {
+    /// Hotkeys: modifiers and trigger key names, after whatever chord is typed so far
+    Keys,
+    /// The `[pipelines]` from the config file
+    Pipelines,
+    /// The `[[profile]]` names from the config file
+    Profiles,
+}
+
+impl Names {
+    fn as_str(self) -> &'static str {
+        match self {
+            Names::Keys => "keys",
+            Names::Pipelines => "pipelines",
+            Names::Profiles => "profiles",
+        }
+    }
+}
+
+/// What an option's value completes to.
+enum Values {
+    /// The option is a switch and takes no value.
+    None,
+    Fixed(Vec<String>),
+    Dynamic(Names),
+    Path,
+    /// Free text, with nothing to suggest.
+    Any,
+}
+
+fn values(arg: &Arg) -> Values {
+    if !arg.get_action().takes_values() {
+        return Values::None;
+    }
+    let possible: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !possible.is_empty() {
+        return Values::Fixed(possible);
+    }
+    let is_hotkey = arg
+        .get_value_names()
+        .is_some_and(|names| names.iter().any(|name| name == "HOTKEY"));
+    match arg.get_id().as_str() {
+        _ if is_hotkey => Values::Dynamic(Names::Keys),
+        "named_pipeline" => Values::Dynamic(Names::Pipelines),
+        "profile" => Values::Dynamic(Names::Profiles),
+        _ if arg.get_value_hint() == ValueHint::AnyPath => Values::Path,
+        _ => Values::Any,
+    }
+}
+
+/// The options of `cmd` that completion offers, globals included.
+fn options(cmd: &clap::Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+}
+
+/// The subcommands of `cmd`, leaving out clap's `help`, which only repeats the others.
+fn subcommands(cmd: &clap::Command) -> impl Iterator<Item = &clap::Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+/// The fixed values of `cmd`'s positional arguments, e.g. the shells of `completions`.
+fn positional_values(cmd: &clap::Command) -> Vec<String> {
+    cmd.get_positionals()
+        .flat_map(|arg| match values(arg) {
+            Values::Fixed(names) => names,
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Every visible `(path, command)` below and including `cmd`, with paths like `/config/init`.
+fn walk<'a>(path: String, cmd: &'a clap::Command, out: &mut Vec<(String, &'a clap::Command)>) {
+    for sub in subcommands(cmd) {
+        walk(format!("{}/{}", path, sub.get_name()), sub, out);
+    }
+    out.push((path, cmd));
+}
+
+/// `-t --trigger-key`, as written on the command line.
+fn flags(arg: &Arg) -> Vec<String> {
+    let short = arg.get_short().map(|c| format!("-{}", c));
+    let long = arg.get_long().map(|name| format!("--{}", name));
+    short.into_iter().chain(long).collect()
+}
+
+/// The first sentence of a help text, for the shells that show descriptions.
+fn summary(help: Option<&StyledStr>) -> String {
+    let help = help.map(|help| help.to_string()).unwrap_or_default();
+    // "e.g. F9" doesn't end a sentence.
+    let end = help
+        .match_indices(". ")
+        .map(|(i, _)| i)
+        .find(|&i| !help[..i].ends_with("e.g") && !help[..i].ends_with("i.e"))
+        .unwrap_or(help.len());
+    help[..end].trim_end_matches('.').to_string()
+}
+
+/// Prints the completion script for `shell`.
+pub fn print_script(mut cmd: clap::Command, shell: Shell) {
+    // Building propagates the global options into every subcommand.
+    cmd.build();
+    let script = match shell {
+        Shell::Bash => bash(&cmd),
+        Shell::Zsh => zsh(&cmd),
+        Shell::Fish => fish(&cmd),
+        Shell::Powershell => powershell(&cmd),
+    };
+    print!("{}", script);
+}
+
+/// Prints the names starting with `prefix`, one per line, for the completion scripts.
+pub fn print_names(names: Names, prefix: &str, config_path: Option<&Path>) {
+    let load = || -> Option<Config> {
+        let path = config_path
+            .map(Path::to_path_buf)
+            .or_else(|| config::default_path().filter(|path| path.exists()))?;
+        Config::load(&path).ok()
+    };
+    let candidates: Vec<String> = match names {
+        Names::Keys => {
+            // Complete the last part of a chord, keeping the modifiers typed before it.
+            let head = match prefix.rsplit_once('+') {
+                Some((modifiers, _)) => format!("{}+", modifiers),
+                None => String::new(),
+            };
+            let unused_modifiers = ["Ctrl", "Shift", "Alt", "Meta"].into_iter().filter(|m| {
+                !head
+                    .to_ascii_lowercase()
+                    .contains(&format!("{}+", m.to_ascii_lowercase()))
+            });
+            unused_modifiers
+                .map(|modifier| format!("{}{}+", head, modifier))
+                .chain(
+                    list_keys::key_names()
+                        .into_iter()
+                        .map(|(_, name)| format!("{}{}", head, name)),
+                )
+                .collect()
+        }
+        Names::Pipelines => load()
+            .map(|config| config.pipelines.into_keys().collect())
+            .unwrap_or_default(),
+        Names::Profiles => load()
+            .map(|config| config.profiles.into_iter().map(|p| p.name).collect())
+            .unwrap_or_default(),
+    };
+    for candidate in candidates.iter().filter(|c| c.starts_with(prefix)) {
+        println!("{}", candidate);
+    }
+}
+
+// --- Bash ---
+
+fn bash(cmd: &clap::Command) -> String {
+    let bin = cmd.get_name();
+    let mut commands = Vec::new();
+    walk(String::new(), cmd, &mut commands);
+
+    let mut s = String::new();
+    let _ = writeln!(s, "# bash completion for {}", bin);
+    let _ = writeln!(s, "_{}() {{", bin);
+    s.push_str(
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    s.push_str("    case \"$prev\" in\n");
+    for arg in options(cmd) {
+        let values = match values(arg) {
+            Values::None => continue,
+            Values::Fixed(names) => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                names.join(" ")
+            ),
+            Values::Dynamic(names) => {
+                let mut line = format!(
+                    "mapfile -t COMPREPLY < <({} complete {} -- \"$cur\" 2>/dev/null)",
+                    bin,
+                    names.as_str()
+                );
+                if let Names::Keys = names {
+                    line.push_str("; [[ ${COMPREPLY[0]} == *+ ]] && compopt -o nospace");
+                }
+                line
+            }
+            Values::Path => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            Values::Any => "COMPREPLY=()".to_string(),
+        };
+        let _ = writeln!(
+            s,
+            "        {})\n            {}\n            return ;;",
+            flags(arg).join("|"),
+            values
+        );
+    }
+    s.push_str("    esac\n\n");
+
+    // Follow the subcommands typed so far to find the one being completed.
+    s.push_str("    local path=\"\" word\n");
+    s.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    s.push_str("        case \"$path/$word\" in\n");
+    for (path, _) in commands.iter().filter(|(path, _)| !path.is_empty()) {
+        let _ = writeln!(s, "            \"{0}\") path=\"{0}\" ;;", path);
+    }
+    s.push_str("        esac\n    done\n\n");
+
+    s.push_str("    local words\n    case \"$path\" in\n");
+    for (path, command) in &commands {
+        let words: Vec<String> = subcommands(command)
+            .map(|sub| sub.get_name().to_string())
+            .chain(positional_values(command))
+            .chain(options(command).flat_map(flags))
+            .collect();
+        let _ = writeln!(s, "        \"{}\") words=\"{}\" ;;", path, words.join(" "));
+    }
+    s.push_str("    esac\n");
+    s.push_str("    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n");
+    s.push_str("}\n");
+    let _ = writeln!(s, "complete -F _{0} {0}", bin);
+    s
+}
+
+// --- Zsh ---
+
+/// Escapes text for a single-quoted `_arguments` spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_function(path: &str, bin: &str) -> String {
+    format!("_{}{}", bin, path.replace(['/', '-'], "_"))
+}
+
+fn zsh(cmd: &clap::Command) -> String {
+    let bin = cmd.get_name();
+    let mut commands = Vec::new();
+    walk(String::new(), cmd, &mut commands);
+
+    let mut s = String::new();
+    let _ = writeln!(s, "#compdef {}\n", bin);
+    let _ = writeln!(s, "_{}_names() {{", bin);
+    s.push_str("    local -a names\n");
+    let _ = writeln!(
+        s,
+        "    names=(${{(f)\"$({} complete $1 -- \"$PREFIX\" 2>/dev/null)\"}})",
+        bin
+    );
+    s.push_str("    compadd -Q -S '' -- ${(M)names:#*+}\n");
+    s.push_str("    compadd -Q -- ${names:#*+}\n");
+    s.push_str("}\n");
+
+    for (path, command) in &commands {
+        let _ = writeln!(s, "\n{}() {{", zsh_function(path, bin));
+        s.push_str("    local curcontext=\"$curcontext\" state line\n");
+        s.push_str("    _arguments -C \\\n");
+        for arg in options(command) {
+            let flags = flags(arg);
+            let spec = match flags.as_slice() {
+                [one] => one.clone(),
+                many => format!("{{{}}}", many.join(",")),
+            };
+            let help = zsh_escape(&summary(arg.get_help()));
+            let action = match values(arg) {
+                Values::None => String::new(),
+                Values::Fixed(names) => format!(":value:({})", names.join(" ")),
+                Values::Dynamic(names) => format!(":name:_{}_names {}", bin, names.as_str()),
+                Values::Path => ":file:_files".to_string(),
+                Values::Any => ":value: ".to_string(),
+            };
+            let repeat = match arg.get_action() {
+                clap::ArgAction::Append => "*",
+                _ => "",
+            };
+            let _ = writeln!(s, "        {}{}'[{}]{}' \\", repeat, spec, help, action);
+        }
+        let subs: Vec<String> = subcommands(command)
+            .map(|sub| {
+                format!(
+                    "{}\\:\"{}\"",
+                    sub.get_name(),
+                    zsh_escape(&summary(sub.get_about())).replace('"', "\\\"")
+                )
+            })
+            .collect();
+        if subs.is_empty() {
+            match positional_values(command).as_slice() {
+                [] => s.push_str("        '*::arg: '\n"),
+                names => {
+                    let _ = writeln!(s, "        ':value:({})'", names.join(" "));
+                }
+            }
+            s.push_str("}\n");
+            continue;
+        }
+        let _ = writeln!(s, "        ': :(({}))' \\", subs.join(" "));
+        s.push_str("        '*:: :->subcommand'\n");
+        s.push_str("    case $state in\n        subcommand)\n            case $line[1] in\n");
+        for sub in subcommands(command) {
+            let sub_path = format!("{}/{}", path, sub.get_name());
+            let _ = writeln!(
+                s,
+                "                {}) {} ;;",
+                sub.get_name(),
+                zsh_function(&sub_path, bin)
+            );
+        }
+        s.push_str("            esac ;;\n    esac\n}\n");
+    }
+    let _ = writeln!(s, "\n_{} \"$@\"", bin);
+    s
+}
+
+// --- Fish ---
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(cmd: &clap::Command) -> String {
+    let bin = cmd.get_name();
+    let mut commands = Vec::new();
+    walk(String::new(), cmd, &mut commands);
+
+    let mut s = String::new();
+    let _ = writeln!(s, "# fish completion for {}", bin);
+    let _ = writeln!(s, "complete -c {} -f", bin);
+    for (path, command) in commands.iter().rev() {
+        let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+        let subs: Vec<&str> = subcommands(command).map(|sub| sub.get_name()).collect();
+        // Options belong to the innermost subcommand typed; subcommands to their parent.
+        let here = match names.last() {
+            Some(name) => format!("__fish_seen_subcommand_from {}", name),
+            None => "true".to_string(),
+        };
+        let before_subs = match (names.is_empty(), subs.is_empty()) {
+            (true, _) => "__fish_use_subcommand".to_string(),
+            (false, true) => here.clone(),
+            (false, false) => format!(
+                "{}; and not __fish_seen_subcommand_from {}",
+                here,
+                subs.join(" ")
+            ),
+        };
+
+        for sub in subcommands(command) {
+            let _ = writeln!(
+                s,
+                "complete -c {} -n '{}' -a {} -d '{}'",
+                bin,
+                before_subs,
+                sub.get_name(),
+                fish_escape(&summary(sub.get_about()))
+            );
+        }
+        let positionals = positional_values(command);
+        if !positionals.is_empty() {
+            let _ = writeln!(
+                s,
+                "complete -c {} -n '{}' -a '{}'",
+                bin,
+                here,
+                positionals.join(" ")
+            );
+        }
+        for arg in options(command) {
+            // Globals are offered once, everywhere.
+            if arg.is_global_set() && !names.is_empty() {
+                continue;
+            }
+            let mut line = format!("complete -c {}", bin);
+            if !names.is_empty() {
+                let _ = write!(line, " -n '{}'", here);
+            }
+            if let Some(short) = arg.get_short() {
+                let _ = write!(line, " -s {}", short);
+            }
+            if let Some(long) = arg.get_long() {
+                let _ = write!(line, " -l {}", long);
+            }
+            match values(arg) {
+                Values::None => {}
+                Values::Fixed(names) => {
+                    let _ = write!(line, " -r -a '{}'", names.join(" "));
+                }
+                Values::Dynamic(names) => {
+                    let _ = write!(
+                        line,
+                        " -r -a '({} complete {} -- (commandline -ct))'",
+                        bin,
+                        names.as_str()
+                    );
+                }
+                Values::Path => line.push_str(" -r -F"),
+                Values::Any => line.push_str(" -r"),
+            }
+            let _ = writeln!(line, " -d '{}'", fish_escape(&summary(arg.get_help())));
+            s.push_str(&line);
+        }
+    }
+    s
+}
+
+// --- PowerShell ---
+
+fn powershell(cmd: &clap::Command) -> String {
+    let bin = cmd.get_name();
+    let mut commands = Vec::new();
+    walk(String::new(), cmd, &mut commands);
+
+    let mut s = String::new();
+    let _ = writeln!(s, "# PowerShell completion for {}", bin);
+    let _ = writeln!(
+        s,
+        "Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{",
+        bin
+    );
+    s.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    s.push_str("    $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object { $_.ToString() })\n");
+    s.push_str("    if ($wordToComplete -and $words.Count -gt 0) { $words = @($words | Select-Object -SkipLast 1) }\n");
+    s.push_str("    $path = ''\n    $prev = ''\n");
+    s.push_str("    foreach ($word in $words) {\n        switch (\"$path/$word\") {\n");
+    for (path, _) in commands.iter().filter(|(path, _)| !path.is_empty()) {
+        let _ = writeln!(s, "            '{0}' {{ $path = '{0}' }}", path);
+    }
+    s.push_str("        }\n        $prev = $word\n    }\n\n");
+
+    s.push_str("    $candidates = switch ($prev) {\n");
+    for arg in options(cmd) {
+        let values = match values(arg) {
+            Values::None | Values::Path | Values::Any => continue,
+            Values::Fixed(names) => names
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Values::Dynamic(names) => {
+                format!("{} complete {} -- $wordToComplete", bin, names.as_str())
+            }
+        };
+        let cases: Vec<String> = flags(arg)
+            .iter()
+            .map(|flag| format!("'{}'", flag))
+            .collect();
+        for case in cases {
+            let _ = writeln!(s, "        {} {{ {}; break }}", case, values);
+        }
+    }
+    s.push_str("        default {\n            switch ($path) {\n");
+    for (path, command) in &commands {
+        let words: Vec<String> = subcommands(command)
+            .map(|sub| format!("'{}'", sub.get_name()))
+            .chain(
+                positional_values(command)
+                    .into_iter()
+                    .map(|name| format!("'{}'", name)),
+            )
+            .chain(
+                options(command)
+                    .flat_map(flags)
+                    .map(|flag| format!("'{}'", flag)),
+            )
+            .collect();
+        let _ = writeln!(s, "                '{}' {{ {} }}", path, words.join(", "));
+    }
+    s.push_str("            }\n        }\n    }\n");
+    s.push_str(
+        "    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n",
+    );
+    s.push_str("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n");
+    s.push_str("    }\n}\n");
+    s
+}
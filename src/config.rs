@@ -0,0 +1,662 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command::ExternalCommand;
+use crate::hotkey::Hotkey;
+use crate::input::PasteShortcut;
+use crate::script::Script;
+use crate::HotkeyBackend;
+use strflatten::transform::{ReplaceRule, TransformStep};
+
+/// Contents of the `--config` TOML file, or of `config.toml` in the default location.
+///
+/// Every option below is named after its command-line flag and only used when neither that flag
+/// nor its `FLATTEN_STRING_*` environment variable is set.
+///
+/// ```toml
+/// trigger_key = "Ctrl+Shift+J"
+/// pipeline = ["trim", "dehyphenate", "flatten"]
+/// join_with = " "
+/// copy_timeout = 1500
+/// restore_clipboard = true
+///
+/// [pipelines]
+/// email_cleanup = ["dehyphenate", "flatten", "collapse-spaces"]
+/// ```
+///
+/// Anywhere a pipeline goes, the name of a `[pipelines]` entry can stand in for its steps,
+/// either on its own (`pipeline = "email_cleanup"`) or mixed with other steps
+/// (`pipeline = ["trim", "email_cleanup"]`).
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Used with `--pipeline` when `--trigger-key` isn't given.
+    pub trigger_key: Option<Hotkey>,
+
+    pub join_with: Option<String>,
+    pub pipeline: Option<Vec<TransformStep>>,
+    pub collapse_spaces: Option<bool>,
+    pub history_key: Option<Hotkey>,
+    pub undo_key: Option<Hotkey>,
+    pub clipboard_key: Option<Hotkey>,
+    pub history_size: Option<usize>,
+    pub save_history: Option<bool>,
+    pub double_tap: Option<u64>,
+    pub grab: Option<bool>,
+    pub cooldown: Option<u64>,
+    pub hotkey_backend: Option<HotkeyBackend>,
+    pub copy_timeout: Option<u64>,
+    pub clipboard_timeout: Option<u64>,
+    pub settle_delay: Option<u64>,
+    pub paste_delay: Option<u64>,
+    pub key_delay: Option<u64>,
+    pub adaptive_timeout: Option<bool>,
+    pub max_size: Option<u64>,
+    pub lossy_utf16: Option<bool>,
+    pub restore_clipboard: Option<bool>,
+    pub restore_delay: Option<u64>,
+    pub preserve_formats: Option<bool>,
+    pub transform_html: Option<bool>,
+    pub ignore_app: Option<Vec<String>>,
+    pub only_app: Option<Vec<String>>,
+    pub primary: Option<bool>,
+    pub type_out: Option<bool>,
+    pub type_delay: Option<u64>,
+    pub copy_only: Option<bool>,
+    pub tray: Option<bool>,
+    pub notify: Option<Vec<crate::activity::Kind>>,
+    pub errors_to: Option<Vec<crate::alerts::Destination>>,
+    pub error_webhook: Option<String>,
+    pub overlay: Option<bool>,
+    pub preview: Option<bool>,
+    pub diff: Option<bool>,
+    pub sounds: Option<Vec<crate::activity::Kind>>,
+    pub success_sound: Option<PathBuf>,
+    pub skipped_sound: Option<PathBuf>,
+    pub failure_sound: Option<PathBuf>,
+    pub volume: Option<u8>,
+    pub event_log: Option<PathBuf>,
+    pub timing: Option<bool>,
+    pub show_content: Option<bool>,
+    pub redact: Option<Vec<crate::privacy::Secret>>,
+    pub profile_key: Option<Hotkey>,
+    pub pause_key: Option<Hotkey>,
+    /// Used when `--profile` isn't given. Named apart from the `[[profile]]` tables.
+    pub default_profile: Option<String>,
+    pub plugin_dir: Option<PathBuf>,
+
+    /// Step lists that pipelines can refer to by name.
+    #[serde(default)]
+    pub pipelines: BTreeMap<String, Vec<TransformStep>>,
+
+    /// Named sets of options to switch between, in the order the profile hotkey cycles them.
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<Profile>,
+
+    /// Find/replace rules run by the `regex` pipeline step, in order.
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<RegexRule>,
+
+    /// Patterns to redact along with the built-in `redact` detectors.
+    #[serde(default, rename = "secret")]
+    pub secrets: Vec<SecretPattern>,
+
+    /// External programs that pipelines can run as steps, by name.
+    #[serde(default, rename = "command")]
+    pub commands: Vec<CommandStep>,
+
+    /// Steps written inline in the script language of `script.rs`, by name.
+    #[serde(default, rename = "script")]
+    pub scripts: Vec<ScriptStep>,
+
+    /// Extra triggers, each running its own pipeline.
+    #[serde(default, rename = "hotkey")]
+    pub hotkeys: Vec<HotkeyBinding>,
+
+    /// Used when `--paste-with` isn't given.
+    pub paste_with: Option<PasteShortcut>,
+
+    /// Shortcuts and pipelines for specific apps, picked from the focused window.
+    #[serde(default, rename = "app")]
+    pub apps: Vec<AppSettings>,
+}
+
+/// A single `[[profile]]` table: options that replace the top-level ones while it's active.
+/// Command-line flags and environment variables still win over it.
+///
+/// ```toml
+/// [[profile]]
+/// name = "email"
+/// pipeline = ["dehyphenate", "smart-flatten"]
+///
+/// [[profile]]
+/// name = "code"
+/// pipeline = ["trim", "collapse-spaces"]
+/// join_with = ""
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    pub join_with: Option<String>,
+    /// Replaces the trigger key's pipeline; `[[hotkey]]` tables keep their own.
+    pub pipeline: Option<Vec<TransformStep>>,
+    pub collapse_spaces: Option<bool>,
+    pub cooldown: Option<u64>,
+    pub copy_timeout: Option<u64>,
+    pub settle_delay: Option<u64>,
+    pub paste_delay: Option<u64>,
+    pub key_delay: Option<u64>,
+    pub restore_clipboard: Option<bool>,
+    pub restore_delay: Option<u64>,
+    pub preserve_formats: Option<bool>,
+    pub transform_html: Option<bool>,
+    pub paste_with: Option<PasteShortcut>,
+    pub type_out: Option<bool>,
+    pub type_delay: Option<u64>,
+    pub copy_only: Option<bool>,
+}
+
+/// A single `[[hotkey]]` table.
+///
+/// ```toml
+/// [[hotkey]]
+/// key = "Ctrl+Shift+F"
+/// pipeline = ["dehyphenate", "flatten"]
+///
+/// [[hotkey]]
+/// key = "Ctrl+Shift+H"
+/// action = "history"
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HotkeyBinding {
+    pub key: Hotkey,
+    #[serde(default)]
+    pub action: Action,
+    /// Only used by the `transform` and `transform-clipboard` actions.
+    #[serde(default)]
+    pub pipeline: Vec<TransformStep>,
+}
+
+/// What a hotkey does when pressed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Copy the selection, run the pipeline over it and paste the result.
+    #[default]
+    Transform,
+    /// Run the pipeline over what's already on the clipboard and paste the result, without
+    /// copying first.
+    TransformClipboard,
+    /// Paste an earlier result again, stepping one further back with each press.
+    History,
+    /// Put the last activation's original text back on the clipboard and paste it.
+    Undo,
+    /// Switch to the next `[[profile]]`, wrapping around to the top-level options.
+    NextProfile,
+    /// Pause all other hotkeys, or resume them. Works while paused.
+    TogglePause,
+}
+
+impl Action {
+    /// Whether the action runs a pipeline.
+    pub fn transforms(self) -> bool {
+        matches!(self, Action::Transform | Action::TransformClipboard)
+    }
+}
+
+impl HotkeyBinding {
+    /// A one-line summary for the startup banner.
+    pub fn describe(&self) -> String {
+        match self.action {
+            Action::Transform => format!("{:?}", self.pipeline),
+            Action::TransformClipboard => format!("{:?} on the clipboard", self.pipeline),
+            Action::History => "re-paste history".to_string(),
+            Action::Undo => "undo last transform".to_string(),
+            Action::NextProfile => "switch to next profile".to_string(),
+            Action::TogglePause => "pause/resume hotkeys".to_string(),
+        }
+    }
+}
+
+/// A single `[[app]]` table.
+///
+/// ```toml
+/// [[app]]
+/// name = "vmconnect.exe"   # executable or window class, case-insensitive
+/// copy = "Ctrl+Insert"
+/// paste = "Shift+Insert"
+///
+/// [[app]]
+/// name = "outlook.exe"
+/// pipeline = ["dehyphenate", "flatten"]
+///
+/// [[app]]
+/// name = "code.exe"
+/// pipeline = []            # leave code alone
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AppSettings {
+    pub name: String,
+    /// Replaces the standard copy shortcut.
+    pub copy: Option<Hotkey>,
+    /// Replaces the paste shortcut, including the terminal default.
+    pub paste: Option<Hotkey>,
+    /// Replaces the pipeline of every transform hotkey. Empty turns them off for this app.
+    pub pipeline: Option<Vec<TransformStep>>,
+}
+
+/// A single `[[rule]]` table.
+///
+/// ```toml
+/// [[rule]]
+/// pattern = "\\s+([,.;:])"
+/// replacement = "$1"
+/// flags = "m"
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RegexRule {
+    pub pattern: String,
+    /// May reference capture groups as `$1` or `${name}`.
+    #[serde(default)]
+    pub replacement: String,
+    /// Any of `i` (case-insensitive), `m` (multi-line), `s` (dot matches newline),
+    /// `x` (ignore whitespace) and `U` (swap greedy/lazy).
+    #[serde(default)]
+    pub flags: String,
+}
+
+/// A single `[[secret]]` table: text to redact wherever copied text is shown or saved.
+///
+/// ```toml
+/// [[secret]]
+/// name = "employee-id"
+/// pattern = "\\bE\\d{6}\\b"
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SecretPattern {
+    /// Shown in the marker that replaces it, as `[REDACTED <name>]`.
+    #[serde(default = "default_secret_name")]
+    pub name: String,
+    /// Only the group named `secret` is replaced if there is one, e.g. `pin: (?P<secret>\d+)`.
+    pub pattern: String,
+}
+
+fn default_secret_name() -> String {
+    "secret".to_string()
+}
+
+/// A single `[[command]]` table: a pipeline step that pipes the text through a program.
+///
+/// ```toml
+/// [[command]]
+/// name = "html-to-markdown"
+/// run = ["pandoc", "-f", "html", "-t", "gfm"]
+/// timeout = 5000
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CommandStep {
+    /// What pipelines call the step.
+    pub name: String,
+    /// The program and its arguments, run directly rather than through a shell.
+    pub run: Vec<String>,
+    /// Milliseconds to wait for the program before stopping it and keeping the text as it
+    /// was.
+    #[serde(default = "default_command_timeout")]
+    pub timeout: u64,
+}
+
+fn default_command_timeout() -> u64 {
+    5000
+}
+
+/// A single `[[script]]` table: a pipeline step written inline, one statement per line.
+///
+/// ```toml
+/// [[script]]
+/// name = "unquote"
+/// code = '''
+/// lines if /^>/ delete /^>\s?/
+/// lines if /^\s*$/ drop
+/// join " "
+/// '''
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptStep {
+    /// What pipelines call the step.
+    pub name: String,
+    pub code: String,
+}
+
+/// Where the config file is read from when `--config` isn't given:
+/// `%APPDATA%\\flatten-string\\config.toml` on Windows,
+/// `~/Library/Application Support/flatten-string/config.toml` on macOS and
+/// `$XDG_CONFIG_HOME/flatten-string/config.toml` (or `~/.config/...`) elsewhere.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(dir.join("flatten-string").join("config.toml"))
+}
+
+/// What `config init` writes: every option commented out at its default.
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+/// Writes the commented default config to `path`, refusing to replace an existing file
+/// unless `force` is set.
+pub fn init(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(anyhow!(
+            "{} already exists. Pass --force to overwrite it.",
+            path.display()
+        ));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create config directory {}", dir.display()))?;
+    }
+    fs::write(path, DEFAULT_CONFIG)
+        .with_context(|| format!("Failed to write config file {}", path.display()))?;
+    println!("Wrote default config to {}.", path.display());
+    Ok(())
+}
+
+/// Loads the config at `path` and checks everything that would otherwise only fail at
+/// startup or silently do nothing.
+pub fn validate(path: &Path) -> Result<()> {
+    let config = Config::load(path)?;
+    config.compile_rules()?;
+    config.compile_secrets()?;
+
+    let mut warnings = Vec::new();
+    let mut keys = Vec::new();
+    keys.extend(config.trigger_key);
+    keys.extend(config.history_key);
+    keys.extend(config.undo_key);
+    keys.extend(config.clipboard_key);
+    keys.extend(config.profile_key);
+    keys.extend(config.pause_key);
+    keys.extend(config.hotkeys.iter().map(|binding| binding.key));
+    for (i, key) in keys.iter().enumerate() {
+        if keys[..i].contains(key) {
+            warnings.push(format!(
+                "{} is bound more than once; only the first binding fires.",
+                key
+            ));
+        }
+    }
+    for (i, profile) in config.profiles.iter().enumerate() {
+        if config.profile_index(&profile.name)? != i {
+            return Err(anyhow!("Profile {:?} is defined twice", profile.name));
+        }
+    }
+    if let Some(name) = &config.default_profile {
+        config.profile_index(name)?;
+    }
+    if config.profile_key.is_some() && config.profiles.is_empty() {
+        warnings.push("profile_key is set but no [[profile]]s are configured.".to_string());
+    }
+    if config.rules.is_empty()
+        && pipelines_of(&config)
+            .flatten()
+            .any(|step| *step == TransformStep::Regex)
+    {
+        warnings.push("A pipeline has a regex step but no [[rule]]s are configured.".to_string());
+    }
+    for (i, command) in config.commands.iter().enumerate() {
+        ExternalCommand::new(command)?;
+        if config.commands[..i].iter().any(|c| c.name == command.name) {
+            return Err(anyhow!("[[command]] {:?} is defined twice", command.name));
+        }
+    }
+    for (i, script) in config.scripts.iter().enumerate() {
+        Script::new(script)?;
+        if config.scripts[..i].iter().any(|s| s.name == script.name)
+            || config.commands.iter().any(|c| c.name == script.name)
+        {
+            return Err(anyhow!(
+                "[[script]] {:?} has the name of a [[command]] or another [[script]]",
+                script.name
+            ));
+        }
+    }
+    let mut custom: Vec<&str> = pipelines_of(&config)
+        .flatten()
+        .filter_map(|step| match step {
+            TransformStep::Custom(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .filter(|name| !config.commands.iter().any(|c| c.name == *name))
+        .filter(|name| !config.scripts.iter().any(|s| s.name == *name))
+        .collect();
+    custom.sort();
+    custom.dedup();
+    for name in custom {
+        warnings.push(format!(
+            "{:?} isn't a built-in step, so a plugin has to add it.",
+            name
+        ));
+    }
+    for (i, binding) in config.hotkeys.iter().enumerate() {
+        if !binding.action.transforms() && !binding.pipeline.is_empty() {
+            warnings.push(format!(
+                "[[hotkey]] #{} ({}) has a pipeline, which its {:?} action ignores.",
+                i + 1,
+                binding.key,
+                binding.action
+            ));
+        }
+    }
+
+    for warning in &warnings {
+        println!("WARNING: {}", warning);
+    }
+    println!(
+        "{} is valid: {} hotkey(s), {} pipeline(s), {} profile(s), {} rule(s), {} command(s), {} script(s), {} app override(s), {} warning(s).",
+        path.display(),
+        keys.len(),
+        config.pipelines.len(),
+        config.profiles.len(),
+        config.rules.len(),
+        config.commands.len(),
+        config.scripts.len(),
+        config.apps.len(),
+        warnings.len()
+    );
+    Ok(())
+}
+
+/// Every pipeline in `config`, named or not.
+fn pipelines_of(config: &Config) -> impl Iterator<Item = &Vec<TransformStep>> {
+    config
+        .pipeline
+        .iter()
+        .chain(config.pipelines.values())
+        .chain(config.profiles.iter().filter_map(|p| p.pipeline.as_ref()))
+        .chain(config.apps.iter().filter_map(|app| app.pipeline.as_ref()))
+        .chain(config.hotkeys.iter().map(|binding| &binding.pipeline))
+}
+
+impl Config {
+    /// Index of the profile called `name`, case-insensitively.
+    pub fn profile_index(&self, name: &str) -> Result<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+                anyhow!(
+                    "Unknown profile {:?}. Configured profiles: {:?}",
+                    name,
+                    names
+                )
+            })
+    }
+
+    /// This config with profile `index`'s options laid over the top-level ones.
+    pub fn with_profile(&self, index: Option<usize>) -> Config {
+        let mut config = self.clone();
+        let Some(profile) = index.and_then(|i| self.profiles.get(i)) else {
+            return config;
+        };
+        macro_rules! overlay {
+            ($($field:ident),* $(,)?) => {$(
+                if let Some(value) = profile.$field.clone() {
+                    config.$field = Some(value);
+                }
+            )*};
+        }
+        overlay!(
+            join_with,
+            pipeline,
+            collapse_spaces,
+            cooldown,
+            copy_timeout,
+            settle_delay,
+            paste_delay,
+            key_delay,
+            restore_clipboard,
+            restore_delay,
+            preserve_formats,
+            transform_html,
+            paste_with,
+            type_out,
+            type_delay,
+            copy_only,
+        );
+        config
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let parse_error = || format!("Failed to parse config file {}", path.display());
+        let mut table: toml::Table = toml::from_str(&contents).with_context(parse_error)?;
+        if !table.contains_key("pipelines") {
+            // Deserializing straight from the text keeps line and column numbers in errors.
+            return toml::from_str(&contents).with_context(parse_error);
+        }
+        expand_pipelines(&mut table).with_context(parse_error)?;
+        table.try_into().with_context(parse_error)
+    }
+
+    /// The steps of the `[pipelines]` entry called `name`.
+    pub fn named_pipeline(&self, name: &str) -> Result<&Vec<TransformStep>> {
+        self.pipelines.get(name).ok_or_else(|| {
+            let names: Vec<&String> = self.pipelines.keys().collect();
+            anyhow!(
+                "Unknown pipeline {:?}. Configured pipelines: {:?}",
+                name,
+                names
+            )
+        })
+    }
+
+    /// Compiles every `[[rule]]`, reporting the first invalid one.
+    pub fn compile_rules(&self) -> Result<Vec<ReplaceRule>> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                rule.compile()
+                    .with_context(|| format!("Invalid rule #{} ({:?})", i + 1, rule.pattern))
+            })
+            .collect()
+    }
+
+    /// Compiles every `[[secret]]` into its name and regex, reporting the first invalid one.
+    pub fn compile_secrets(&self) -> Result<Vec<(String, Regex)>> {
+        self.secrets
+            .iter()
+            .enumerate()
+            .map(|(i, secret)| {
+                let regex = Regex::new(&secret.pattern)
+                    .with_context(|| format!("Invalid secret #{} ({:?})", i + 1, secret.pattern))?;
+                Ok((secret.name.clone(), regex))
+            })
+            .collect()
+    }
+}
+
+/// Replaces `[pipelines]` names in every `pipeline` key with the steps they stand for.
+fn expand_pipelines(table: &mut toml::Table) -> Result<()> {
+    let Some(toml::Value::Table(named)) = table.get("pipelines").cloned() else {
+        return Err(anyhow!("`pipelines` must be a table of step lists"));
+    };
+    for name in named.keys() {
+        if TransformStep::from_str(name, true).is_ok() {
+            return Err(anyhow!(
+                "Pipeline name {:?} is already the name of a transform step",
+                name
+            ));
+        }
+    }
+
+    let expand = |value: &mut toml::Value| {
+        let items = match value {
+            toml::Value::String(_) => vec![value.clone()],
+            toml::Value::Array(items) => items.clone(),
+            _ => return, // Left for deserialization to reject
+        };
+        let mut expanded = Vec::new();
+        for item in items {
+            match item.as_str().and_then(|name| named.get(name)) {
+                Some(toml::Value::Array(steps)) => expanded.extend(steps.iter().cloned()),
+                _ => expanded.push(item),
+            }
+        }
+        *value = toml::Value::Array(expanded);
+    };
+    if let Some(pipeline) = table.get_mut("pipeline") {
+        expand(pipeline);
+    }
+    for key in ["profile", "hotkey", "app"] {
+        let Some(toml::Value::Array(tables)) = table.get_mut(key) else {
+            continue;
+        };
+        for pipeline in tables.iter_mut().filter_map(|t| t.get_mut("pipeline")) {
+            expand(pipeline);
+        }
+    }
+    Ok(())
+}
+
+impl RegexRule {
+    pub fn compile(&self) -> Result<ReplaceRule> {
+        let mut builder = RegexBuilder::new(&self.pattern);
+        for flag in self.flags.chars() {
+            match flag {
+                'i' => builder.case_insensitive(true),
+                'm' => builder.multi_line(true),
+                's' => builder.dot_matches_new_line(true),
+                'x' => builder.ignore_whitespace(true),
+                'U' => builder.swap_greed(true),
+                other => return Err(anyhow!("Unknown regex flag '{}'", other)),
+            };
+        }
+        let regex: Regex = builder.build()?;
+        Ok(ReplaceRule {
+            regex,
+            replacement: self.replacement.clone(),
+        })
+    }
+}
@@ -0,0 +1,77 @@
+//! `--detach`: start the listener again as a background process and return.
+//!
+//! The background copy has no console to print to (and none to close, taking it along), so
+//! its output is appended to a log file instead.
+
+use anyhow::{Context, Result};
+use std::env;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::rotate::Retention;
+
+/// Where the detached listener's output goes: `listener.log` next to the config file.
+pub fn log_path(config_path: Option<&Path>) -> PathBuf {
+    match config_path {
+        Some(path) => path.with_file_name("listener.log"),
+        None => env::temp_dir().join("flatten-string-listener.log"),
+    }
+}
+
+/// Starts this program again without `--detach`, detached from the console and writing to
+/// `log`, and returns once it's running. The listener holds `log` open from then on, so it's
+/// only rotated here, as it starts.
+pub fn relaunch(log: &Path, retention: Retention) -> Result<()> {
+    if let Some(dir) = log.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    if let Err(e) = retention.check(log) {
+        log::warn!("{:#}", e);
+    }
+    let output = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(|| format!("Failed to open {}", log.display()))?;
+    let exe = env::current_exe().context("Failed to find the running executable")?;
+
+    let mut command = Command::new(exe);
+    command
+        .args(env::args_os().skip(1).filter(|arg| arg != "--detach"))
+        .env_remove(format!("{}DETACH", crate::ENV_PREFIX))
+        .stdin(Stdio::null())
+        .stdout(output.try_clone().context("Failed to share the log file")?)
+        .stderr(output);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        // Without a console of its own, closing this one doesn't end it, nor does Ctrl+C in it.
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // A session of its own, so the terminal's hangup on closing doesn't reach it.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    let child = command
+        .spawn()
+        .context("Failed to start the background listener")?;
+    println!(
+        "Listening in the background (PID {}). Output goes to {}.",
+        child.id(),
+        log.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! What a pipeline changed, word by word.
+//!
+//! Whitespace runs count as words of their own, so removed newlines and collapsed spaces
+//! show up as changes, marked with visible stand-ins.
+
+/// One piece of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Past this many word pairs the comparison isn't worth its memory, and everything is shown
+/// as replaced.
+const MAX_CELLS: usize = 4_000_000;
+
+/// The words and whitespace runs of `old` and `new`, with the ones only in `old` removed
+/// and the ones only in `new` added.
+pub fn words<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    diff(&split(old), &split(new))
+}
+
+/// How to mark changes when rendering them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// `[-removed-]{+added+}`, like `git diff --word-diff=plain`.
+    Plain,
+    /// Red and green, for terminals.
+    Color,
+}
+
+impl Style {
+    /// Color when `stream` is a terminal and `NO_COLOR` isn't set.
+    pub fn for_stream(stream: &impl std::io::IsTerminal) -> Self {
+        match stream.is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+            true => Style::Color,
+            false => Style::Plain,
+        }
+    }
+}
+
+/// Renders `changes` as the old text with the changes marked inline. Whitespace in a change
+/// is drawn as `⏎` (newline), `→` (tab) and `·` (space), so it can be seen.
+pub fn render(changes: &[Change], style: Style) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < changes.len() {
+        if let Change::Same(text) = changes[i] {
+            out.push_str(text);
+            i += 1;
+            continue;
+        }
+        // Each run of changes shows everything removed, then everything added.
+        let end = changes[i..]
+            .iter()
+            .position(|change| matches!(change, Change::Same(_)))
+            .map_or(changes.len(), |n| i + n);
+        let removed: String = changes[i..end]
+            .iter()
+            .filter_map(|change| match change {
+                Change::Removed(text) => Some(visible(text)),
+                _ => None,
+            })
+            .collect();
+        let added: String = changes[i..end]
+            .iter()
+            .filter_map(|change| match change {
+                Change::Added(text) => Some(visible(text)),
+                _ => None,
+            })
+            .collect();
+        let (remove_marks, add_marks) = match style {
+            Style::Plain => (("[-", "-]"), ("{+", "+}")),
+            Style::Color => (("\x1b[31;9m", "\x1b[0m"), ("\x1b[32m", "\x1b[0m")),
+        };
+        if !removed.is_empty() {
+            out.push_str(remove_marks.0);
+            out.push_str(&removed);
+            out.push_str(remove_marks.1);
+        }
+        if !added.is_empty() {
+            out.push_str(add_marks.0);
+            out.push_str(&added);
+            out.push_str(add_marks.1);
+        }
+        i = end;
+    }
+    out
+}
+
+fn visible(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' => '⏎',
+            '\t' => '→',
+            ' ' => '·',
+            c => c,
+        })
+        .filter(|&c| c != '\r')
+        .collect()
+}
+
+// Alternating runs of whitespace and everything else.
+fn split(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (at, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|in_space| in_space != space) {
+            pieces.push(&text[start..at]);
+            start = at;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+// The changes that turn `old` into `new`, through their longest common subsequence.
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Change<'a>> {
+    // Matching ends need no table.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut changes: Vec<Change> = old[..prefix].iter().map(|s| Change::Same(s)).collect();
+    if (old_mid.len() + 1) * (new_mid.len() + 1) > MAX_CELLS {
+        changes.extend(old_mid.iter().map(|s| Change::Removed(s)));
+        changes.extend(new_mid.iter().map(|s| Change::Added(s)));
+    } else {
+        // longest[i][j]: the common subsequence length of old_mid[i..] and new_mid[j..].
+        let width = new_mid.len() + 1;
+        let mut longest = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                longest[i * width + j] = match old_mid[i] == new_mid[j] {
+                    true => longest[(i + 1) * width + j + 1] + 1,
+                    false => longest[(i + 1) * width + j].max(longest[i * width + j + 1]),
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() && j < new_mid.len() {
+            if old_mid[i] == new_mid[j] {
+                changes.push(Change::Same(old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if longest[(i + 1) * width + j] >= longest[i * width + j + 1] {
+                changes.push(Change::Removed(old_mid[i]));
+                i += 1;
+            } else {
+                changes.push(Change::Added(new_mid[j]));
+                j += 1;
+            }
+        }
+        changes.extend(old_mid[i..].iter().map(|s| Change::Removed(s)));
+        changes.extend(new_mid[j..].iter().map(|s| Change::Added(s)));
+    }
+    changes.extend(old[old.len() - suffix..].iter().map(|s| Change::Same(s)));
+    changes
+}
@@ -1,7 +1,7 @@
 /// This is a copy of rdev::Key, so that #[derive(clap::ValueEnum)] works.
 ///
 /// I also added F13 through F24 for convenience.
-#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PTTKey {
     /// Alt key on Linux and Windows (option key on macOS)
     Alt,
@@ -127,6 +127,31 @@ pub enum PTTKey {
     Unknown(u32),
 }
 
+impl PTTKey {
+    /// Rough category, used to group `list-keys` output.
+    pub fn group(&self) -> &'static str {
+        use PTTKey::*;
+        match self {
+            Alt | AltGr | ControlLeft | ControlRight | MetaLeft | MetaRight | ShiftLeft
+            | ShiftRight | Function => "modifier",
+            F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 | F13 | F14 | F15
+            | F16 | F17 | F18 | F19 | F20 | F21 | F22 | F23 | F24 => "function",
+            KeyA | KeyB | KeyC | KeyD | KeyE | KeyF | KeyG | KeyH | KeyI | KeyJ | KeyK | KeyL
+            | KeyM | KeyN | KeyO | KeyP | KeyQ | KeyR | KeyS | KeyT | KeyU | KeyV | KeyW | KeyX
+            | KeyY | KeyZ => "letter",
+            Num0 | Num1 | Num2 | Num3 | Num4 | Num5 | Num6 | Num7 | Num8 | Num9 => "digit",
+            UpArrow | DownArrow | LeftArrow | RightArrow | Home | End | PageUp | PageDown
+            | Insert | Delete => "navigation",
+            Kp0 | Kp1 | Kp2 | Kp3 | Kp4 | Kp5 | Kp6 | Kp7 | Kp8 | Kp9 | KpReturn | KpMinus
+            | KpPlus | KpMultiply | KpDivide | KpDelete => "keypad",
+            BackQuote | Minus | Equal | LeftBracket | RightBracket | SemiColon | Quote
+            | BackSlash | IntlBackslash | Comma | Dot | Slash => "punctuation",
+            Backspace | CapsLock | Escape | Return | Space | Tab | PrintScreen | ScrollLock
+            | Pause | NumLock | Unknown(_) => "other",
+        }
+    }
+}
+
 impl From<PTTKey> for rdev::Key {
     fn from(item: PTTKey) -> Self {
         match item {
@@ -251,3 +276,128 @@ impl From<PTTKey> for rdev::Key {
         }
     }
 }
+
+impl From<rdev::Key> for PTTKey {
+    fn from(item: rdev::Key) -> Self {
+        match item {
+            rdev::Key::Alt => PTTKey::Alt,
+            rdev::Key::AltGr => PTTKey::AltGr,
+            rdev::Key::Backspace => PTTKey::Backspace,
+            rdev::Key::CapsLock => PTTKey::CapsLock,
+            rdev::Key::ControlLeft => PTTKey::ControlLeft,
+            rdev::Key::ControlRight => PTTKey::ControlRight,
+            rdev::Key::Delete => PTTKey::Delete,
+            rdev::Key::DownArrow => PTTKey::DownArrow,
+            rdev::Key::End => PTTKey::End,
+            rdev::Key::Escape => PTTKey::Escape,
+            rdev::Key::F1 => PTTKey::F1,
+            rdev::Key::F10 => PTTKey::F10,
+            rdev::Key::F11 => PTTKey::F11,
+            rdev::Key::F12 => PTTKey::F12,
+            rdev::Key::F2 => PTTKey::F2,
+            rdev::Key::F3 => PTTKey::F3,
+            rdev::Key::F4 => PTTKey::F4,
+            rdev::Key::F5 => PTTKey::F5,
+            rdev::Key::F6 => PTTKey::F6,
+            rdev::Key::F7 => PTTKey::F7,
+            rdev::Key::F8 => PTTKey::F8,
+            rdev::Key::F9 => PTTKey::F9,
+            rdev::Key::Home => PTTKey::Home,
+            rdev::Key::LeftArrow => PTTKey::LeftArrow,
+            rdev::Key::MetaLeft => PTTKey::MetaLeft,
+            rdev::Key::MetaRight => PTTKey::MetaRight,
+            rdev::Key::PageDown => PTTKey::PageDown,
+            rdev::Key::PageUp => PTTKey::PageUp,
+            rdev::Key::Return => PTTKey::Return,
+            rdev::Key::RightArrow => PTTKey::RightArrow,
+            rdev::Key::ShiftLeft => PTTKey::ShiftLeft,
+            rdev::Key::ShiftRight => PTTKey::ShiftRight,
+            rdev::Key::Space => PTTKey::Space,
+            rdev::Key::Tab => PTTKey::Tab,
+            rdev::Key::UpArrow => PTTKey::UpArrow,
+            rdev::Key::PrintScreen => PTTKey::PrintScreen,
+            rdev::Key::ScrollLock => PTTKey::ScrollLock,
+            rdev::Key::Pause => PTTKey::Pause,
+            rdev::Key::NumLock => PTTKey::NumLock,
+            rdev::Key::BackQuote => PTTKey::BackQuote,
+            rdev::Key::Num1 => PTTKey::Num1,
+            rdev::Key::Num2 => PTTKey::Num2,
+            rdev::Key::Num3 => PTTKey::Num3,
+            rdev::Key::Num4 => PTTKey::Num4,
+            rdev::Key::Num5 => PTTKey::Num5,
+            rdev::Key::Num6 => PTTKey::Num6,
+            rdev::Key::Num7 => PTTKey::Num7,
+            rdev::Key::Num8 => PTTKey::Num8,
+            rdev::Key::Num9 => PTTKey::Num9,
+            rdev::Key::Num0 => PTTKey::Num0,
+            rdev::Key::Minus => PTTKey::Minus,
+            rdev::Key::Equal => PTTKey::Equal,
+            rdev::Key::KeyQ => PTTKey::KeyQ,
+            rdev::Key::KeyW => PTTKey::KeyW,
+            rdev::Key::KeyE => PTTKey::KeyE,
+            rdev::Key::KeyR => PTTKey::KeyR,
+            rdev::Key::KeyT => PTTKey::KeyT,
+            rdev::Key::KeyY => PTTKey::KeyY,
+            rdev::Key::KeyU => PTTKey::KeyU,
+            rdev::Key::KeyI => PTTKey::KeyI,
+            rdev::Key::KeyO => PTTKey::KeyO,
+            rdev::Key::KeyP => PTTKey::KeyP,
+            rdev::Key::LeftBracket => PTTKey::LeftBracket,
+            rdev::Key::RightBracket => PTTKey::RightBracket,
+            rdev::Key::KeyA => PTTKey::KeyA,
+            rdev::Key::KeyS => PTTKey::KeyS,
+            rdev::Key::KeyD => PTTKey::KeyD,
+            rdev::Key::KeyF => PTTKey::KeyF,
+            rdev::Key::KeyG => PTTKey::KeyG,
+            rdev::Key::KeyH => PTTKey::KeyH,
+            rdev::Key::KeyJ => PTTKey::KeyJ,
+            rdev::Key::KeyK => PTTKey::KeyK,
+            rdev::Key::KeyL => PTTKey::KeyL,
+            rdev::Key::SemiColon => PTTKey::SemiColon,
+            rdev::Key::Quote => PTTKey::Quote,
+            rdev::Key::BackSlash => PTTKey::BackSlash,
+            rdev::Key::IntlBackslash => PTTKey::IntlBackslash,
+            rdev::Key::KeyZ => PTTKey::KeyZ,
+            rdev::Key::KeyX => PTTKey::KeyX,
+            rdev::Key::KeyC => PTTKey::KeyC,
+            rdev::Key::KeyV => PTTKey::KeyV,
+            rdev::Key::KeyB => PTTKey::KeyB,
+            rdev::Key::KeyN => PTTKey::KeyN,
+            rdev::Key::KeyM => PTTKey::KeyM,
+            rdev::Key::Comma => PTTKey::Comma,
+            rdev::Key::Dot => PTTKey::Dot,
+            rdev::Key::Slash => PTTKey::Slash,
+            rdev::Key::Insert => PTTKey::Insert,
+            rdev::Key::KpReturn => PTTKey::KpReturn,
+            rdev::Key::KpMinus => PTTKey::KpMinus,
+            rdev::Key::KpPlus => PTTKey::KpPlus,
+            rdev::Key::KpMultiply => PTTKey::KpMultiply,
+            rdev::Key::KpDivide => PTTKey::KpDivide,
+            rdev::Key::Kp0 => PTTKey::Kp0,
+            rdev::Key::Kp1 => PTTKey::Kp1,
+            rdev::Key::Kp2 => PTTKey::Kp2,
+            rdev::Key::Kp3 => PTTKey::Kp3,
+            rdev::Key::Kp4 => PTTKey::Kp4,
+            rdev::Key::Kp5 => PTTKey::Kp5,
+            rdev::Key::Kp6 => PTTKey::Kp6,
+            rdev::Key::Kp7 => PTTKey::Kp7,
+            rdev::Key::Kp8 => PTTKey::Kp8,
+            rdev::Key::Kp9 => PTTKey::Kp9,
+            rdev::Key::KpDelete => PTTKey::KpDelete,
+            rdev::Key::Function => PTTKey::Function,
+            rdev::Key::Unknown(124) => PTTKey::F13,
+            rdev::Key::Unknown(125) => PTTKey::F14,
+            rdev::Key::Unknown(126) => PTTKey::F15,
+            rdev::Key::Unknown(127) => PTTKey::F16,
+            rdev::Key::Unknown(128) => PTTKey::F17,
+            rdev::Key::Unknown(129) => PTTKey::F18,
+            rdev::Key::Unknown(130) => PTTKey::F19,
+            rdev::Key::Unknown(131) => PTTKey::F20,
+            rdev::Key::Unknown(132) => PTTKey::F21,
+            rdev::Key::Unknown(133) => PTTKey::F22,
+            rdev::Key::Unknown(134) => PTTKey::F23,
+            rdev::Key::Unknown(135) => PTTKey::F24,
+            rdev::Key::Unknown(code) => PTTKey::Unknown(code),
+        }
+    }
+}
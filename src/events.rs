@@ -0,0 +1,91 @@
+//! `--event-log`: a JSON line per activation, appended to a file, for looking back at how
+//! the tool behaved over weeks. Never holds copied text, only how much there was.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strflatten::transform::TransformStep;
+
+use crate::activity;
+use crate::config::Action;
+use crate::rotate::Retention;
+
+/// One activation, e.g.
+/// `{"timestamp":"2024-05-01T09:30:12.345Z","trigger":"Ctrl+Shift+F","action":"transform",...}`.
+#[derive(Serialize)]
+pub struct Event<'a> {
+    /// When the hotkey fired, in UTC.
+    pub timestamp: String,
+    pub trigger: String,
+    pub action: Action,
+    /// The steps run, after any `[[app]]` override. Missing for actions without a pipeline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<&'a [TransformStep]>,
+    pub app: String,
+    /// Characters read from the selection or clipboard.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copied_chars: Option<usize>,
+    /// Characters pasted, typed out or left on the clipboard.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasted_chars: Option<usize>,
+    pub duration_ms: f64,
+    pub result: activity::Kind,
+    /// Why it was skipped or failed, as the notifications say it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'a str>,
+}
+
+impl Event<'_> {
+    /// Appends the event to `path` as one line of JSON, first rotating it if `retention`
+    /// says it's due.
+    pub fn append(&self, path: &Path, retention: Retention) -> Result<()> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        if let Err(e) = retention.check(path) {
+            log::warn!("{:#}", e);
+        }
+        // One write per line, so lines from two processes don't interleave.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write to the event log {}", path.display()))
+    }
+}
+
+/// `at` as an RFC 3339 timestamp in UTC with milliseconds.
+pub fn timestamp(at: SystemTime) -> String {
+    let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let secs = since_epoch.as_secs();
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// Howard Hinnant's days-to-civil-date conversion, for days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
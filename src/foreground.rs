@@ -0,0 +1,101 @@
+//! Identifying the application that will receive the simulated shortcuts.
+
+/// The focused window's program and window class, lowercased. Either may be missing.
+#[derive(Debug, Default)]
+pub struct ForegroundApp {
+    /// File name of the executable, e.g. `windowsterminal.exe`.
+    pub exe: Option<String>,
+    /// Window class name, e.g. `consolewindowclass`.
+    pub class: Option<String>,
+}
+
+impl ForegroundApp {
+    /// Whether `name` (case-insensitive) is the app's executable or window class.
+    pub fn is(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.exe.as_deref() == Some(name.as_str()) || self.class.as_deref() == Some(name.as_str())
+    }
+
+    /// Whether this is a terminal emulator or console, which usually can't take Ctrl+V.
+    pub fn is_terminal(&self) -> bool {
+        KNOWN_TERMINALS.iter().any(|name| self.is(name))
+    }
+}
+
+impl std::fmt::Display for ForegroundApp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.exe, &self.class) {
+            (Some(exe), Some(class)) => write!(f, "{} ({})", exe, class),
+            (Some(name), None) | (None, Some(name)) => write!(f, "{}", name),
+            (None, None) => write!(f, "unknown app"),
+        }
+    }
+}
+
+/// Executables and window classes of common terminals.
+const KNOWN_TERMINALS: &[&str] = &[
+    // Windows Terminal and the classic console host
+    "windowsterminal.exe",
+    "cascadia_hosting_window_class",
+    "openconsole.exe",
+    "consolewindowclass",
+    // Git Bash, Cygwin and MSYS2
+    "mintty.exe",
+    "mintty",
+    "conemu.exe",
+    "conemu64.exe",
+    "virtualconsoleclass",
+    "putty.exe",
+    "putty",
+    "kitty.exe",
+    "alacritty.exe",
+    "wezterm-gui.exe",
+    "tabby.exe",
+    "hyper.exe",
+];
+
+/// The app owning the focused window.
+#[cfg(windows)]
+pub fn current() -> ForegroundApp {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetClassNameW, GetForegroundWindow, GetWindowThreadProcessId,
+    };
+
+    let window = unsafe { GetForegroundWindow() };
+    if window.is_null() {
+        return ForegroundApp::default();
+    }
+
+    let mut buffer = [0u16; 260];
+    let len = unsafe { GetClassNameW(window, buffer.as_mut_ptr(), buffer.len() as i32) };
+    let class = (len > 0).then(|| String::from_utf16_lossy(&buffer[..len as usize]).to_lowercase());
+
+    let mut pid = 0;
+    unsafe { GetWindowThreadProcessId(window, &mut pid) };
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    let mut exe = None;
+    if !process.is_null() {
+        let mut len = buffer.len() as u32;
+        let ok = unsafe {
+            QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len)
+        };
+        if ok != 0 {
+            let path = String::from_utf16_lossy(&buffer[..len as usize]);
+            exe = path.rsplit('\\').next().map(str::to_lowercase);
+        }
+        unsafe { CloseHandle(process) };
+    }
+
+    ForegroundApp { exe, class }
+}
+
+/// The app owning the focused window. Only detected on Windows so far.
+#[cfg(not(windows))]
+pub fn current() -> ForegroundApp {
+    ForegroundApp::default()
+}
@@ -0,0 +1,96 @@
+//! What `status` reports about the running listener: that it's alive, which profile is
+//! active, whether hotkeys are paused and how the last activation went.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::activity;
+use crate::events;
+use crate::stats;
+
+static PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Makes `name` the profile reported, or none for the top-level options.
+pub fn set_profile(name: Option<&str>) {
+    *PROFILE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = name.map(str::to_string);
+}
+
+pub fn set_paused(paused: bool) {
+    PAUSED.store(paused, Ordering::Relaxed);
+}
+
+/// The listener's state as `status` shows it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Report {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    /// Missing when the top-level options are in use.
+    pub profile: Option<String>,
+    pub paused: bool,
+    /// Missing before the first activation.
+    pub last_activation: Option<LastActivation>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LastActivation {
+    /// When it ended, in UTC.
+    pub timestamp: String,
+    pub secs_ago: u64,
+    pub result: activity::Kind,
+    pub message: String,
+}
+
+pub fn report() -> Report {
+    let last_activation = activity::recent().into_iter().next().map(|entry| {
+        let elapsed = entry.at.elapsed();
+        LastActivation {
+            timestamp: events::timestamp(SystemTime::now() - elapsed),
+            secs_ago: elapsed.as_secs(),
+            result: entry.kind,
+            message: entry.message,
+        }
+    });
+    Report {
+        pid: std::process::id(),
+        uptime_secs: stats::snapshot().uptime_secs,
+        profile: PROFILE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone(),
+        paused: PAUSED.load(Ordering::Relaxed),
+        last_activation,
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (hours, minutes) = (self.uptime_secs / 3600, self.uptime_secs / 60 % 60);
+        writeln!(
+            f,
+            "Running (pid {}) for {}h {:02}m.",
+            self.pid, hours, minutes
+        )?;
+        match &self.profile {
+            Some(name) => writeln!(f, "Profile: {}", name)?,
+            None => writeln!(f, "Profile: none (top-level options)")?,
+        }
+        if self.paused {
+            writeln!(f, "Hotkeys: paused")?;
+        }
+        match &self.last_activation {
+            Some(last) => write!(
+                f,
+                "Last activation: {} ({} s ago), {:?}: {}",
+                last.timestamp, last.secs_ago, last.result, last.message
+            ),
+            None => write!(f, "Last activation: none yet"),
+        }
+    }
+}
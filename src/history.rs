@@ -0,0 +1,156 @@
+//! The texts of past activations, so they can be pasted again.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::privacy;
+
+/// One activation: the copied text and what the pipeline turned it into.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub original: String,
+    pub transformed: String,
+    /// Copied from an app that marked it as a secret, so it's never saved or shown.
+    #[serde(skip)]
+    pub concealed: bool,
+}
+
+/// A ring of the last `capacity` entries, newest first.
+#[derive(Debug)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    /// Index of the entry the next `cycle` returns.
+    cursor: usize,
+    /// Where every change is saved, with `--save-history`.
+    path: Option<PathBuf>,
+}
+
+/// Where `--save-history` keeps the history: `history.json` next to the config file.
+pub fn default_path() -> Option<PathBuf> {
+    config::default_path().map(|path| path.with_file_name("history.json"))
+}
+
+/// Reads the entries saved at `path`, newest first. A missing file means no history yet.
+fn read_entries(path: &Path) -> Result<VecDeque<Entry>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse history file {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(VecDeque::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read history file {}", path.display())),
+    }
+}
+
+impl History {
+    /// `capacity` is raised to 1 so the last activation can always be undone.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            cursor: 0,
+            path: None,
+        }
+    }
+
+    /// Like `new`, but starts from the entries saved at `path` and saves every change there.
+    pub fn persistent(capacity: usize, path: PathBuf) -> Result<Self> {
+        let mut history = History::new(capacity);
+        history.entries = read_entries(&path)?;
+        history.entries.truncate(history.capacity);
+        history.path = Some(path);
+        Ok(history)
+    }
+
+    /// Records an activation, dropping the oldest entry when full. Restarts `cycle` from here.
+    pub fn push(&mut self, original: String, transformed: String, concealed: bool) {
+        self.cursor = 0;
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(Entry {
+            original,
+            transformed,
+            concealed,
+        });
+        if let Err(e) = self.save() {
+            log::error!("Failed to save history: {:?}", e);
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        }
+        // Credentials in the text don't belong on disk, even if pasting it again loses them.
+        let entries: Vec<Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.concealed)
+            .map(|entry| Entry {
+                original: privacy::redact(&entry.original),
+                transformed: privacy::redact(&entry.transformed),
+                concealed: false,
+            })
+            .collect();
+        let contents = serde_json::to_string_pretty(&entries)?;
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write history file {}", path.display()))
+    }
+
+    pub fn latest(&self) -> Option<&Entry> {
+        self.entries.front()
+    }
+
+    /// Steps back through the entries one per call, starting at the newest and wrapping
+    /// around after the oldest. Returns the entry's position (1 = newest) with it.
+    pub fn cycle(&mut self) -> Option<(usize, &Entry)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let index = self.cursor % self.entries.len();
+        self.cursor = index + 1;
+        Some((index + 1, &self.entries[index]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Prints the history saved by `--save-history`, or deletes it with `clear`.
+pub fn show_saved(clear: bool) -> Result<()> {
+    let path = default_path()
+        .ok_or_else(|| anyhow!("No default history location; it lives next to the config file"))?;
+    if clear {
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Deleted {}.", path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => println!("No saved history."),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to delete history file {}", path.display()))
+            }
+        }
+        return Ok(());
+    }
+
+    let entries = read_entries(&path)?;
+    if entries.is_empty() {
+        println!("No saved history. Run with --save-history to keep it.");
+        return Ok(());
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        println!("{}. {:.100}", i + 1, entry.transformed);
+        println!("   from: {:.100}", entry.original);
+    }
+    Ok(())
+}
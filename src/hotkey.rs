@@ -0,0 +1,388 @@
+use clap::ValueEnum;
+use rdev::{EventType, Key};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::easy_rdev_key::PTTKey;
+
+/// Modifier keys that can be part of a chord. Left and right variants count as the same modifier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    /// The physical keys that produce each active modifier.
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys = Vec::new();
+        if self.ctrl {
+            keys.extend([Key::ControlLeft, Key::ControlRight]);
+        }
+        if self.shift {
+            keys.extend([Key::ShiftLeft, Key::ShiftRight]);
+        }
+        if self.alt {
+            keys.extend([Key::Alt, Key::AltGr]);
+        }
+        if self.meta {
+            keys.extend([Key::MetaLeft, Key::MetaRight]);
+        }
+        keys
+    }
+
+    /// One key per active modifier, to hold down while simulating a chord.
+    pub fn press_keys(&self) -> Vec<Key> {
+        let keys = [
+            (self.ctrl, Key::ControlLeft),
+            (self.shift, Key::ShiftLeft),
+            (self.alt, Key::Alt),
+            (self.meta, Key::MetaLeft),
+        ];
+        keys.iter()
+            .filter(|(active, _)| *active)
+            .map(|(_, key)| *key)
+            .collect()
+    }
+}
+
+/// Tracks which modifier keys are currently held, from the listener's press/release events.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModifierState {
+    // Indexed by [left, right] so releasing one side doesn't clear the other.
+    ctrl: [bool; 2],
+    shift: [bool; 2],
+    alt: [bool; 2],
+    meta: [bool; 2],
+}
+
+impl ModifierState {
+    pub fn update(&mut self, event_type: &EventType) {
+        let (key, pressed) = match *event_type {
+            EventType::KeyPress(key) => (key, true),
+            EventType::KeyRelease(key) => (key, false),
+            _ => return,
+        };
+        let slot = match key {
+            Key::ControlLeft => &mut self.ctrl[0],
+            Key::ControlRight => &mut self.ctrl[1],
+            Key::ShiftLeft => &mut self.shift[0],
+            Key::ShiftRight => &mut self.shift[1],
+            Key::Alt => &mut self.alt[0],
+            Key::AltGr => &mut self.alt[1],
+            Key::MetaLeft => &mut self.meta[0],
+            Key::MetaRight => &mut self.meta[1],
+            _ => return,
+        };
+        *slot = pressed;
+    }
+
+    pub fn current(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl.contains(&true),
+            shift: self.shift.contains(&true),
+            alt: self.alt.contains(&true),
+            meta: self.meta.contains(&true),
+        }
+    }
+}
+
+/// A mouse button that can fire a hotkey.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// Side button usually bound to "back" (XButton1 on Windows, button 8 on X11)
+    Back,
+    /// Side button usually bound to "forward" (XButton2 on Windows, button 9 on X11)
+    Forward,
+}
+
+impl MouseButton {
+    pub const ALL: [MouseButton; 5] = [
+        MouseButton::Left,
+        MouseButton::Right,
+        MouseButton::Middle,
+        MouseButton::Back,
+        MouseButton::Forward,
+    ];
+
+    /// The name accepted in hotkeys, e.g. `mouse-middle`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MouseButton::Left => "mouse-left",
+            MouseButton::Right => "mouse-right",
+            MouseButton::Middle => "mouse-middle",
+            MouseButton::Back => "mouse-back",
+            MouseButton::Forward => "mouse-forward",
+        }
+    }
+}
+
+/// The codes rdev reports the back and forward side buttons with: the XBUTTON number on
+/// Windows and the core button number on X11.
+#[cfg(target_os = "linux")]
+const SIDE_BUTTONS: (u8, u8) = (8, 9);
+#[cfg(not(target_os = "linux"))]
+const SIDE_BUTTONS: (u8, u8) = (1, 2);
+
+impl From<MouseButton> for rdev::Button {
+    fn from(item: MouseButton) -> Self {
+        match item {
+            MouseButton::Left => rdev::Button::Left,
+            MouseButton::Right => rdev::Button::Right,
+            MouseButton::Middle => rdev::Button::Middle,
+            MouseButton::Back => rdev::Button::Unknown(SIDE_BUTTONS.0),
+            MouseButton::Forward => rdev::Button::Unknown(SIDE_BUTTONS.1),
+        }
+    }
+}
+
+/// The non-modifier part of a hotkey.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    Key(PTTKey),
+    Mouse(MouseButton),
+}
+
+impl Trigger {
+    pub fn is_pressed_by(&self, event_type: &EventType) -> bool {
+        match (*self, *event_type) {
+            (Trigger::Key(key), EventType::KeyPress(pressed)) => pressed == key.into(),
+            (Trigger::Mouse(button), EventType::ButtonPress(pressed)) => pressed == button.into(),
+            _ => false,
+        }
+    }
+
+    pub fn is_released_by(&self, event_type: &EventType) -> bool {
+        match (*self, *event_type) {
+            (Trigger::Key(key), EventType::KeyRelease(released)) => released == key.into(),
+            (Trigger::Mouse(button), EventType::ButtonRelease(released)) => {
+                released == button.into()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // The name it's parsed from, e.g. `key-j`.
+            Trigger::Key(key) => match key.to_possible_value() {
+                Some(value) => write!(f, "{}", value.get_name()),
+                None => write!(f, "{:?}", key),
+            },
+            Trigger::Mouse(button) => write!(f, "{}", button.name()),
+        }
+    }
+}
+
+/// A trigger such as `F9`, `Ctrl+Shift+J` or `Ctrl+MouseMiddle`.
+///
+/// The last `+`-separated part is a PTTKey name (`key-j`, `f9`, ...), with single letters and
+/// digits accepted as shorthand, or a mouse button (`mouse-left`, `mouse-right`,
+/// `mouse-middle`, `mouse-back`, `mouse-forward`). Everything before it is a modifier:
+/// `ctrl`, `shift`, `alt` or `meta` (also `win`, `super`, `cmd`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Hotkey {
+    pub modifiers: Modifiers,
+    pub trigger: Trigger,
+}
+
+impl Hotkey {
+    /// Whether `event_type` fires this hotkey while `held` modifiers are down.
+    ///
+    /// Modifiers must match exactly, so `Ctrl+J` doesn't fire on `Ctrl+Shift+J`.
+    pub fn matches(&self, event_type: &EventType, held: Modifiers) -> bool {
+        self.trigger.is_pressed_by(event_type) && held == self.modifiers
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|name| !name.is_empty());
+        let key_name = key_name.ok_or_else(|| format!("Missing key in hotkey {:?}", s))?;
+
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "meta" | "win" | "super" | "cmd" | "command" => modifiers.meta = true,
+                _ => return Err(format!("Unknown modifier {:?} in hotkey {:?}", part, s)),
+            }
+        }
+
+        Ok(Hotkey {
+            modifiers,
+            trigger: parse_trigger(key_name)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.meta, "Meta"),
+        ];
+        for (_, name) in names.iter().filter(|(active, _)| *active) {
+            write!(f, "{}+", name)?;
+        }
+        write!(f, "{}", self.trigger)
+    }
+}
+
+/// Detects two presses of the same hotkey within a time window.
+///
+/// The trigger has to be released between the presses, so OS key repeat from holding it
+/// down doesn't count as a second tap.
+#[derive(Debug)]
+pub struct DoubleTap {
+    window: Duration,
+    first_press: Option<FirstPress>,
+}
+
+#[derive(Debug)]
+struct FirstPress {
+    binding: usize,
+    trigger: Trigger,
+    at: Instant,
+    released: bool,
+}
+
+impl DoubleTap {
+    pub fn new(window: Duration) -> Self {
+        DoubleTap {
+            window,
+            first_press: None,
+        }
+    }
+
+    /// Records a press of hotkey `binding`. Returns true if it completes a double tap.
+    pub fn press(&mut self, binding: usize, trigger: Trigger) -> bool {
+        let now = Instant::now();
+        match &self.first_press {
+            Some(first)
+                if first.binding == binding
+                    && first.released
+                    && now.duration_since(first.at) <= self.window =>
+            {
+                self.first_press = None;
+                true
+            }
+            Some(first) if first.binding == binding && !first.released => false, // Key repeat
+            _ => {
+                self.first_press = Some(FirstPress {
+                    binding,
+                    trigger,
+                    at: now,
+                    released: false,
+                });
+                false
+            }
+        }
+    }
+
+    /// Notes when the first press's trigger is let go.
+    pub fn observe(&mut self, event_type: &EventType) {
+        if let Some(first) = self.first_press.as_mut() {
+            if first.trigger.is_released_by(event_type) {
+                first.released = true;
+            }
+        }
+    }
+}
+
+fn parse_trigger(name: &str) -> Result<Trigger, String> {
+    let button = match name.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+        "mouseleft" => Some(MouseButton::Left),
+        "mouseright" => Some(MouseButton::Right),
+        "mousemiddle" | "middleclick" => Some(MouseButton::Middle),
+        "mouseback" | "mouse4" | "xbutton1" => Some(MouseButton::Back),
+        "mouseforward" | "mouse5" | "xbutton2" => Some(MouseButton::Forward),
+        _ => None,
+    };
+    match button {
+        Some(button) => Ok(Trigger::Mouse(button)),
+        None => parse_key(name).map(Trigger::Key),
+    }
+}
+
+fn parse_key(name: &str) -> Result<PTTKey, String> {
+    // Allow "J" and "7" as shorthand for "key-j" and "num7".
+    let expanded = match name.chars().collect::<Vec<_>>()[..] {
+        [c] if c.is_ascii_alphabetic() => format!("key-{}", c),
+        [c] if c.is_ascii_digit() => format!("num{}", c),
+        _ => name.to_string(),
+    };
+    PTTKey::from_str(&expanded, true).map_err(|_| format!("Unknown key {:?}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_parses_back() {
+        let modifiers = [
+            Modifiers::default(),
+            Modifiers {
+                ctrl: true,
+                shift: true,
+                ..Modifiers::default()
+            },
+            Modifiers {
+                alt: true,
+                meta: true,
+                ..Modifiers::default()
+            },
+        ];
+        let buttons = [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::Back,
+            MouseButton::Forward,
+        ];
+        let triggers = PTTKey::value_variants()
+            .iter()
+            .map(|&key| Trigger::Key(key))
+            .chain(buttons.map(Trigger::Mouse));
+        for trigger in triggers {
+            for modifiers in modifiers {
+                let hotkey = Hotkey { modifiers, trigger };
+                assert_eq!(hotkey.to_string().parse(), Ok(hotkey), "{}", hotkey);
+            }
+        }
+    }
+
+    #[test]
+    fn display_uses_key_names() {
+        assert_eq!(
+            "Ctrl+Shift+J".parse::<Hotkey>().unwrap().to_string(),
+            "Ctrl+Shift+key-j"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Reading and rewriting the Windows "HTML Format" (CF_HTML) clipboard payload.
+//!
+//! The payload is UTF-8 HTML preceded by a header of byte offsets:
+//!
+//! ```text
+//! Version:0.9
+//! StartHTML:0000000105
+//! EndHTML:0000000199
+//! StartFragment:0000000141
+//! EndFragment:0000000163
+//! <html><body><!--StartFragment-->...<!--EndFragment--></body></html>
+//! ```
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Rewrites the fragment of a CF_HTML payload with `edit`, fixing up the header offsets.
+///
+/// Returns `None` if the payload is malformed.
+pub fn edit_fragment(data: &[u8], edit: impl FnOnce(&str) -> String) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(data).ok()?;
+    let start = header_value(text, "StartFragment")?;
+    let end = header_value(text, "EndFragment")?;
+    let fragment = text.get(start.value as usize..end.value as usize)?;
+
+    let edited = edit(fragment);
+    let delta = edited.len() as i64 - fragment.len() as i64;
+
+    let mut out = String::with_capacity(text.len() + edited.len());
+    out.push_str(&text[..start.value as usize]);
+    out.push_str(&edited);
+    out.push_str(&text[end.value as usize..]);
+
+    // The header sits before the fragment, so only the end offsets move. Values keep their
+    // zero-padded width, so the header's own length (and the start offsets) stay valid.
+    set_header_value(&mut out, &end, end.value + delta)?;
+    if let Some(end_html) = header_value(text, "EndHTML").filter(|v| v.value >= 0) {
+        set_header_value(&mut out, &end_html, end_html.value + delta)?;
+    }
+    Some(out.into_bytes())
+}
+
+/// Flattens the hard wraps of an HTML fragment while keeping its markup.
+///
+/// `<br>`s and newlines in text become `join_with`; `<pre>` blocks are left alone.
+pub fn flatten_fragment(html: &str, join_with: &str) -> String {
+    let joiner = escape(join_with);
+    let mut out = String::with_capacity(html.len());
+    let mut last = 0;
+    for pre in PRE.find_iter(html) {
+        out.push_str(&flatten_markup(&html[last..pre.start()], &joiner));
+        out.push_str(pre.as_str());
+        last = pre.end();
+    }
+    out.push_str(&flatten_markup(&html[last..], &joiner));
+    out
+}
+
+static PRE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<pre\b.*?</pre\s*>").unwrap());
+static BREAK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>[ \t]*(?:\r?\n[ \t]*)?").unwrap());
+
+fn flatten_markup(html: &str, joiner: &str) -> String {
+    let html = BREAK.replace_all(html, regex::NoExpand(joiner));
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    let mut chars = html.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            '\r' if chars.peek() == Some(&'\n') => continue,
+            // Inside a tag a newline just separates attributes.
+            '\r' | '\n' if in_tag => {
+                out.push(' ');
+                continue;
+            }
+            '\r' | '\n' => {
+                out.push_str(joiner);
+                continue;
+            }
+            _ => (),
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A numeric header value and where its digits are in the payload.
+struct HeaderValue {
+    value: i64,
+    at: usize,
+    width: usize,
+}
+
+fn header_value(text: &str, key: &str) -> Option<HeaderValue> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with('<') {
+            break; // Reached the HTML itself
+        }
+        if let Some(digits) = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            let digits = digits.trim_end();
+            return Some(HeaderValue {
+                value: digits.parse().ok()?,
+                at: offset + key.len() + 1,
+                width: digits.len(),
+            });
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn set_header_value(out: &mut String, header: &HeaderValue, value: i64) -> Option<()> {
+    let digits = format!("{:0width$}", value, width = header.width);
+    if digits.len() != header.width {
+        return None;
+    }
+    out.replace_range(header.at..header.at + header.width, &digits);
+    Some(())
+}
@@ -0,0 +1,246 @@
+//! Bookkeeping for input events we simulate ourselves.
+//!
+//! rdev doesn't expose SendInput's extra-info field or the hook's "injected" flag, so every
+//! event is recorded here just before it's simulated, and the listener drops the matching
+//! event when it comes back through the global hook.
+//!
+//! On Windows, where our input is tagged with `EXTRA_INFO`, hooks of our own read the field
+//! ahead of rdev's instead (see `watch_extra_info`). Matching by type and time is then only
+//! the fallback, for when they can't be set up.
+//!
+//! Another tool remapping keys can turn our events into different ones, which this can't
+//! match. The listener treats hotkeys arriving this soon after we simulated something with
+//! suspicion instead, so an activation can't keep triggering itself.
+//!
+//! Keys pressed here are also tracked until they're released, so they can be released
+//! after all if an activation fails or the program exits halfway through a shortcut.
+
+use rdev::{EventType, Key, SimulateError};
+use std::collections::VecDeque;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Tags all the input we send in its `dwExtraInfo`, so hooks that can read the field (other
+/// tools', and ours) can tell it from typing.
+#[cfg(windows)]
+pub const EXTRA_INFO: usize = 0x5354_464C; // "STFL"
+
+/// Set once `watch_extra_info`'s hooks see events, so `take` can go by `TAGGED`.
+#[cfg(windows)]
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the event being passed through the hooks right now carries `EXTRA_INFO`.
+#[cfg(windows)]
+static TAGGED: AtomicBool = AtomicBool::new(false);
+
+/// Injected events normally come back within a few milliseconds. Anything older was
+/// swallowed by the OS or another hook and shouldn't be matched anymore.
+const EXPIRY: Duration = Duration::from_secs(1);
+
+/// How long after a simulated event other input may still be its echo.
+const ECHO_WINDOW: Duration = Duration::from_millis(100);
+
+static PENDING: Mutex<VecDeque<(EventType, Instant)>> = Mutex::new(VecDeque::new());
+
+/// Keys we pressed and haven't released yet.
+static HELD: Mutex<Vec<Key>> = Mutex::new(Vec::new());
+
+/// Simulates `event_type`, remembering it so the listener can recognize it.
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    record(event_type);
+    #[cfg(windows)]
+    send_input(event_type)?;
+    #[cfg(not(windows))]
+    rdev::simulate(event_type)?;
+    let mut held = HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match *event_type {
+        EventType::KeyPress(key) if !held.contains(&key) => held.push(key),
+        EventType::KeyRelease(key) => held.retain(|&k| k != key),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Releases every key we pressed and didn't release, newest first. Returns how many.
+pub fn release_held() -> usize {
+    let held = std::mem::take(&mut *HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    for &key in held.iter().rev() {
+        // Nothing more can be done about a key that won't release either.
+        let _ = simulate(&EventType::KeyRelease(key));
+    }
+    held.len()
+}
+
+/// Remembers an event about to be sent some other way than `simulate`.
+pub fn record(event_type: &EventType) {
+    pending().push_back((*event_type, Instant::now()));
+}
+
+/// Whether we simulated anything within the last `ECHO_WINDOW`.
+pub fn echo_possible() -> bool {
+    pending()
+        .back()
+        .is_some_and(|(_, at)| at.elapsed() < ECHO_WINDOW)
+}
+
+/// Returns true (and forgets the event) if `event_type` is one we simulated.
+pub fn take(event_type: &EventType) -> bool {
+    let mut pending = pending();
+    while pending.front().is_some_and(|(_, at)| at.elapsed() > EXPIRY) {
+        pending.pop_front();
+    }
+    let position = pending.iter().position(|(sent, _)| sent == event_type);
+    // The hooks know for certain, even if another tool remapped the event on its way.
+    #[cfg(windows)]
+    let ours = match WATCHING.load(Ordering::Relaxed) {
+        true => TAGGED.load(Ordering::Relaxed),
+        false => position.is_some(),
+    };
+    #[cfg(not(windows))]
+    let ours = position.is_some();
+    if !ours {
+        return false;
+    }
+    if let Some(i) = position {
+        pending.remove(i);
+    }
+    // With --type-out these spell out the text.
+    if crate::privacy::shows_content() {
+        log::trace!("Dropped our own {:?}", event_type);
+    }
+    true
+}
+
+/// Hooks in ahead of rdev to read each event's `dwExtraInfo`, which rdev doesn't pass on.
+/// Low-level hooks run newest first, so this is called from rdev's callback, on its thread,
+/// once rdev has hooked in. Only the first call does anything.
+#[cfg(windows)]
+pub fn watch_extra_info() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SetWindowsHookExW, UnhookWindowsHookEx, WH_KEYBOARD_LL, WH_MOUSE_LL,
+    };
+
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let null = std::ptr::null_mut();
+    let hooks = unsafe {
+        [
+            SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook), null, 0),
+            SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), null, 0),
+        ]
+    };
+    if hooks.iter().any(|hook| hook.is_null()) {
+        log::warn!(
+            "Failed to hook in to read dwExtraInfo ({}). Our own input is told apart by its timing instead.",
+            std::io::Error::last_os_error()
+        );
+        for hook in hooks.into_iter().filter(|hook| !hook.is_null()) {
+            unsafe { UnhookWindowsHookEx(hook) };
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn keyboard_hook(code: i32, wparam: usize, lparam: isize) -> isize {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{CallNextHookEx, HC_ACTION, KBDLLHOOKSTRUCT};
+
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+        note_extra_info(info.dwExtraInfo);
+    }
+    CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam)
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn mouse_hook(code: i32, wparam: usize, lparam: isize) -> isize {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{CallNextHookEx, HC_ACTION, MSLLHOOKSTRUCT};
+
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam as *const MSLLHOOKSTRUCT);
+        note_extra_info(info.dwExtraInfo);
+    }
+    CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam)
+}
+
+/// Called by our hooks just before rdev's hook gets the same event.
+#[cfg(windows)]
+fn note_extra_info(extra_info: usize) {
+    TAGGED.store(extra_info == EXTRA_INFO, Ordering::Relaxed);
+    WATCHING.store(true, Ordering::Relaxed);
+}
+
+/// Sends a key or mouse button event as rdev would, but tagged with `EXTRA_INFO`.
+#[cfg(windows)]
+fn send_input(event_type: &EventType) -> Result<(), SimulateError> {
+    use rdev::Button;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, KEYEVENTF_KEYUP,
+        MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP,
+        MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT,
+    };
+
+    let key = |key: Key, flags| {
+        let vk = crate::vk::virtual_key(key).ok_or(SimulateError)?;
+        Ok(INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: vk,
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: EXTRA_INFO,
+                },
+            },
+        })
+    };
+    let button = |button: Button, down: bool| {
+        let (flags, data) = match (button, down) {
+            (Button::Left, true) => (MOUSEEVENTF_LEFTDOWN, 0),
+            (Button::Left, false) => (MOUSEEVENTF_LEFTUP, 0),
+            (Button::Right, true) => (MOUSEEVENTF_RIGHTDOWN, 0),
+            (Button::Right, false) => (MOUSEEVENTF_RIGHTUP, 0),
+            (Button::Middle, true) => (MOUSEEVENTF_MIDDLEDOWN, 0),
+            (Button::Middle, false) => (MOUSEEVENTF_MIDDLEUP, 0),
+            (Button::Unknown(code), true) => (MOUSEEVENTF_XDOWN, code.into()),
+            (Button::Unknown(code), false) => (MOUSEEVENTF_XUP, code.into()),
+        };
+        Ok(INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dx: 0,
+                    dy: 0,
+                    mouseData: data,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: EXTRA_INFO,
+                },
+            },
+        })
+    };
+    let input = match *event_type {
+        EventType::KeyPress(k) => key(k, 0),
+        EventType::KeyRelease(k) => key(k, KEYEVENTF_KEYUP),
+        EventType::ButtonPress(b) => button(b, true),
+        EventType::ButtonRelease(b) => button(b, false),
+        // Never simulated, so they needn't be told apart.
+        _ => return rdev::simulate(event_type),
+    }?;
+    let sent = unsafe { SendInput(1, &input, std::mem::size_of::<INPUT>() as i32) };
+    match sent {
+        1 => Ok(()),
+        _ => Err(SimulateError),
+    }
+}
+
+fn pending() -> std::sync::MutexGuard<'static, VecDeque<(EventType, Instant)>> {
+    // The queue is always left consistent, so a panic elsewhere doesn't invalidate it.
+    PENDING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
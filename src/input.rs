@@ -0,0 +1,216 @@
+//! Simulated keyboard input behind a platform-neutral `InputSimulator` trait.
+
+use anyhow::{anyhow, Context, Result};
+use rdev::{Button, EventType, Key};
+use serde::Deserialize;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+use crate::easy_rdev_key::PTTKey;
+use crate::hotkey::{Hotkey, Modifiers, Trigger};
+use crate::injected;
+use crate::layout;
+
+/// The modifier of the system copy/paste shortcuts: Cmd on macOS, Ctrl elsewhere.
+const SHORTCUT_MODIFIER: Modifiers = Modifiers {
+    ctrl: cfg!(not(target_os = "macos")),
+    shift: false,
+    alt: false,
+    meta: cfg!(target_os = "macos"),
+};
+
+/// The system copy shortcut: Ctrl+C, or Cmd+C on macOS.
+pub fn standard_copy() -> Hotkey {
+    Hotkey {
+        modifiers: SHORTCUT_MODIFIER,
+        trigger: Trigger::Key(PTTKey::KeyC),
+    }
+}
+
+/// The key combination that pastes in the focused application.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PasteShortcut {
+    /// Ctrl+V, or Cmd+V on macOS.
+    CtrlV,
+    /// What most Linux terminals expect.
+    CtrlShiftV,
+    /// Understood by nearly every terminal, including the Windows console and mintty.
+    ShiftInsert,
+}
+
+impl PasteShortcut {
+    pub fn chord(self) -> Hotkey {
+        let (modifiers, key) = match self {
+            PasteShortcut::CtrlV => (SHORTCUT_MODIFIER, PTTKey::KeyV),
+            PasteShortcut::CtrlShiftV => (
+                Modifiers {
+                    ctrl: true,
+                    shift: true,
+                    ..Modifiers::default()
+                },
+                PTTKey::KeyV,
+            ),
+            PasteShortcut::ShiftInsert => (
+                Modifiers {
+                    shift: true,
+                    ..Modifiers::default()
+                },
+                PTTKey::Insert,
+            ),
+        };
+        Hotkey {
+            modifiers,
+            trigger: Trigger::Key(key),
+        }
+    }
+}
+
+impl fmt::Display for PasteShortcut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chord())
+    }
+}
+
+/// Something that can type into the focused application.
+pub trait InputSimulator: Send {
+    /// Sends a single input event.
+    fn send(&mut self, event_type: &EventType) -> Result<()>;
+
+    /// Pause between the events of a simulated shortcut, so slow apps see each one.
+    fn key_delay(&self) -> Duration;
+
+    /// Releases keys the user may still be holding.
+    fn release_keys(&mut self, keys: &[Key]) -> Result<()> {
+        for key in keys {
+            self.send(&EventType::KeyRelease(*key))?;
+        }
+        Ok(())
+    }
+
+    /// Presses and releases `chord`'s trigger while holding its modifiers.
+    fn send_chord(&mut self, chord: &Hotkey) -> Result<()> {
+        let modifiers = chord.modifiers.press_keys();
+        for modifier in &modifiers {
+            self.send(&EventType::KeyPress(*modifier))?;
+            thread::sleep(self.key_delay());
+        }
+        let (press, release) = match chord.trigger {
+            Trigger::Key(key) => (
+                EventType::KeyPress(key.into()),
+                EventType::KeyRelease(key.into()),
+            ),
+            Trigger::Mouse(button) => (
+                EventType::ButtonPress(button.into()),
+                EventType::ButtonRelease(button.into()),
+            ),
+        };
+        self.send(&press)?;
+        thread::sleep(self.key_delay());
+        self.send(&release)?;
+        for modifier in modifiers.iter().rev() {
+            thread::sleep(self.key_delay());
+            self.send(&EventType::KeyRelease(*modifier))?;
+        }
+        Ok(())
+    }
+
+    /// Types `text` character by character, pausing `delay` after each one.
+    fn type_text(&mut self, text: &str, delay: Duration) -> Result<()> {
+        let _ = (text, delay);
+        Err(anyhow!("Typing text out isn't supported on this platform"))
+    }
+
+    /// A middle click, which pastes the primary selection on X11 and Wayland.
+    fn send_middle_click(&mut self) -> Result<()> {
+        self.send(&EventType::ButtonPress(Button::Middle))?;
+        thread::sleep(self.key_delay());
+        self.send(&EventType::ButtonRelease(Button::Middle))
+    }
+}
+
+/// Simulates input through rdev, recording each event so the listener can ignore it.
+pub struct RdevSimulator {
+    pub key_delay: Duration,
+}
+
+impl InputSimulator for RdevSimulator {
+    fn key_delay(&self) -> Duration {
+        self.key_delay
+    }
+
+    fn send(&mut self, event_type: &EventType) -> Result<()> {
+        // Letters go by what the active layout types, not by their QWERTY position.
+        let event_type = match *event_type {
+            EventType::KeyPress(key) => EventType::KeyPress(layout::resolve(key)),
+            EventType::KeyRelease(key) => EventType::KeyRelease(layout::resolve(key)),
+            other => other,
+        };
+        injected::simulate(&event_type)
+            .with_context(|| format!("Failed to simulate {:?}", event_type))
+    }
+
+    #[cfg(windows)]
+    fn type_text(&mut self, text: &str, delay: Duration) -> Result<()> {
+        for c in text.chars() {
+            match c {
+                '\r' => continue, // The '\n' of a CRLF presses Enter on its own
+                // Apps handle a real Enter key more reliably than a Unicode newline.
+                '\n' => {
+                    self.send(&EventType::KeyPress(Key::Return))?;
+                    self.send(&EventType::KeyRelease(Key::Return))?;
+                }
+                c => send_unicode(c)?,
+            }
+            thread::sleep(delay);
+            crate::cancel::check()?;
+        }
+        Ok(())
+    }
+}
+
+/// Types `c` with SendInput's Unicode packets, independent of the keyboard layout.
+#[cfg(windows)]
+fn send_unicode(c: char) -> Result<()> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+    };
+
+    // Unicode packets reach the hook as presses and releases of VK_PACKET.
+    const VK_PACKET: u32 = 0xE7;
+
+    // Characters outside the BMP go as two packets, one per surrogate.
+    let mut units = [0; 2];
+    let mut inputs = Vec::new();
+    for &unit in c.encode_utf16(&mut units).iter() {
+        injected::record(&EventType::KeyPress(Key::Unknown(VK_PACKET)));
+        injected::record(&EventType::KeyRelease(Key::Unknown(VK_PACKET)));
+        for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+            inputs.push(INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: 0,
+                        wScan: unit,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: injected::EXTRA_INFO,
+                    },
+                },
+            });
+        }
+    }
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize != inputs.len() {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to type {:?}", c));
+    }
+    Ok(())
+}
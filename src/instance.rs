@@ -0,0 +1,83 @@
+//! Keeps a second listener from starting while one is running, so a hotkey press isn't
+//! handled (and pasted) twice.
+//!
+//! Windows uses a named mutex in the session's namespace. Elsewhere it's an advisory lock on
+//! a file in the runtime directory, which the OS drops when the process exits, however it
+//! exits.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Held for as long as this process is the running instance.
+pub struct Guard {
+    #[cfg(windows)]
+    _mutex: windows_sys::Win32::Foundation::HANDLE,
+    #[cfg(unix)]
+    _lock: std::fs::File,
+}
+
+/// Becomes the running instance, or returns `None` if another process already is.
+#[cfg(windows)]
+pub fn acquire() -> Result<Option<Guard>> {
+    use anyhow::Context;
+    use windows_sys::Win32::Foundation::{GetLastError, ERROR_ALREADY_EXISTS};
+    use windows_sys::Win32::System::Threading::CreateMutexW;
+
+    let name: Vec<u16> = "Local\\flatten-string-listener"
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    let mutex = unsafe { CreateMutexW(std::ptr::null(), 0, name.as_ptr()) };
+    if mutex.is_null() {
+        return Err(std::io::Error::last_os_error()).context("Failed to create the instance mutex");
+    }
+    // Closing the handle is left to process exit, so the mutex lives exactly as long.
+    if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+        return Ok(None);
+    }
+    Ok(Some(Guard { _mutex: mutex }))
+}
+
+/// Becomes the running instance, or returns `None` if another process already is.
+#[cfg(unix)]
+pub fn acquire() -> Result<Option<Guard>> {
+    use anyhow::Context;
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    let path = runtime_path("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(error).with_context(|| format!("Failed to lock {}", path.display()));
+    }
+    Ok(Some(Guard { _lock: file }))
+}
+
+/// `flatten-string-<user>.<extension>` in a directory only the running user's processes
+/// use: the runtime directory, or else the temp directory (which is per user on Windows).
+pub fn runtime_path(extension: &str) -> PathBuf {
+    // Per user, so two people on one machine can each run one.
+    #[cfg(unix)]
+    let (dir, user) = (
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir),
+        unsafe { libc::getuid() }.to_string(),
+    );
+    #[cfg(windows)]
+    let (dir, user) = (
+        std::env::temp_dir(),
+        std::env::var("USERNAME").unwrap_or_default(),
+    );
+    dir.join(format!("flatten-string-{}.{}", user, extension))
+}
@@ -0,0 +1,115 @@
+//! Lets commands like `stats` ask the running listener questions.
+//!
+//! The listener answers on a loopback TCP port, which works the same everywhere. Its port
+//! and a random token go in a file only this user can read, and a request without the token
+//! gets no answer, so other users on the machine can't ask.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::instance;
+
+/// How long a question waits for its answer.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn endpoint_path() -> PathBuf {
+    instance::runtime_path("port")
+}
+
+/// Answers requests on a thread of its own until the program exits. `answer` gets each
+/// request's command, e.g. `stats`, and returns its reply, or `None` for commands it
+/// doesn't know.
+pub fn serve(answer: impl Fn(&str) -> Option<String> + Send + 'static) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .context("Failed to listen for requests from other commands")?;
+    let port = listener.local_addr()?.port();
+    let token = token();
+    write_private(&endpoint_path(), &format!("{} {}", port, token))?;
+
+    thread::Builder::new()
+        .name("ipc".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = respond(stream, &token, &answer) {
+                    log::debug!("Dropped a request: {:#}", e);
+                }
+            }
+        })
+        .expect("failed to spawn the ipc thread");
+    Ok(())
+}
+
+fn respond(stream: TcpStream, token: &str, answer: &impl Fn(&str) -> Option<String>) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let Some((given, command)) = request.trim_end().split_once(' ') else {
+        return Err(anyhow!("Malformed request"));
+    };
+    if given != token {
+        return Err(anyhow!("Wrong token"));
+    }
+    let reply = answer(command).unwrap_or_else(|| format!("Unknown request {:?}", command));
+    (&stream).write_all(reply.as_bytes())?;
+    Ok(())
+}
+
+/// Sends `command` to the running listener and returns its reply.
+pub fn ask(command: &str) -> Result<String> {
+    let path = endpoint_path();
+    let endpoint = fs::read_to_string(&path).map_err(|_| {
+        anyhow!(
+            "No flatten-string is listening. Start it first, or check that it's running as you."
+        )
+    })?;
+    let (port, token) = endpoint
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("{} is malformed", path.display()))?;
+    let port: u16 = port
+        .parse()
+        .with_context(|| format!("{} is malformed", path.display()))?;
+    // A listener that exited uncleanly leaves the file behind, pointing at nothing.
+    let mut stream = TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, port).into(), TIMEOUT)
+        .map_err(|_| anyhow!("No flatten-string is listening. Start it first."))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    writeln!(stream, "{} {}", token, command)?;
+    let mut reply = Vec::new();
+    stream
+        .read_to_end(&mut reply)
+        .context("The listener didn't answer")?;
+    Ok(String::from_utf8_lossy(&reply).into_owned())
+}
+
+// Unguessable enough for a local secret, without a dependency for it.
+fn token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    if let Ok(since) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(since.as_nanos());
+    }
+    format!(
+        "{:016x}{:016x}",
+        hasher.finish(),
+        RandomState::new().build_hasher().finish()
+    )
+}
+
+// Writes `contents` to `path` readable by this user alone.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
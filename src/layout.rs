@@ -0,0 +1,188 @@
+//! Resolving letter keys through the active keyboard layout.
+//!
+//! rdev simulates keys by physical position (X11 keycodes) or by the US virtual-key code, so
+//! on AZERTY or Dvorak `Key::KeyC` can end up typing a different letter than the "C" that the
+//! copy shortcut expects. Before a letter is simulated it's looked up by the character it
+//! should produce instead. macOS isn't resolved yet and keeps the physical key.
+
+use rdev::Key;
+
+const LETTERS: [(Key, char); 26] = [
+    (Key::KeyA, 'a'),
+    (Key::KeyB, 'b'),
+    (Key::KeyC, 'c'),
+    (Key::KeyD, 'd'),
+    (Key::KeyE, 'e'),
+    (Key::KeyF, 'f'),
+    (Key::KeyG, 'g'),
+    (Key::KeyH, 'h'),
+    (Key::KeyI, 'i'),
+    (Key::KeyJ, 'j'),
+    (Key::KeyK, 'k'),
+    (Key::KeyL, 'l'),
+    (Key::KeyM, 'm'),
+    (Key::KeyN, 'n'),
+    (Key::KeyO, 'o'),
+    (Key::KeyP, 'p'),
+    (Key::KeyQ, 'q'),
+    (Key::KeyR, 'r'),
+    (Key::KeyS, 's'),
+    (Key::KeyT, 't'),
+    (Key::KeyU, 'u'),
+    (Key::KeyV, 'v'),
+    (Key::KeyW, 'w'),
+    (Key::KeyX, 'x'),
+    (Key::KeyY, 'y'),
+    (Key::KeyZ, 'z'),
+];
+
+/// The key that types `key`'s letter in the active layout; anything else is returned as is.
+pub fn resolve(key: Key) -> Key {
+    let Some(&(_, letter)) = LETTERS.iter().find(|(k, _)| *k == key) else {
+        return key;
+    };
+    key_for_char(letter).unwrap_or(key)
+}
+
+#[cfg(windows)]
+fn key_for_char(c: char) -> Option<Key> {
+    use crate::easy_rdev_key::PTTKey;
+    use crate::vk::virtual_key;
+    use clap::ValueEnum;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, VkKeyScanExW};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowThreadProcessId,
+    };
+
+    // Layouts are per thread, so ask for the one of the app that receives the keys.
+    let thread = unsafe { GetWindowThreadProcessId(GetForegroundWindow(), std::ptr::null_mut()) };
+    let layout = unsafe { GetKeyboardLayout(thread) };
+    let scan = unsafe { VkKeyScanExW(c as u16, layout) };
+    // The high byte holds the modifiers needed; a letter that needs any isn't usable.
+    if scan == -1 || scan as u16 >> 8 != 0 {
+        return None;
+    }
+    let vk = scan as u16 & 0xff;
+    let key = PTTKey::value_variants()
+        .iter()
+        .map(|&key| rdev::Key::from(key))
+        .find(|&key| virtual_key(key) == Some(vk))
+        .unwrap_or(Key::Unknown(vk.into()));
+    Some(key)
+}
+
+#[cfg(target_os = "linux")]
+struct Keymap {
+    display: *mut x11::xlib::Display,
+    /// Letters looked up so far, until the keyboard mapping changes.
+    keys: std::collections::BTreeMap<char, Option<Key>>,
+}
+
+// SAFETY: the display is only ever used behind `KEYMAP`'s lock.
+#[cfg(target_os = "linux")]
+unsafe impl Send for Keymap {}
+
+/// Opened on first use and kept open, since opening a display takes a round trip.
+#[cfg(target_os = "linux")]
+static KEYMAP: std::sync::Mutex<Option<Keymap>> = std::sync::Mutex::new(None);
+
+#[cfg(target_os = "linux")]
+fn key_for_char(c: char) -> Option<Key> {
+    use x11::xlib::{
+        MappingNotify, XEvent, XKeysymToKeycode, XNextEvent, XOpenDisplay, XPending,
+        XRefreshKeyboardMapping,
+    };
+
+    let mut keymap = KEYMAP
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if keymap.is_none() {
+        let display = unsafe { XOpenDisplay(std::ptr::null()) };
+        if display.is_null() {
+            return None;
+        }
+        *keymap = Some(Keymap {
+            display,
+            keys: Default::default(),
+        });
+    }
+    let keymap = keymap.as_mut()?;
+    let display = keymap.display;
+    // Every client is told when the layout changes; until then the earlier lookups hold.
+    while unsafe { XPending(display) } > 0 {
+        let mut event: XEvent = unsafe { std::mem::zeroed() };
+        unsafe { XNextEvent(display, &mut event) };
+        if event.get_type() == MappingNotify {
+            unsafe { XRefreshKeyboardMapping(&mut event.mapping) };
+            keymap.keys.clear();
+        }
+    }
+    *keymap.keys.entry(c).or_insert_with(|| {
+        // Latin-1 keysyms are the character's code point.
+        let keycode = unsafe { XKeysymToKeycode(display, c as u64) };
+        (keycode != 0).then(|| key_from_code(keycode.into()))
+    })
+}
+
+/// The key rdev's listener reports for the X11 `keycode`, so the keys we simulate match
+/// their echo. Only the keys that type characters; rdev keeps its own table private.
+#[cfg(target_os = "linux")]
+fn key_from_code(keycode: u32) -> Key {
+    match keycode {
+        10 => Key::Num1,
+        11 => Key::Num2,
+        12 => Key::Num3,
+        13 => Key::Num4,
+        14 => Key::Num5,
+        15 => Key::Num6,
+        16 => Key::Num7,
+        17 => Key::Num8,
+        18 => Key::Num9,
+        19 => Key::Num0,
+        20 => Key::Minus,
+        21 => Key::Equal,
+        24 => Key::KeyQ,
+        25 => Key::KeyW,
+        26 => Key::KeyE,
+        27 => Key::KeyR,
+        28 => Key::KeyT,
+        29 => Key::KeyY,
+        30 => Key::KeyU,
+        31 => Key::KeyI,
+        32 => Key::KeyO,
+        33 => Key::KeyP,
+        34 => Key::LeftBracket,
+        35 => Key::RightBracket,
+        38 => Key::KeyA,
+        39 => Key::KeyS,
+        40 => Key::KeyD,
+        41 => Key::KeyF,
+        42 => Key::KeyG,
+        43 => Key::KeyH,
+        44 => Key::KeyJ,
+        45 => Key::KeyK,
+        46 => Key::KeyL,
+        47 => Key::SemiColon,
+        48 => Key::Quote,
+        49 => Key::BackQuote,
+        51 => Key::BackSlash,
+        52 => Key::KeyZ,
+        53 => Key::KeyX,
+        54 => Key::KeyC,
+        55 => Key::KeyV,
+        56 => Key::KeyB,
+        57 => Key::KeyN,
+        58 => Key::KeyM,
+        59 => Key::Comma,
+        60 => Key::Dot,
+        61 => Key::Slash,
+        65 => Key::Space,
+        94 => Key::IntlBackslash,
+        _ => Key::Unknown(keycode),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn key_for_char(_c: char) -> Option<Key> {
+    None
+}
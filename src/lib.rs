@@ -0,0 +1,26 @@
+//! The text transforms behind the `strflatten` hotkey tool, for programs that want to
+//! flatten text themselves instead of shelling out.
+//!
+//! Build a [`Pipeline`] from the built-in steps, your own [`Transform`]s, or the same
+//! [`TransformStep`] names the command line takes:
+//!
+//! ```
+//! use strflatten::{CustomTransforms, Pipeline, TransformOptions, TransformStep};
+//!
+//! let options = TransformOptions {
+//!     join_with: " ".to_string(),
+//!     rules: Vec::new(),
+//!     custom: CustomTransforms::default(),
+//! };
+//! let pipeline = Pipeline::from_steps(&[TransformStep::Dehyphenate, TransformStep::Flatten], &options);
+//! println!("{}", pipeline.apply("hard-\nwrapped\ntext"));
+//! ```
+
+mod normalize;
+pub mod transform;
+pub use transform::{
+    CollapseSpaces, CustomTransforms, Dehyphenate, Flatten, FlattenParagraphs, Nfc, Nfkc, Pipeline,
+    PlainPunctuation, PlainSpaces, Replace, ReplaceRule, SmartFlatten, StripBullets,
+    StripCitations, StripHtml, StripInvisible, StripLineNumbers, StripMarkdown, StripPageHeaders,
+    StripQuotes, Transform, TransformOptions, TransformStep, Trim, TrimLines,
+};
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::easy_rdev_key::PTTKey;
+use crate::hotkey::MouseButton;
+
+const GROUPS: &[&str] = &[
+    "letter",
+    "digit",
+    "function",
+    "modifier",
+    "navigation",
+    "punctuation",
+    "keypad",
+    "other",
+    "mouse",
+];
+
+/// Every name accepted as the key part of a hotkey, with its group.
+pub fn key_names() -> Vec<(&'static str, String)> {
+    let mut names: Vec<(&str, String)> = PTTKey::value_variants()
+        .iter()
+        .filter_map(|key| Some((key.group(), key.to_possible_value()?.get_name().to_string())))
+        .collect();
+    names.extend(
+        MouseButton::ALL
+            .iter()
+            .map(|button| ("mouse", button.name().to_string())),
+    );
+    names
+}
+
+/// Prints every name accepted as the key part of a hotkey, grouped by category.
+pub fn list_keys(json: bool) -> Result<()> {
+    let names = key_names();
+
+    if json {
+        let groups: serde_json::Map<String, serde_json::Value> = GROUPS
+            .iter()
+            .map(|group| {
+                let members = names
+                    .iter()
+                    .filter(|(g, _)| g == group)
+                    .map(|(_, name)| serde_json::Value::from(name.as_str()))
+                    .collect();
+                (group.to_string(), serde_json::Value::Array(members))
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&groups)?);
+        return Ok(());
+    }
+
+    for group in GROUPS {
+        println!("{}:", group);
+        for (_, name) in names.iter().filter(|(g, _)| g == group) {
+            println!("  {}", name);
+        }
+    }
+    println!("---");
+    println!("Combine with modifiers for chords, e.g. Ctrl+Shift+key-j or Alt+f9.");
+    Ok(())
+}
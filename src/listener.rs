@@ -0,0 +1,201 @@
+use rdev::{EventType, Key};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::config::{Action, HotkeyBinding};
+use crate::hotkey::{DoubleTap, ModifierState, Trigger};
+use crate::injected;
+
+/// What the listener decided about a single input event.
+#[derive(Debug, Default)]
+pub struct Verdict {
+    /// Index of the binding to run, if the event activated one.
+    pub activate: Option<usize>,
+    /// Whether the event belongs to a hotkey and should be kept from other applications.
+    pub is_trigger: bool,
+}
+
+/// Turns the raw rdev event stream into hotkey activations.
+pub struct Listener {
+    pub bindings: Vec<HotkeyBinding>,
+    modifiers: ModifierState,
+    double_tap: Option<DoubleTap>,
+    /// Triggers whose press was reported as a trigger and whose release hasn't been seen yet.
+    held_triggers: Vec<Trigger>,
+    /// Minimum time between the end of one activation and the start of the next.
+    cooldown: Duration,
+    /// Set from an activation starting until `finished` is called. No other activation
+    /// starts in the meantime, even one triggered by the running one's own input.
+    busy: bool,
+    /// A binding pressed while busy, run next. Further presses are folded into it.
+    pending: Option<usize>,
+    /// While set, hotkeys are ignored and reach the focused application as usual.
+    paused: bool,
+    ready_at: Option<Instant>,
+}
+
+impl Listener {
+    pub fn new(
+        bindings: Vec<HotkeyBinding>,
+        double_tap: Option<DoubleTap>,
+        cooldown: Duration,
+    ) -> Self {
+        Listener {
+            bindings,
+            modifiers: ModifierState::default(),
+            double_tap,
+            held_triggers: Vec::new(),
+            cooldown,
+            busy: false,
+            pending: None,
+            paused: false,
+            ready_at: None,
+        }
+    }
+
+    /// Swaps in reloaded hotkeys and timings, keeping track of the keys currently held. A
+    /// queued activation is dropped, since its binding may be gone or another one now.
+    pub fn reconfigure(
+        &mut self,
+        bindings: Vec<HotkeyBinding>,
+        double_tap: Option<DoubleTap>,
+        cooldown: Duration,
+    ) {
+        self.bindings = bindings;
+        self.pending = None;
+        self.double_tap = double_tap;
+        self.cooldown = cooldown;
+    }
+
+    // Only the Windows tray asks so far.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes hotkeys, and shows the new state in the tray icon's tooltip.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        crate::health::set_paused(paused);
+        #[cfg(windows)]
+        crate::tray::show_paused(paused);
+    }
+
+    /// Treats every key as released, for when the hook may have missed releases.
+    #[cfg_attr(not(windows), allow(dead_code))] // Only Windows session changes need it
+    pub fn forget_held_keys(&mut self) {
+        self.modifiers = ModifierState::default();
+        self.held_triggers.clear();
+    }
+
+    /// Must be called once an activation reported by `handle` has completed. Returns the
+    /// binding queued in the meantime, which counts as started; call this again after it.
+    pub fn finished(&mut self) -> Option<HotkeyBinding> {
+        if let Some(binding) = self.pending.take().and_then(|id| self.bindings.get(id)) {
+            cancel::reset();
+            return Some(binding.clone());
+        }
+        self.busy = false;
+        self.ready_at = Some(Instant::now() + self.cooldown);
+        None
+    }
+
+    pub fn handle(&mut self, event_type: &EventType) -> Verdict {
+        let mut verdict = Verdict::default();
+
+        // Our own simulated Ctrl+C/Ctrl+V must neither fire hotkeys nor disturb modifier state.
+        if injected::take(event_type) {
+            return verdict;
+        }
+        if self.busy && *event_type == EventType::KeyPress(Key::Escape) {
+            log::info!("Esc pressed. Cancelling...");
+            cancel::request();
+            self.pending = None;
+        }
+
+        // Every key, so with privacy on this would log what's typed.
+        if crate::privacy::shows_content() {
+            log::trace!("{:?}", event_type);
+        }
+        let held = self.modifiers.current();
+        // While paused only the pause toggle is a hotkey.
+        let found = self.bindings.iter().position(|b| {
+            (!self.paused || b.action == Action::TogglePause) && b.key.matches(event_type, held)
+        });
+        if let Some(id) = found {
+            let trigger = self.bindings[id].key.trigger;
+            // A press while the trigger is still down is OS key repeat, not a new press.
+            let is_repeat = self.held_triggers.contains(&trigger);
+            if !is_repeat {
+                self.held_triggers.push(trigger);
+                let fire = self
+                    .double_tap
+                    .as_mut()
+                    .is_none_or(|tap| tap.press(id, trigger));
+                if fire && self.try_activate(id) {
+                    verdict.activate = Some(id);
+                }
+            }
+            verdict.is_trigger = true;
+        } else if let Some(i) = self
+            .held_triggers
+            .iter()
+            .position(|trigger| trigger.is_released_by(event_type))
+        {
+            // The release of a trigger press is part of the trigger too.
+            self.held_triggers.remove(i);
+            verdict.is_trigger = true;
+        }
+
+        if let Some(tap) = self.double_tap.as_mut() {
+            tap.observe(event_type);
+        }
+        // Track modifiers after matching, so a bare modifier (e.g. ShiftRight) can be a trigger.
+        self.modifiers.update(event_type);
+
+        verdict
+    }
+
+    /// Starts an activation of binding `id` unless the cooldown hasn't passed yet. While
+    /// another one is running it's queued instead, so simulated keystrokes never interleave.
+    /// The pause toggle takes effect right away instead and never starts one.
+    pub fn try_activate(&mut self, id: usize) -> bool {
+        if self.bindings[id].action == Action::TogglePause {
+            self.set_paused(!self.paused);
+            match self.paused {
+                true => log::info!("Paused. Hotkeys do nothing until resumed."),
+                false => log::info!("Resumed."),
+            }
+            return false;
+        }
+        if self.paused {
+            log::info!("Ignoring trigger: paused.");
+            return false;
+        }
+        if self.busy && injected::echo_possible() {
+            // More likely the running activation's own input, changed by a key remapper on
+            // its way back, than a real press. Queueing it could loop forever.
+            log::info!("Ignoring trigger: it arrived while simulating input.");
+            return false;
+        }
+        if self.busy {
+            match self.pending {
+                None => log::info!("Queued trigger: it runs once the current activation finishes."),
+                Some(_) => log::info!("Ignoring trigger: an activation is already queued."),
+            }
+            self.pending.get_or_insert(id);
+            return false;
+        }
+        if self
+            .ready_at
+            .is_some_and(|ready_at| Instant::now() < ready_at)
+        {
+            log::info!("Ignoring trigger: still cooling down from the last activation.");
+            return false;
+        }
+        self.busy = true;
+        cancel::reset();
+        log::debug!("Activating {}", self.bindings[id].key);
+        true
+    }
+}
@@ -0,0 +1,155 @@
+//! Diagnostic logging through the `log` macros, for timing and clipboard problems that the
+//! progress messages don't explain.
+//!
+//! The filter takes `RUST_LOG`'s syntax: a default level and/or `module=level` pairs,
+//! comma-separated, e.g. `info,clipboard=trace`. Modules are named without the crate, as
+//! in `src/`, and a module's setting covers its submodules.
+
+use anyhow::{anyhow, Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::rotate::{Retention, RotatingFile};
+
+/// Used when neither `--log-level` nor `RUST_LOG` is given.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+
+/// Which records are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter {
+    default: LevelFilter,
+    /// Most specific module first.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let level = |name: &str| {
+            LevelFilter::from_str(name.trim()).map_err(|_| {
+                anyhow!(
+                    "Unknown log level {:?}. Use off, error, warn, info, debug or trace.",
+                    name.trim()
+                )
+            })
+        };
+        let mut filter = Filter {
+            default: DEFAULT_LEVEL,
+            modules: Vec::new(),
+        };
+        for part in spec.split(',').filter(|part| !part.trim().is_empty()) {
+            match part.split_once('=') {
+                Some((module, name)) => filter
+                    .modules
+                    .push((module.trim().to_string(), level(name)?)),
+                None => filter.default = level(part)?,
+            }
+        }
+        filter
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.matches("::").count()));
+        Ok(filter)
+    }
+}
+
+impl Filter {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let module = target.strip_prefix("strflatten::").unwrap_or(target);
+        self.modules
+            .iter()
+            .find(|(name, _)| {
+                module == name
+                    || module
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .chain(Some(self.default))
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    started: Instant,
+    /// Stderr when not set.
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let module = record
+            .target()
+            .strip_prefix("strflatten::")
+            .unwrap_or(record.target());
+        let line = format!(
+            "[{:>9.3}s {:<5} {}] {}\n",
+            self.started.elapsed().as_secs_f64(),
+            record.level(),
+            module,
+            record.args()
+        );
+        // A log line that can't be written isn't worth failing over.
+        let _ = match &self.file {
+            Some(file) => file
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .write_all(line.as_bytes()),
+            None => io::stderr().write_all(line.as_bytes()),
+        };
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .flush();
+        }
+    }
+}
+
+/// Starts logging with `spec` (or `RUST_LOG`), to `file` if given, rotated by `retention`,
+/// or else stderr.
+pub fn init(spec: Option<&str>, file: Option<&Path>, retention: Retention) -> Result<()> {
+    let filter = match spec
+        .map(str::to_string)
+        .or_else(|| std::env::var("RUST_LOG").ok())
+    {
+        Some(spec) => spec.parse().context("Invalid log filter")?,
+        None => Filter {
+            default: DEFAULT_LEVEL,
+            modules: Vec::new(),
+        },
+    };
+    let file = match file {
+        Some(path) => Some(Mutex::new(RotatingFile::open(path, retention)?)),
+        None => None,
+    };
+    log::set_max_level(filter.max());
+    log::set_boxed_logger(Box::new(Logger {
+        filter,
+        started: Instant::now(),
+        file,
+    }))
+    .context("A logger is already set")
+}
@@ -1,149 +1,2870 @@
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-// Use text-specific clipboard functions
-use clipboard_win::{get_clipboard_string, set_clipboard_string};
-use rdev::{listen, simulate, Event, EventType, Key};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use rdev::{listen, Event};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+use strflatten::transform::{
+    apply_pipeline_timed, CustomTransforms, StepParser, TransformOptions, TransformStep,
+};
 
 // --- Import the key enum module ---
+mod activity;
+
+mod alerts;
+
+mod detach;
+
 mod easy_rdev_key;
-use easy_rdev_key::PTTKey;
 
-// --- CLI Arguments ---
-#[derive(Parser, Debug)]
-#[command(
-    author,
-    version,
-    about,
-    long_about = "Listens for a hotkey, copies selected text, removes newlines, and pastes the result."
-)]
-struct Args {
-    #[arg(
-        short,
-        long,
-        value_enum,
-        help = "Key to trigger the remove-newlines-and-paste action."
-    )]
-    trigger_key: PTTKey,
+mod events;
+
+mod foreground;
+use foreground::ForegroundApp;
+
+mod hotkey;
+use hotkey::{DoubleTap, Hotkey, Modifiers};
+
+mod health;
+
+mod history;
+use history::History;
+
+mod html_format;
+
+mod injected;
+
+mod input;
+use input::{InputSimulator, PasteShortcut, RdevSimulator};
+
+mod instance;
+
+mod ipc;
+
+mod layout;
+
+mod list_keys;
+
+mod listener;
+use listener::Listener;
+
+#[cfg(windows)]
+mod vk;
+
+mod autostart;
+
+mod cancel;
+
+mod capture;
+
+mod cleanup;
+
+mod clipboard;
+use clipboard::Clipboard;
+
+mod command;
+use command::ExternalCommand;
+
+mod completions;
+use completions::{Names, Shell};
+
+mod config;
+use config::{Action, AppSettings, Config, HotkeyBinding};
+
+mod diff;
+
+#[cfg(windows)]
+mod register_hotkey;
+
+mod reload;
+
+mod rotate;
+use rotate::Retention;
+
+mod script;
+use script::Script;
+
+#[cfg(windows)]
+mod overlay;
+
+mod logging;
+
+mod phases;
+use phases::Phases;
+
+mod plugin;
+
+mod privacy;
+
+#[cfg(windows)]
+mod preview;
+
+#[cfg(windows)]
+mod session;
+
+mod sound;
+
+mod stats;
+
+mod status;
+use status::{Status, WithStatus};
+
+mod supervisor;
+use supervisor::Component;
+
+mod timing;
+use timing::LearnedTiming;
+
+mod transformer;
+use transformer::Transformer;
+
+#[cfg(windows)]
+mod tray;
+
+// --- CLI Arguments ---
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = "Listens for a hotkey, copies selected text, runs it through a pipeline of transforms (removing newlines by default), and pastes the result."
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "HOTKEY",
+        help = "Key, chord or mouse button to trigger the copy-transform-and-paste action, e.g. F9, Ctrl+Shift+J or Ctrl+MouseMiddle. Runs --pipeline."
+    )]
+    trigger_key: Option<Hotkey>,
+
+    #[arg(
+        short,
+        long,
+        default_value = " ",
+        help = "String to join lines with in place of each removed newline."
+    )]
+    join_with: String,
+
+    #[arg(
+        short,
+        long,
+        value_parser = StepParser,
+        value_delimiter = ',',
+        default_value = "dehyphenate,flatten",
+        help = "Comma-separated transforms to apply in order, e.g. trim,dehyphenate,flatten. Plugins add steps of their own."
+    )]
+    pipeline: Vec<TransformStep>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "pipeline",
+        help = "Use this [pipelines] entry from the config file as --pipeline."
+    )]
+    named_pipeline: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Paste with this shortcut everywhere, e.g. shift-insert for legacy or remote-desktop apps. By default terminals get shift-insert and everything else ctrl-v."
+    )]
+    paste_with: Option<PasteShortcut>,
+
+    #[arg(
+        long,
+        value_name = "APP",
+        value_delimiter = ',',
+        help = "Ignore hotkeys while one of these apps is focused, e.g. keepass.exe,game.exe. Matches executable names or window classes."
+    )]
+    ignore_app: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "APP",
+        value_delimiter = ',',
+        help = "Only act on hotkeys while one of these apps is focused. Windows only."
+    )]
+    only_app: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Linux only: transform the highlighted text through the PRIMARY selection and paste it with a middle click, leaving the clipboard untouched."
+    )]
+    primary: bool,
+
+    #[arg(
+        long,
+        help = "Windows only: type the result out key by key instead of pasting it, for apps that block paste such as VMs, remote desktops and some banking sites."
+    )]
+    type_out: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 5,
+        help = "Pause after each character typed by --type-out. Raise it if the target app drops characters."
+    )]
+    type_delay: u64,
+
+    #[arg(
+        long,
+        help = "Stop once the transformed text is on the clipboard instead of pasting it, to paste it yourself or into several places."
+    )]
+    copy_only: bool,
+
+    #[arg(
+        long,
+        help = "Instead of listening for hotkeys, run --pipeline over every text copied to the clipboard. Works where key simulation doesn't, such as Wayland."
+    )]
+    watch: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "Transform the clipboard's text in place once and exit, without listening for hotkeys or simulating copy/paste. Same as `transform` with no TEXT."
+    )]
+    once: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Only print results and errors from transform and --once, not progress messages. Their exit codes tell scripts what happened: 3 for an empty clipboard, 4 for a clipboard error, 5 for a keyboard hook or simulation error and 6 when nothing changed."
+    )]
+    quiet: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "How transform and --once report results. json prints one object per text with the original, the result, each step and how long it took."
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "Copy and transform as usual, but print the result instead of pasting it, then put the clipboard back. For trying out new pipelines and [[rule]]s."
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "HOTKEY",
+        help = "Hotkey that pastes an earlier result again. Each further press steps one result further back."
+    )]
+    history_key: Option<Hotkey>,
+
+    #[arg(
+        long,
+        value_name = "HOTKEY",
+        help = "Hotkey that pastes the last copied text as it was before transforming. Select the transformed text first to replace it."
+    )]
+    undo_key: Option<Hotkey>,
+
+    #[arg(
+        long,
+        value_name = "HOTKEY",
+        help = "Hotkey that runs --pipeline over what's already on the clipboard and pastes it, without copying first. For text that arrived some other way, e.g. by clipboard sync."
+    )]
+    clipboard_key: Option<Hotkey>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 20,
+        help = "How many past results to keep for --history-key."
+    )]
+    history_size: usize,
+
+    #[arg(
+        long,
+        help = "Keep the history in history.json next to the config file, so it survives restarts and `history` can list it. Off by default since it writes copied text to disk."
+    )]
+    save_history: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Start with this [[profile]] from the config file active instead of the top-level options."
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "HOTKEY",
+        help = "Hotkey that switches to the next [[profile]], and back to the top-level options after the last one."
+    )]
+    profile_key: Option<Hotkey>,
+
+    #[arg(
+        long,
+        value_name = "HOTKEY",
+        help = "Hotkey that pauses every other hotkey until it's pressed again, e.g. while gaming. Paused hotkeys reach the focused application as usual."
+    )]
+    pause_key: Option<Hotkey>,
+
+    #[arg(
+        long,
+        help = "Append a collapse-spaces step to the pipeline, normalizing runs of spaces/tabs."
+    )]
+    collapse_spaces: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "TOML config file holding defaults for these options plus [[hotkey]], [[rule]] and [[app]] tables. Defaults to flatten-string/config.toml in the user config directory (%APPDATA% on Windows)."
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Show an icon in the notification area whose menu pauses hotkeys, switches profiles, opens the config file, shows recent activity and quits (Windows only)."
+    )]
+    tray: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "EVENTS",
+        help = "Comma-separated activation outcomes to show a notification for: success, skipped (e.g. nothing selected) and failure. Also shows the --tray icon, which they come from (Windows only)."
+    )]
+    notify: Vec<activity::Kind>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "DESTINATIONS",
+        default_values_t = [alerts::Destination::Console],
+        help = "Comma-separated places to report errors: console, notification (from the --tray icon, which it shows; Windows only), log (at error level, to --log-file if given) and webhook (POSTed as JSON to --error-webhook)."
+    )]
+    errors_to: Vec<alerts::Destination>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Where --errors-to webhook posts each error, as JSON with the message in `text` and `content` for Slack and Discord. Sent with curl."
+    )]
+    error_webhook: Option<String>,
+
+    #[arg(
+        long,
+        help = "Show a brief label near the mouse cursor after each activation saying what it did, e.g. which pipeline ran (Windows only)."
+    )]
+    overlay: bool,
+
+    #[arg(
+        long,
+        help = "Print a word-level diff of each original and result, marking removed newlines (⏎) and spaces (·), to see why a pipeline produced what it did. From transform and --once it goes to stderr."
+    )]
+    diff: bool,
+
+    #[arg(
+        long,
+        help = "Show each result and what it changed in a window, and only paste it once confirmed (Windows only)."
+    )]
+    preview: bool,
+
+    #[arg(
+        long,
+        help = "Print how long each phase of every activation took: the copy and the wait for it, reading the clipboard, each pipeline step, writing the clipboard, the paste delay and the paste."
+    )]
+    timing: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append a line of JSON to this file for each activation: when, which hotkey and pipeline, in which app, how many characters, how long it took and how it ended. Never the text itself."
+    )]
+    event_log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "EVENTS",
+        help = "Comma-separated activation outcomes to play a sound for: success (a click), skipped (a low tone) and failure (two falling tones)."
+    )]
+    sounds: Vec<activity::Kind>,
+
+    #[arg(
+        long,
+        value_name = "WAV",
+        help = "Play this WAV file on success instead of the built-in click, with --sounds success."
+    )]
+    success_sound: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "WAV",
+        help = "Play this WAV file when nothing was done instead of the built-in tone, with --sounds skipped."
+    )]
+    skipped_sound: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "WAV",
+        help = "Play this WAV file on failure instead of the built-in tones, with --sounds failure."
+    )]
+    failure_sound: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = 100,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        value_name = "PERCENT",
+        help = "Volume of the --sounds cues, from 0 to 100."
+    )]
+    volume: u8,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Load transform plugins (.dll, .so or .dylib) from this directory. Defaults to plugins/ next to the config file."
+    )]
+    plugin_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Start listening even if another instance already is, e.g. to run two profiles side by side. Their hotkeys must not overlap."
+    )]
+    allow_multiple: bool,
+
+    #[arg(
+        long,
+        help = "Start listening in the background, detached from this console, and return right away. Its output is appended to listener.log next to the config file. To start it at login, see autostart enable."
+    )]
+    detach: bool,
+
+    #[arg(
+        long,
+        value_name = "FILTER",
+        help = "Which diagnostics to log: off, error, warn (the default), info, debug or trace, optionally per module, e.g. info,clipboard=trace. Same syntax as RUST_LOG, which it overrides."
+    )]
+    log_level: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append diagnostics to this file instead of stderr."
+    )]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MB",
+        default_value_t = 10,
+        help = "Start a new --log-file, --event-log or --detach listener.log once it would grow past this size, keeping --log-keep old ones. 0 disables the limit."
+    )]
+    log_max_size: u64,
+
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = 0,
+        help = "Also start a new log file once it was started this many days ago. 0 disables the limit."
+    )]
+    log_max_age: u64,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 3,
+        help = "How many old log files to keep when starting a new one, as <name>.1 (the newest) to <name>.N. 0 deletes them."
+    )]
+    log_keep: usize,
+
+    #[arg(
+        long,
+        help = "Show copied text in the console and logs. By default only its length and a hash are shown, since it may be a password or confidential."
+    )]
+    show_content: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "SECRETS",
+        default_values_t = privacy::Secret::ALL,
+        help = "Comma-separated kinds of credential to redact wherever copied text is shown, and in the saved history: private-key, jwt, api-key, bearer and password. All of them by default; [[secret]] tables in the config add patterns of their own."
+    )]
+    redact: Vec<privacy::Secret>,
+
+    #[arg(
+        long,
+        help = "Don't watch the config file for changes. By default edits to hotkeys, pipelines and delays apply without restarting."
+    )]
+    no_reload: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Only fire when a hotkey is pressed twice within this many milliseconds."
+    )]
+    double_tap: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Swallow hotkey presses so the focused application never receives them."
+    )]
+    grab: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 500,
+        help = "Ignore hotkeys for this many milliseconds after an activation finishes."
+    )]
+    cooldown: u64,
+
+    #[arg(
+        long,
+        help = "Wait for a key press, print its trigger name, and offer to save it to --config."
+    )]
+    capture_key: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "hook",
+        help = "How hotkeys are detected. register-hotkey works without administrator privileges but only supports key chords."
+    )]
+    hotkey_backend: HotkeyBackend,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 1000,
+        help = "How long to wait for the simulated copy to reach the clipboard before giving up."
+    )]
+    copy_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 1000,
+        help = "How long to keep retrying while another app has the clipboard open."
+    )]
+    clipboard_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 150,
+        help = "How long to wait for the clipboard to settle where its changes can't be detected, e.g. Linux."
+    )]
+    settle_delay: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        help = "Extra wait after the transformed text is on the clipboard, before pasting. Raise it if the old text gets pasted."
+    )]
+    paste_delay: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 30,
+        help = "Pause between the key presses of a simulated shortcut."
+    )]
+    key_delay: u64,
+
+    #[arg(
+        long,
+        help = "Learn how long each app takes to copy and wait about that long instead of --copy-timeout, failing fast in quick apps and waiting longer in slow ones. Needs clipboard change detection (Windows, macOS)."
+    )]
+    adaptive_timeout: bool,
+
+    #[arg(
+        long,
+        value_name = "MB",
+        default_value_t = 5,
+        help = "Skip selections whose text is larger than this in UTF-8, to avoid freezing on huge copies. 0 disables the limit."
+    )]
+    max_size: u64,
+
+    #[arg(
+        long,
+        help = "Replace lone surrogates in the copied text instead of failing. Some apps put malformed UTF-16 on the clipboard."
+    )]
+    lossy_utf16: bool,
+
+    #[arg(
+        long,
+        help = "Restore the clipboard's previous contents after pasting instead of leaving the transformed text on it."
+    )]
+    restore_clipboard: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 300,
+        help = "How long to wait after pasting before --restore-clipboard puts the old contents back."
+    )]
+    restore_delay: u64,
+
+    #[arg(
+        long,
+        help = "Keep the selection's other clipboard formats (HTML, RTF, images), only replacing its plain text (Windows only)."
+    )]
+    preserve_formats: bool,
+
+    #[arg(
+        long,
+        help = "Also flatten the selection's HTML clipboard format, keeping links and formatting. Implies --preserve-formats (Windows only)."
+    )]
+    transform_html: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum HotkeyBackend {
+    /// rdev's low-level keyboard/mouse hook
+    Hook,
+    /// The Win32 RegisterHotKey API
+    RegisterHotkey,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// The transformed text, plus progress messages unless --quiet
+    Text,
+    /// A JSON object per transformed text, for editor plugins and scripts
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Listen for hotkeys (the default when no command is given)
+    Run,
+    /// Run --pipeline once over TEXT, stdin or files and print the result, or over the clipboard in place
+    Transform {
+        #[arg(
+            help = "Text to transform, joined with spaces, or - for stdin. Without it, piped stdin is transformed, or else the clipboard's text."
+        )]
+        text: Vec<String>,
+
+        #[arg(
+            long = "in",
+            value_name = "FILE",
+            conflicts_with = "text",
+            help = "Transform this file instead. Repeat it with --in-place to clean several files at once."
+        )]
+        input: Vec<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["text", "in_place"],
+            help = "Write the result to this file instead of stdout."
+        )]
+        out: Option<PathBuf>,
+
+        #[arg(
+            long,
+            requires = "input",
+            help = "Overwrite each --in file with its result."
+        )]
+        in_place: bool,
+    },
+    /// List the results saved by --save-history, newest first
+    History {
+        #[arg(long, help = "Delete the saved history instead.")]
+        clear: bool,
+    },
+    /// Show the running listener's counters: how activations ended, how long they took, how
+    /// much text went through and how often the clipboard was busy
+    Stats {
+        #[arg(long, help = "Print the counters as a JSON object.")]
+        json: bool,
+    },
+    /// Show whether a listener is running, its profile, whether it's paused and how its
+    /// last activation went. Exits with an error when none is running
+    Status {
+        #[arg(long, help = "Print the status as a JSON object.")]
+        json: bool,
+    },
+    /// Print every supported trigger key name
+    ListKeys {
+        #[arg(long, help = "Print the names as a JSON object of groups.")]
+        json: bool,
+    },
+    /// Create or check the config file (--config, or the default location)
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Start listening at login with the options given along with this command, e.g.
+    /// `strflatten autostart enable --tray --trigger-key F9`
+    Autostart {
+        #[command(subcommand)]
+        command: AutostartCommand,
+    },
+    /// Print a completion script, e.g. `strflatten completions bash >> ~/.bashrc`
+    Completions { shell: Shell },
+    /// List names for the completion scripts to offer
+    #[command(hide = true)]
+    Complete {
+        names: Names,
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Write a commented config file with every option at its default
+    Init {
+        #[arg(long, help = "Overwrite the file if it already exists.")]
+        force: bool,
+    },
+    /// Parse the config file and report errors with their line and column
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+enum AutostartCommand {
+    /// Register the listener to start at login, replacing any earlier registration
+    Enable {
+        #[arg(
+            long,
+            help = "Start it with administrator privileges through Task Scheduler instead of the Run key, so it can see keys pressed in elevated windows. Needs an elevated prompt (Windows only)."
+        )]
+        elevated: bool,
+    },
+    /// Stop starting the listener at login
+    Disable,
+}
+
+// --- Core Logic ---
+
+/// Everything an activation needs besides the hotkey's own pipeline.
+struct ActivationSettings {
+    transform: TransformOptions,
+    /// Runs the pipelines, on the transformer component's thread.
+    transformer: Transformer,
+    /// How long to wait for a simulated copy to reach the clipboard.
+    copy_timeout: Duration,
+    /// How long to wait for the clipboard where its changes can't be detected.
+    settle_delay: Duration,
+    /// Extra wait between setting the clipboard and pasting.
+    paste_delay: Duration,
+    /// Per-app copy timeouts to use instead of `copy_timeout`, with `--adaptive-timeout`.
+    learned_timing: Option<RefCell<LearnedTiming>>,
+    /// The system clipboard, shared by every activation.
+    clipboard: RefCell<Box<dyn Clipboard>>,
+    /// How long to keep retrying while another app has the clipboard open.
+    clipboard_timeout: Duration,
+    /// Types the copy/paste shortcuts into the focused application.
+    input: RefCell<Box<dyn InputSimulator>>,
+    /// Per-app shortcuts and pipelines from the config file.
+    apps: Vec<AppSettings>,
+    /// Paste shortcut for every app without its own, instead of detecting terminals.
+    paste_with: Option<PasteShortcut>,
+    /// Apps in which hotkeys do nothing.
+    ignore_apps: Vec<String>,
+    /// If not empty, the only apps in which hotkeys act.
+    only_apps: Vec<String>,
+    /// Work on the primary selection instead of copying and pasting through the clipboard.
+    primary: bool,
+    /// Type results out with this pause per character instead of pasting them.
+    type_delay: Option<Duration>,
+    /// Leave results on the clipboard without pasting them.
+    copy_only: bool,
+    /// Largest clipboard text, in bytes, that will be transformed.
+    max_size: Option<usize>,
+    /// Repair malformed clipboard UTF-16 instead of failing.
+    lossy_utf16: bool,
+    /// Put the pre-existing clipboard contents back this long after pasting.
+    restore_after: Option<Duration>,
+    /// Keep the copied selection's non-text formats (HTML, RTF, images) instead of
+    /// replacing everything with plain text.
+    preserve_formats: bool,
+
+    /// Also flatten the HTML Format copy of the selection, keeping its markup.
+    transform_html: bool,
+    /// Past results, shared by every activation.
+    history: RefCell<History>,
+    /// Print results instead of pasting them.
+    dry_run: bool,
+    /// Outcomes to show a notification for.
+    notify: Vec<activity::Kind>,
+    /// Sounds to play for outcomes.
+    sounds: sound::Cues,
+    /// Show what each activation did near the cursor.
+    overlay: bool,
+    /// Print what each pipeline changed.
+    diff: bool,
+    /// Ask before pasting each result.
+    #[cfg_attr(not(windows), allow(dead_code))] // Rejected elsewhere
+    preview: bool,
+    /// Where to append a JSON line per activation.
+    event_log: Option<PathBuf>,
+    /// When to start a new event log.
+    retention: Retention,
+    /// How long each phase of the running activation took, with `--timing`.
+    phases: Phases,
+}
+
+impl ActivationSettings {
+    fn new(args: &Args, config: &Config) -> Result<Self> {
+        let clipboard = clipboard::system(Duration::from_millis(args.clipboard_timeout))?;
+        let input = Box::new(RdevSimulator {
+            key_delay: Duration::from_millis(args.key_delay),
+        });
+        Self::with_backends(args, config, clipboard, input)
+    }
+
+    /// Like `new`, but with the clipboard and input to use instead of the system's.
+    fn with_backends(
+        args: &Args,
+        config: &Config,
+        clipboard: Box<dyn Clipboard>,
+        input: Box<dyn InputSimulator>,
+    ) -> Result<Self> {
+        if args.primary && !cfg!(target_os = "linux") {
+            return Err(anyhow!(
+                "--primary needs a primary selection, which only Linux has"
+            ));
+        }
+        // Elsewhere the focused app is never known, so nothing would be allowed.
+        if !args.only_app.is_empty() && !cfg!(windows) {
+            return Err(anyhow!(
+                "--only-app needs to know the focused app, which is only supported on Windows"
+            ));
+        }
+        if args.type_out && !cfg!(windows) {
+            return Err(anyhow!("--type-out is only supported on Windows"));
+        }
+        if args.tray && !cfg!(windows) {
+            return Err(anyhow!("--tray is only supported on Windows"));
+        }
+        if !args.notify.is_empty() && !cfg!(windows) {
+            return Err(anyhow!("--notify is only supported on Windows"));
+        }
+        if args.errors_to.contains(&alerts::Destination::Notification) && !cfg!(windows) {
+            return Err(anyhow!(
+                "--errors-to notification is only supported on Windows"
+            ));
+        }
+        if args.overlay && !cfg!(windows) {
+            return Err(anyhow!("--overlay is only supported on Windows"));
+        }
+        if args.preview && !cfg!(windows) {
+            return Err(anyhow!("--preview is only supported on Windows"));
+        }
+        // Elsewhere a snapshot only holds the text, so the other formats would be lost anyway.
+        if args.preserve_formats && !cfg!(windows) {
+            return Err(anyhow!("--preserve-formats is only supported on Windows"));
+        }
+        if args.transform_html && !cfg!(windows) {
+            return Err(anyhow!("--transform-html is only supported on Windows"));
+        }
+        if args.copy_only && (args.type_out || args.restore_clipboard) {
+            return Err(anyhow!(
+                "--copy-only leaves the result on the clipboard, so it can't be combined with --type-out or --restore-clipboard"
+            ));
+        }
+        if args.type_out && args.primary {
+            return Err(anyhow!(
+            "--type-out and --primary can't be combined; --primary always pastes with a middle click"
+        ));
+        }
+
+        Ok(ActivationSettings {
+            transform: transform_options(args, config)?,
+            transformer: Transformer::spawn(),
+            copy_timeout: Duration::from_millis(args.copy_timeout),
+            settle_delay: Duration::from_millis(args.settle_delay),
+            paste_delay: Duration::from_millis(args.paste_delay),
+            learned_timing: match args.adaptive_timeout {
+                true => Some(RefCell::new(LearnedTiming::load()?)),
+                false => None,
+            },
+            input: RefCell::new(input),
+            apps: config.apps.clone(),
+            ignore_apps: args.ignore_app.clone(),
+            only_apps: args.only_app.clone(),
+            paste_with: args.paste_with,
+            primary: args.primary,
+            type_delay: args
+                .type_out
+                .then(|| Duration::from_millis(args.type_delay)),
+            copy_only: args.copy_only,
+            clipboard: RefCell::new(clipboard),
+            clipboard_timeout: Duration::from_millis(args.clipboard_timeout),
+            max_size: (args.max_size > 0).then(|| args.max_size as usize * 1_000_000),
+            lossy_utf16: args.lossy_utf16,
+            // A dry run has nothing to wait for before putting back what it copied over.
+            restore_after: match (args.restore_clipboard, args.dry_run) {
+                (true, _) => Some(Duration::from_millis(args.restore_delay)),
+                (false, true) => Some(Duration::ZERO),
+                (false, false) => None,
+            },
+            preserve_formats: args.preserve_formats || args.transform_html,
+            transform_html: args.transform_html,
+            history: RefCell::new(match (args.save_history, history::default_path()) {
+                (true, Some(path)) => History::persistent(args.history_size, path)?,
+                _ => History::new(args.history_size),
+            }),
+            dry_run: args.dry_run,
+            notify: args.notify.clone(),
+            sounds: sound::Cues::new(
+                &args.sounds,
+                [
+                    args.success_sound.as_deref(),
+                    args.skipped_sound.as_deref(),
+                    args.failure_sound.as_deref(),
+                ],
+                args.volume,
+            )?,
+            overlay: args.overlay,
+            preview: args.preview,
+            diff: args.diff,
+            event_log: args.event_log.clone(),
+            retention: retention(args),
+            phases: Phases::new(args.timing),
+        })
+    }
+
+    /// Whether hotkeys may act while `app` is focused, going by `--ignore-app` and
+    /// `--only-app`.
+    fn allows(&self, app: &ForegroundApp) -> bool {
+        let listed = |names: &[String]| names.iter().any(|name| app.is(name));
+        !listed(&self.ignore_apps) && (self.only_apps.is_empty() || listed(&self.only_apps))
+    }
+
+    /// The `[[app]]` table for `app`, if there is one.
+    fn app_settings(&self, app: &ForegroundApp) -> Option<&AppSettings> {
+        self.apps.iter().find(|settings| app.is(&settings.name))
+    }
+
+    /// The steps `binding` runs while `app` is focused, or `None` if it runs none.
+    fn pipeline_for<'a>(
+        &'a self,
+        binding: &'a HotkeyBinding,
+        app: &ForegroundApp,
+    ) -> Option<&'a [TransformStep]> {
+        if !binding.action.transforms() {
+            return None;
+        }
+        // The focused app's `[[app]]` pipeline replaces the hotkey's.
+        let app_pipeline = self
+            .app_settings(app)
+            .and_then(|settings| settings.pipeline.as_deref());
+        Some(app_pipeline.unwrap_or(&binding.pipeline))
+    }
+
+    /// The copy shortcut for the focused app: its `[[app]]` override or the standard one.
+    fn copy_chord(&self) -> Hotkey {
+        let app = foreground::current();
+        self.app_settings(&app)
+            .and_then(|settings| settings.copy)
+            .unwrap_or_else(input::standard_copy)
+    }
+
+    /// The paste shortcut for the focused app: its `[[app]]` override, `--paste-with`,
+    /// Shift+Insert for known terminals, or the standard one.
+    fn paste_chord(&self) -> Hotkey {
+        let app = foreground::current();
+        let configured = self.app_settings(&app).and_then(|settings| settings.paste);
+        if let Some(chord) = configured {
+            return chord;
+        }
+        if let Some(shortcut) = self.paste_with {
+            return shortcut.chord();
+        }
+        if app.is_terminal() {
+            log::info!("Detected terminal {}.", app);
+            return PasteShortcut::ShiftInsert.chord();
+        }
+        PasteShortcut::CtrlV.chord()
+    }
+
+    /// Whether `size` bytes of text is over `--max-size`, saying so if it is.
+    fn exceeds_max_size(&self, size: usize) -> bool {
+        let Some(limit) = self.max_size else {
+            return false;
+        };
+        if size <= limit {
+            return false;
+        }
+        println!(
+            "Clipboard text is {:.1} MB, over the {:.1} MB limit (--max-size). Skipping.",
+            size as f64 / 1e6,
+            limit as f64 / 1e6
+        );
+        true
+    }
+}
+
+/// How an activation that didn't fail ended.
+enum Outcome {
+    /// This many characters were pasted, typed out or left on the clipboard, from `copied`
+    /// characters of selection if they were transformed.
+    Done {
+        copied: Option<usize>,
+        chars: usize,
+    },
+    /// Nothing was pasted, for this reason.
+    Skipped(&'static str),
+    ProfileSwitched,
+}
+
+impl Outcome {
+    fn done(text: &str) -> Self {
+        Outcome::Done {
+            copied: None,
+            chars: text.chars().count(),
+        }
+    }
+
+    fn transformed(copied: usize, text: &str) -> Self {
+        Outcome::Done {
+            copied: Some(copied),
+            chars: text.chars().count(),
+        }
+    }
+}
+
+fn transform_and_paste(
+    binding: &HotkeyBinding,
+    app: &ForegroundApp,
+    settings: &ActivationSettings,
+) -> Result<Outcome> {
+    let pipeline = settings
+        .pipeline_for(binding, app)
+        .unwrap_or(&binding.pipeline);
+    if binding.action.transforms() && pipeline != binding.pipeline.as_slice() {
+        println!("Using the [[app]] pipeline for {}.", app);
+    }
+    if binding.action.transforms() && pipeline.is_empty() {
+        println!("No pipeline for {}. Skipping.", app);
+        return Ok(Outcome::Skipped("No pipeline for this app"));
+    }
+    settings.phases.start();
+
+    // 0. Remember what was on the clipboard before we touch it
+    //    Kept by `cleanup`, so it's put back even if the activation never gets to step 9.
+    if settings.restore_after.is_some() {
+        let saved = settings
+            .clipboard
+            .borrow_mut()
+            .take_snapshot()
+            .context("Failed to save the original clipboard")
+            .status(Status::ClipboardError)?;
+        settings.phases.end("clipboard save");
+        cleanup::keep_clipboard(saved, settings.clipboard_timeout);
+    }
+
+    let held = binding.key.modifiers;
+    let result = match binding.action {
+        Action::Transform if settings.primary => primary_transform_paste(held, pipeline, settings),
+        Action::Transform => copy_transform_paste(held, pipeline, app, settings),
+        Action::TransformClipboard => {
+            println!("Trigger key pressed. Transforming the clipboard...");
+            settings
+                .input
+                .borrow_mut()
+                .release_keys(&held.keys())
+                .context("Failed to release trigger modifiers")
+                .status(Status::SimulationError)?;
+            transform_clipboard_and_paste(pipeline, settings)
+        }
+        Action::History => paste_history(held, settings),
+        Action::Undo => paste_original(held, settings),
+        Action::NextProfile => Ok(Outcome::ProfileSwitched), // Done by `Runtime::run_action`
+        Action::TogglePause => Ok(Outcome::Skipped("Paused")), // Done by the listener
+    };
+
+    // 9. Restore the original clipboard once the target app has read the paste
+    if let Some(delay) = settings.restore_after {
+        thread::sleep(delay);
+        if let Some(saved) = cleanup::take_clipboard() {
+            // Reported on its own, so it doesn't hide how the activation itself went.
+            match settings.clipboard.borrow_mut().restore_snapshot(&saved) {
+                Ok(()) => println!("Restored original clipboard."),
+                Err(e) => log::error!("Failed to restore the original clipboard: {:#}", e),
+            }
+            settings.phases.end("restore");
+        }
+    }
+    settings.phases.report();
+
+    result
+}
+
+fn copy_transform_paste(
+    held: Modifiers,
+    pipeline: &[TransformStep],
+    app: &ForegroundApp,
+    settings: &ActivationSettings,
+) -> Result<Outcome> {
+    let copy = settings.copy_chord();
+    println!("Trigger key pressed. Simulating Copy ({})...", copy);
+
+    // 1. Simulate the copy shortcut
+    //    Release the chord's modifiers first, or Ctrl+Shift+J would copy with Ctrl+Shift+C.
+    settings
+        .input
+        .borrow_mut()
+        .release_keys(&held.keys())
+        .context("Failed to release trigger modifiers")
+        .status(Status::SimulationError)?;
+    let before_copy = settings.clipboard.borrow().sequence_number();
+    settings
+        .input
+        .borrow_mut()
+        .send_chord(&copy)
+        .context("Failed to simulate the copy shortcut")
+        .status(Status::SimulationError)?;
+    settings.phases.end("copy");
+
+    // 2. Wait for clipboard to update
+    //    The target app handles the copy asynchronously, so proceed as soon as it lands.
+    //    If it never does, nothing was selected; pasting the stale clipboard would be wrong.
+    let app_name = app.to_string();
+    let timeout = match &settings.learned_timing {
+        Some(learned) => learned
+            .borrow()
+            .copy_timeout(&app_name, settings.copy_timeout),
+        None => settings.copy_timeout,
+    };
+    log::debug!("Waiting up to {:?} for {} to copy", timeout, app_name);
+    let copy_started = Instant::now();
+    let copied = clipboard::wait_for_change(
+        settings.clipboard.borrow().as_ref(),
+        before_copy,
+        timeout,
+        settings.settle_delay,
+    );
+    settings.phases.end("copy wait");
+    // Without a sequence number there's no latency to measure.
+    if let (Some(learned), Some(_)) = (&settings.learned_timing, before_copy) {
+        let latency = copied.then(|| copy_started.elapsed());
+        learned.borrow_mut().record_copy(&app_name, latency);
+    }
+    if !copied {
+        println!(
+            "Clipboard didn't change within {:?}. Is anything selected? Skipping.",
+            timeout
+        );
+        return Ok(Outcome::Skipped("Nothing selected"));
+    }
+    cancel::check()?;
+
+    transform_clipboard_and_paste(pipeline, settings)
+}
+
+// Runs the pipeline over the clipboard's text and pastes the result: the rest of
+// `copy_transform_paste` once the selection is copied, and all of `transform-clipboard`.
+fn transform_clipboard_and_paste(
+    pipeline: &[TransformStep],
+    settings: &ActivationSettings,
+) -> Result<Outcome> {
+    // 3. Refuse huge selections before reading them into memory, where the backend can tell
+    let size = settings.clipboard.borrow_mut().text_size()?;
+    if size.is_some_and(|size| settings.exceeds_max_size(size)) {
+        return Ok(Outcome::Skipped("Selection too large"));
+    }
+
+    println!("Getting text from clipboard...");
+
+    // 4. Get text from clipboard
+    //    With --preserve-formats, keep a copy of everything else that was copied too.
+    let copied = match settings.preserve_formats {
+        true => Some(
+            settings
+                .clipboard
+                .borrow_mut()
+                .take_snapshot()
+                .context("Failed to read the copied formats")
+                .status(Status::ClipboardError)?,
+        ),
+        false => None,
+    };
+    let original_text = match &copied {
+        Some(copied) => copied
+            .text(settings.lossy_utf16)
+            .context("Failed to decode the copied text")
+            .status(Status::ClipboardError)?,
+        None => settings
+            .clipboard
+            .borrow_mut()
+            .get_text(settings.lossy_utf16)
+            .context("Failed to get text from clipboard. Was text copied?")
+            .status(Status::ClipboardError)?,
+    };
+    if settings.exceeds_max_size(original_text.len()) {
+        return Ok(Outcome::Skipped("Selection too large"));
+    }
+
+    if original_text.is_empty() {
+        println!("Clipboard text is empty. Skipping.");
+        return Ok(Outcome::Skipped("Clipboard text is empty"));
+    }
+    let concealed = settings.clipboard.borrow_mut().is_concealed();
+    let copied_chars = original_text.chars().count();
+    settings.phases.end("clipboard read");
+
+    // 5. Run the transform pipeline
+    let (modified_text, timings) =
+        settings
+            .transformer
+            .apply_timed(&original_text, pipeline, &settings.transform)?;
+    settings.phases.end_steps(pipeline, &timings);
+    cancel::check()?;
+    if settings.diff && !concealed {
+        print_diff(&original_text, &modified_text);
+    }
+    if settings.dry_run {
+        print_dry_run(pipeline, &modified_text);
+        return Ok(Outcome::transformed(copied_chars, &modified_text));
+    }
+    #[cfg(windows)]
+    if settings.preview {
+        let confirmed = preview::confirm(&original_text, &modified_text)?;
+        settings.phases.end("preview");
+        if !confirmed {
+            println!("Paste cancelled in the preview.");
+            return Ok(Outcome::Skipped("Cancelled in the preview"));
+        }
+    }
+    println!(
+        "Applied {:?}. Result: {}",
+        pipeline,
+        privacy::Excerpt::new(&modified_text).concealed(concealed)
+    );
+    settings
+        .history
+        .borrow_mut()
+        .push(original_text.clone(), modified_text.clone(), concealed);
+
+    if let Some(delay) = settings.type_delay {
+        println!("Typing modified text...");
+        let typed = settings
+            .input
+            .borrow_mut()
+            .type_text(&modified_text, delay)
+            .context("Failed to type the modified text")
+            .status(Status::SimulationError);
+        settings.phases.end("typing");
+        return typed.map(|()| Outcome::transformed(copied_chars, &modified_text));
+    }
+
+    // 6. Set modified text to clipboard
+    let mut clipboard = settings.clipboard.borrow_mut();
+    let before_set = clipboard.sequence_number();
+    match &copied {
+        Some(copied) => {
+            let mut modified = copied.with_text(&modified_text);
+            if settings.transform_html {
+                flatten_html(
+                    &mut modified,
+                    clipboard.html_format(),
+                    &settings.transform.join_with,
+                );
+            }
+            clipboard
+                .restore_snapshot(&modified)
+                .context("Failed to set modified text to clipboard")
+                .status(Status::ClipboardError)?
+        }
+        None => clipboard
+            .set_text(&modified_text)
+            .context("Failed to set modified text to clipboard")
+            .status(Status::ClipboardError)?,
+    }
+
+    // 7. Wait for clipboard to update again
+    clipboard::wait_for_change(
+        clipboard.as_ref(),
+        before_set,
+        settings.copy_timeout,
+        settings.settle_delay,
+    );
+    drop(clipboard);
+    settings.phases.end("clipboard write");
+    if settings.copy_only {
+        println!("Left the modified text on the clipboard (--copy-only).");
+        return Ok(Outcome::transformed(copied_chars, &modified_text));
+    }
+    log::debug!("Pasting after {:?}", settings.paste_delay);
+    thread::sleep(settings.paste_delay);
+    settings.phases.end("paste delay");
+    cancel::check()?;
+
+    // 8. Simulate the paste shortcut the focused app understands
+    let paste = settings.paste_chord();
+    println!("Pasting modified text ({})...", paste);
+    settings
+        .input
+        .borrow_mut()
+        .send_chord(&paste)
+        .context("Failed to simulate the paste shortcut")
+        .status(Status::SimulationError)?;
+    settings.phases.end("paste");
+
+    println!("Paste simulated.");
+    Ok(Outcome::transformed(copied_chars, &modified_text))
+}
+
+// Like `copy_transform_paste`, but reads the highlighted text straight from the primary
+// selection and pastes with a middle click, so the clipboard is never touched.
+fn primary_transform_paste(
+    held: Modifiers,
+    pipeline: &[TransformStep],
+    settings: &ActivationSettings,
+) -> Result<Outcome> {
+    println!("Trigger key pressed. Reading the primary selection...");
+
+    // 1. Get the highlighted text
+    settings
+        .input
+        .borrow_mut()
+        .release_keys(&held.keys())
+        .context("Failed to release trigger modifiers")
+        .status(Status::SimulationError)?;
+    let original_text = settings
+        .clipboard
+        .borrow_mut()
+        .get_primary_text()
+        .context("Failed to read the primary selection. Is anything highlighted?")
+        .status(Status::ClipboardError)?;
+    settings.phases.end("selection read");
+    if original_text.is_empty() {
+        println!("Primary selection is empty. Skipping.");
+        return Ok(Outcome::Skipped("Nothing selected"));
+    }
+    if settings.exceeds_max_size(original_text.len()) {
+        return Ok(Outcome::Skipped("Selection too large"));
+    }
+    let concealed = settings.clipboard.borrow_mut().is_concealed();
+    let copied_chars = original_text.chars().count();
+
+    // 2. Run the transform pipeline
+    let (modified_text, timings) =
+        settings
+            .transformer
+            .apply_timed(&original_text, pipeline, &settings.transform)?;
+    settings.phases.end_steps(pipeline, &timings);
+    cancel::check()?;
+    if settings.diff && !concealed {
+        print_diff(&original_text, &modified_text);
+    }
+    if settings.dry_run {
+        print_dry_run(pipeline, &modified_text);
+        return Ok(Outcome::transformed(copied_chars, &modified_text));
+    }
+    println!(
+        "Applied {:?}. Result: {}",
+        pipeline,
+        privacy::Excerpt::new(&modified_text).concealed(concealed)
+    );
+    settings
+        .history
+        .borrow_mut()
+        .push(original_text, modified_text.clone(), concealed);
+
+    // 3. Offer the result as the primary selection and middle-click it in
+    settings
+        .clipboard
+        .borrow_mut()
+        .set_primary_text(&modified_text)
+        .context("Failed to set the primary selection")
+        .status(Status::ClipboardError)?;
+    settings.phases.end("selection write");
+    if settings.copy_only {
+        println!("Left the modified text in the primary selection (--copy-only).");
+        return Ok(Outcome::transformed(copied_chars, &modified_text));
+    }
+    thread::sleep(settings.paste_delay);
+    settings.phases.end("paste delay");
+    cancel::check()?;
+    println!("Pasting modified text (middle click)...");
+    settings
+        .input
+        .borrow_mut()
+        .send_middle_click()
+        .context("Failed to simulate a middle click")
+        .status(Status::SimulationError)?;
+    settings.phases.end("paste");
+
+    println!("Paste simulated.");
+    Ok(Outcome::transformed(copied_chars, &modified_text))
+}
+
+// --- Watch Mode ---
+
+/// How often `--watch` checks the clipboard for new text.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// Runs the pipeline over each new clipboard text, in place. The user copies and pastes
+// themselves, so no input needs to be simulated.
+fn watch_clipboard(pipeline: &[TransformStep], settings: &ActivationSettings) -> Result<()> {
+    let mut clipboard = settings.clipboard.borrow_mut();
+    println!(
+        "Watching the clipboard. Copied text runs through {:?}.",
+        pipeline
+    );
+    println!("Ctrl+C in this window to exit.");
+    println!("---");
+
+    // Leave whatever was copied before starting alone. Comparing texts covers backends
+    // without a sequence number; it also keeps us from re-transforming our own results.
+    let mut last_text = clipboard.get_text(true).ok();
+    let mut last_sequence = clipboard.sequence_number();
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let sequence = clipboard.sequence_number();
+        if sequence.is_some() && sequence == last_sequence {
+            continue;
+        }
+        last_sequence = sequence;
+
+        let size = clipboard.text_size().ok().flatten();
+        if size.is_some_and(|size| settings.exceeds_max_size(size)) {
+            continue;
+        }
+        // Non-text copies (images, files) have no text; wait for the next change.
+        let Ok(text) = clipboard.get_text(settings.lossy_utf16) else {
+            continue;
+        };
+        if last_text.as_deref() == Some(text.as_str()) || text.is_empty() {
+            continue;
+        }
+        if settings.exceeds_max_size(text.len()) {
+            last_text = Some(text);
+            continue;
+        }
+        // Passwords are copied to be pasted as they are.
+        if clipboard.is_concealed() {
+            last_text = Some(text);
+            continue;
+        }
+
+        let modified = settings
+            .transformer
+            .apply(&text, pipeline, &settings.transform)?;
+        if modified != text {
+            clipboard
+                .set_text(&modified)
+                .context("Failed to set modified text to clipboard")
+                .status(Status::ClipboardError)?;
+            last_sequence = clipboard.sequence_number();
+            println!(
+                "Transformed copied text: {}",
+                privacy::Excerpt::new(&modified)
+            );
+            settings
+                .history
+                .borrow_mut()
+                .push(text, modified.clone(), false);
+        }
+        last_text = Some(modified);
+    }
+}
+
+// Whether we're running under a Wayland compositor.
+fn is_wayland_session() -> bool {
+    cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+// Pastes the next older result from the history.
+fn paste_history(held: Modifiers, settings: &ActivationSettings) -> Result<Outcome> {
+    let mut history = settings.history.borrow_mut();
+    let total = history.len();
+    let Some((position, entry)) = history.cycle() else {
+        println!("History is empty. Skipping.");
+        return Ok(Outcome::Skipped("History is empty"));
+    };
+    println!(
+        "Re-pasting history entry {}/{}, copied from: {}",
+        position,
+        total,
+        privacy::Excerpt::new(&entry.original).concealed(entry.concealed)
+    );
+    paste_text(held, &entry.transformed, settings)
+}
+
+// Pastes the original text of the last activation, undoing its transform.
+fn paste_original(held: Modifiers, settings: &ActivationSettings) -> Result<Outcome> {
+    let history = settings.history.borrow();
+    let Some(entry) = history.latest() else {
+        println!("Nothing to undo. Skipping.");
+        return Ok(Outcome::Skipped("Nothing to undo"));
+    };
+    println!(
+        "Undoing. Pasting original text: {}",
+        privacy::Excerpt::new(&entry.original).concealed(entry.concealed)
+    );
+    paste_text(held, &entry.original, settings)
+}
+
+// Puts `text` on the clipboard and pastes it, or types it out with `--type-out`.
+fn paste_text(held: Modifiers, text: &str, settings: &ActivationSettings) -> Result<Outcome> {
+    if settings.dry_run {
+        println!("Dry run. Would paste:");
+        println!("{}", text);
+        println!("---");
+        return Ok(Outcome::done(text));
+    }
+    settings
+        .input
+        .borrow_mut()
+        .release_keys(&held.keys())
+        .context("Failed to release trigger modifiers")
+        .status(Status::SimulationError)?;
+    if let Some(delay) = settings.type_delay {
+        let typed = settings
+            .input
+            .borrow_mut()
+            .type_text(text, delay)
+            .context("Failed to type the text")
+            .status(Status::SimulationError);
+        settings.phases.end("typing");
+        return typed.map(|()| Outcome::done(text));
+    }
+    let mut clipboard = settings.clipboard.borrow_mut();
+    let before_set = clipboard.sequence_number();
+    clipboard
+        .set_text(text)
+        .context("Failed to set text to clipboard")
+        .status(Status::ClipboardError)?;
+    clipboard::wait_for_change(
+        clipboard.as_ref(),
+        before_set,
+        settings.copy_timeout,
+        settings.settle_delay,
+    );
+    drop(clipboard);
+    settings.phases.end("clipboard write");
+    if settings.copy_only {
+        println!("Left the text on the clipboard (--copy-only).");
+        return Ok(Outcome::done(text));
+    }
+    thread::sleep(settings.paste_delay);
+    settings.phases.end("paste delay");
+    cancel::check()?;
+    let paste = settings.paste_chord();
+    settings
+        .input
+        .borrow_mut()
+        .send_chord(&paste)
+        .context("Failed to simulate the paste shortcut")
+        .status(Status::SimulationError)?;
+    settings.phases.end("paste");
+
+    println!("Paste simulated.");
+    Ok(Outcome::done(text))
+}
+
+// Answers `ipc::ask` from other commands.
+fn answer_request(command: &str) -> Option<String> {
+    match command {
+        "stats" => serde_json::to_string(&stats::snapshot()).ok(),
+        "status" => serde_json::to_string(&health::report()).ok(),
+        _ => None,
+    }
+}
+
+// `stats`: asks the running listener for its counters.
+fn show_stats(json: bool) -> Result<()> {
+    let reply = ipc::ask("stats")?;
+    let snapshot: stats::Snapshot =
+        serde_json::from_str(&reply).with_context(|| format!("Unexpected reply {:?}", reply))?;
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+        false => println!("{}", snapshot),
+    }
+    Ok(())
+}
+
+// `status`: asks the running listener how it's doing.
+fn show_status(json: bool) -> Result<()> {
+    let reply = ipc::ask("status")?;
+    let report: health::Report =
+        serde_json::from_str(&reply).with_context(|| format!("Unexpected reply {:?}", reply))?;
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => println!("{}", report),
+    }
+    Ok(())
+}
+
+// Formats `n` with thousands separators, e.g. 1,243.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+// Prints a word-level diff of `original` and `modified`, colored on a terminal. In private
+// mode only their lengths.
+fn print_diff(original: &str, modified: &str) {
+    if !privacy::shows_content() {
+        println!(
+            "Changes: {} chars became {}",
+            original.chars().count(),
+            modified.chars().count()
+        );
+        return;
+    }
+    let style = diff::Style::for_stream(&io::stdout());
+    println!("Changes:");
+    let (original, modified) = (privacy::redact(original), privacy::redact(modified));
+    println!(
+        "{}",
+        diff::render(&diff::words(&original, &modified), style)
+    );
+    println!("---");
+}
+
+// Prints the whole result of a `--dry-run` in place of pasting it or setting the clipboard.
+// In private mode only its length.
+fn print_dry_run(pipeline: &[TransformStep], text: &str) {
+    if !privacy::shows_content() {
+        println!(
+            "Dry run. Applied {:?}. Result: {}",
+            pipeline,
+            privacy::Excerpt::new(text)
+        );
+        return;
+    }
+    println!("Dry run. Applied {:?}. Result:", pipeline);
+    println!("{}", privacy::redact(text));
+    println!("---");
+}
+
+// Flattens the hard wraps in the snapshot's HTML Format, if it has one.
+fn flatten_html(snapshot: &mut clipboard::Snapshot, format: Option<u32>, join_with: &str) {
+    let Some(format) = format else {
+        return;
+    };
+    let Some(data) = snapshot.get(format) else {
+        return;
+    };
+    match html_format::edit_fragment(data, |html| html_format::flatten_fragment(html, join_with)) {
+        Some(edited) => {
+            snapshot.set(format, edited);
+            println!("Flattened the HTML copy of the selection.");
+        }
+        None => log::warn!("Couldn't parse the copied HTML. Leaving it unchanged."),
+    }
 }
 
-// --- Core Logic ---
-fn remove_newlines_and_paste() -> Result<()> {
-    println!("Trigger key pressed. Simulating Copy (Ctrl+C)...");
+// Grabs all input, swallowing events for which `handle_event` returns true.
+#[cfg(any(windows, target_os = "macos"))]
+fn run_grab(handle_event: impl Fn(&Event) -> bool + 'static) -> Result<()> {
+    let callback = move |event: Event| {
+        if handle_event(&event) {
+            None // Swallow the trigger so the focused application never sees it
+        } else {
+            Some(event)
+        }
+    };
+    if let Err(error) = rdev::grab(callback) {
+        log::error!("Failed to set up the global input grab: {:?}", error);
+        log::error!("This might be a permissions issue. Try running the program as administrator.");
+        return Err(anyhow!("Input grab error: {:?}", error));
+    }
+    Ok(())
+}
 
-    // 1. Simulate Ctrl+C
-    send_ctrl_c().context("Failed to simulate Ctrl+C")?;
+// rdev can only grab through evdev on Linux, which needs root and isn't enabled.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn run_grab(_handle_event: impl Fn(&Event) -> bool + 'static) -> Result<()> {
+    Err(anyhow!("--grab is only supported on Windows and macOS"))
+}
 
-    // 2. Wait for clipboard to update
-    //    This delay is crucial! The OS needs time to process the copy command.
-    thread::sleep(Duration::from_millis(150)); // Adjust if needed
+impl Args {
+    /// Takes every option not given on the command line or in its environment variable
+    /// from the config file instead.
+    fn apply_config(&mut self, matches: &ArgMatches, config: &Config) {
+        let is_set = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        macro_rules! fill {
+            ($($field:ident),* $(,)?) => {$(
+                if !is_set(stringify!($field)) {
+                    if let Some(value) = config.$field.clone() {
+                        self.$field = value;
+                    }
+                }
+            )*};
+        }
+        fill!(
+            join_with,
+            pipeline,
+            collapse_spaces,
+            history_size,
+            save_history,
+            grab,
+            cooldown,
+            hotkey_backend,
+            copy_timeout,
+            clipboard_timeout,
+            settle_delay,
+            paste_delay,
+            key_delay,
+            adaptive_timeout,
+            max_size,
+            lossy_utf16,
+            restore_clipboard,
+            restore_delay,
+            preserve_formats,
+            transform_html,
+            ignore_app,
+            only_app,
+            primary,
+            type_out,
+            type_delay,
+            copy_only,
+            tray,
+            notify,
+            errors_to,
+            overlay,
+            preview,
+            diff,
+            sounds,
+            volume,
+            show_content,
+            redact,
+            timing,
+        );
+        self.trigger_key = self.trigger_key.or(config.trigger_key);
+        self.history_key = self.history_key.or(config.history_key);
+        self.undo_key = self.undo_key.or(config.undo_key);
+        self.clipboard_key = self.clipboard_key.or(config.clipboard_key);
+        self.double_tap = self.double_tap.or(config.double_tap);
+        self.paste_with = self.paste_with.or(config.paste_with);
+        self.profile_key = self.profile_key.or(config.profile_key);
+        self.pause_key = self.pause_key.or(config.pause_key);
+        if self.profile.is_none() {
+            self.profile = config.default_profile.clone();
+        }
+        if self.plugin_dir.is_none() {
+            self.plugin_dir = config.plugin_dir.clone();
+        }
+        if self.success_sound.is_none() {
+            self.success_sound = config.success_sound.clone();
+        }
+        if self.skipped_sound.is_none() {
+            self.skipped_sound = config.skipped_sound.clone();
+        }
+        if self.failure_sound.is_none() {
+            self.failure_sound = config.failure_sound.clone();
+        }
+        if self.event_log.is_none() {
+            self.event_log = config.event_log.clone();
+        }
+        if self.error_webhook.is_none() {
+            self.error_webhook = config.error_webhook.clone();
+        }
+    }
+}
 
-    println!("Getting text from clipboard...");
+/// Prefix of the environment variables that stand in for each option, e.g.
+/// `FLATTEN_STRING_TRIGGER_KEY` for `--trigger-key`.
+const ENV_PREFIX: &str = "FLATTEN_STRING_";
+
+/// The command-line interface, with every option also read from its `FLATTEN_STRING_*`
+/// variable. Flags on the command line win over the environment, which wins over the
+/// config file. Switches such as `--grab` take `true` or `false`.
+fn cli() -> clap::Command {
+    // Global, so options can follow a subcommand as well: `strflatten run --trigger-key F9`.
+    Args::command()
+        .mut_args(|arg| {
+            let name = arg.get_id().as_str().to_ascii_uppercase();
+            arg.env(format!("{}{}", ENV_PREFIX, name)).global(true)
+        })
+        .after_help(
+            "Every option can also be set through its environment variable, e.g. FLATTEN_STRING_TRIGGER_KEY=F9 or FLATTEN_STRING_GRAB=true. Command-line flags take precedence over the environment, and the environment over the config file.",
+        )
+}
 
-    // 3. Get text from clipboard
-    let original_text = get_clipboard_string()
-        .map_err(|e| anyhow!("Clipboard error getting string: {}", e)) // Map clipboard-win error
-        .context("Failed to get text from clipboard. Was text copied?")?;
+/// Where `transform` and `--once` take their text from.
+enum OnceInput {
+    /// Text given on the command line; the result is printed.
+    Text(String),
+    /// Everything in `input`, or stdin without one. The result is written to `out`, or to
+    /// stdout as is for shell pipelines.
+    Stream {
+        input: Option<PathBuf>,
+        out: Option<PathBuf>,
+    },
+    /// Files that are each overwritten with their result.
+    InPlace(Vec<PathBuf>),
+    /// The clipboard's text, which is replaced by the result.
+    Clipboard,
+}
 
-    if original_text.is_empty() {
-        println!("Clipboard text is empty. Skipping.");
-        return Ok(());
+impl OnceInput {
+    /// What `transform` reads: its TEXT, `-` or piped stdin, --in files, or the clipboard.
+    fn from_args(
+        text: &[String],
+        input: &[PathBuf],
+        out: Option<PathBuf>,
+        in_place: bool,
+    ) -> Result<Self> {
+        if in_place {
+            return Ok(OnceInput::InPlace(input.to_vec()));
+        }
+        let input = match input {
+            [] => None,
+            [path] => Some(path.clone()),
+            _ => return Err(anyhow!("Several --in files need --in-place")),
+        };
+        Ok(match text {
+            [dash] if dash == "-" => OnceInput::Stream { input, out },
+            [] if input.is_some() || out.is_some() || !io::stdin().is_terminal() => {
+                OnceInput::Stream { input, out }
+            }
+            [] => OnceInput::Clipboard,
+            words => OnceInput::Text(words.join(" ")),
+        })
     }
+}
 
-    // 4. Remove newlines
-    //    Replace both Windows (\r\n) and Unix (\n) newlines.
-    //    Replacing \r and \n individually covers both cases.
-    let modified_text = original_text
-        .replace('\r', "")
-        .replace("-\n", "")
-        .replace('\n', " "); // Replace newline with a space
-    println!(
-        "Removed newlines. Result (first 100): {:.100}...",
-        modified_text
-    );
+/// `--output json`'s report on one transformed text.
+#[derive(Serialize)]
+struct Report<'a> {
+    /// The file the text came from, with `--in`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a Path>,
+    original: &'a str,
+    transformed: &'a str,
+    changed: bool,
+    steps: Vec<StepReport>,
+    duration_ms: f64,
+}
+
+#[derive(Serialize)]
+struct StepReport {
+    step: TransformStep,
+    duration_ms: f64,
+}
 
-    // 5. Set modified text to clipboard
-    set_clipboard_string(&modified_text)
-        .map_err(|e| anyhow!("Clipboard error setting string: {}", e)) // Map clipboard-win error
-        .context("Failed to set modified text to clipboard")?;
+impl Report<'_> {
+    /// Prints the report as a single line of JSON, so several of them form JSON Lines.
+    fn print(&self) -> Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+/// Runs `args.pipeline` once over `input`, without listening for hotkeys. The status says
+/// whether the text changed.
+fn transform_once(args: &Args, config: &Config, input: OnceInput) -> Result<Status> {
+    let options = transform_options(args, config)?;
+    check_pipeline(&args.pipeline, &options)?;
+    let json = args.output == OutputFormat::Json;
+    let changed = |original: &str, modified: &str| match original == modified {
+        true => Status::NoChange,
+        false => Status::Success,
+    };
+    // Transforms `original` and, with `--output json`, prints its report.
+    let transform = |file: Option<&Path>, original: &str| -> Result<String> {
+        let started = Instant::now();
+        let (modified, timings) = apply_pipeline_timed(original, &args.pipeline, &options);
+        if args.diff {
+            // On stderr, so the result alone goes to stdout.
+            let style = diff::Style::for_stream(&io::stderr());
+            if let Some(file) = file {
+                eprintln!("{}:", file.display());
+            }
+            let (original, modified) = (privacy::redact(original), privacy::redact(&modified));
+            eprintln!(
+                "{}",
+                diff::render(&diff::words(&original, &modified), style)
+            );
+        }
+        if json {
+            let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+            Report {
+                file,
+                original,
+                transformed: &modified,
+                changed: modified != original,
+                steps: args
+                    .pipeline
+                    .iter()
+                    .zip(timings)
+                    .map(|(step, duration)| StepReport {
+                        step: step.clone(),
+                        duration_ms: millis(duration),
+                    })
+                    .collect(),
+                duration_ms: millis(started.elapsed()),
+            }
+            .print()?;
+        }
+        Ok(modified)
+    };
+    match input {
+        OnceInput::Text(text) => {
+            let modified = transform(None, &text)?;
+            if !json {
+                println!("{}", modified);
+            }
+            return Ok(changed(&text, &modified));
+        }
+        OnceInput::Stream { input, out } => {
+            let text = match &input {
+                Some(path) => read_file(path)?,
+                None => {
+                    let mut text = String::new();
+                    io::stdin()
+                        .read_to_string(&mut text)
+                        .context("Failed to read stdin")?;
+                    text
+                }
+            };
+            let modified = transform(input.as_deref(), &text)?;
+            match &out {
+                Some(path) => write_file(path, &modified)?,
+                None if json => {}
+                None => {
+                    let mut stdout = io::stdout().lock();
+                    stdout.write_all(modified.as_bytes())?;
+                    stdout.flush().context("Failed to write stdout")?;
+                }
+            }
+            return Ok(changed(&text, &modified));
+        }
+        OnceInput::InPlace(paths) => {
+            let quiet = args.quiet || json;
+            let mut status = Status::NoChange;
+            for path in &paths {
+                let text = read_file(path)?;
+                let modified = transform(Some(path), &text)?;
+                if modified == text {
+                    if !quiet {
+                        println!("Unchanged {}", path.display());
+                    }
+                    continue;
+                }
+                write_file(path, &modified)?;
+                status = Status::Success;
+                if !quiet {
+                    println!("Transformed {}", path.display());
+                }
+            }
+            return Ok(status);
+        }
+        OnceInput::Clipboard => {}
+    }
 
-    // 6. Wait for clipboard to update again
-    thread::sleep(Duration::from_millis(150)); // Delay before pasting
+    let mut clipboard = clipboard::system(Duration::from_millis(args.clipboard_timeout))
+        .status(Status::ClipboardError)?;
+    let original = clipboard
+        .get_text(args.lossy_utf16)
+        .context("Failed to get text from clipboard")
+        .status(Status::ClipboardError)?;
+    if original.is_empty() {
+        return Err(anyhow!("The clipboard has no text")).status(Status::ClipboardEmpty);
+    }
+    let modified = transform(None, &original)?;
+    let quiet = args.quiet || json;
+    if args.dry_run {
+        if !json {
+            print_dry_run(&args.pipeline, &modified);
+        }
+        return Ok(changed(&original, &modified));
+    }
+    if modified == original {
+        if !quiet {
+            println!("{:?} left the clipboard unchanged.", args.pipeline);
+        }
+        return Ok(Status::NoChange);
+    }
+    // This process is about to exit, taking a Linux clipboard's contents with it.
+    #[cfg(target_os = "linux")]
+    let set = {
+        drop(clipboard);
+        clipboard::serve_text(&modified, Duration::from_millis(args.clipboard_timeout))
+    };
+    #[cfg(not(target_os = "linux"))]
+    let set = clipboard.set_text(&modified);
+    set.context("Failed to set modified text to clipboard")
+        .status(Status::ClipboardError)?;
+    if !quiet {
+        println!(
+            "Applied {:?} to the clipboard. Result: {}",
+            args.pipeline,
+            privacy::Excerpt::new(&modified)
+        );
+    }
+    Ok(Status::Success)
+}
 
-    println!("Pasting modified text (Ctrl+V)...");
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
 
-    // 7. Simulate Ctrl+V
-    send_ctrl_v().context("Failed to simulate Ctrl+V")?;
+fn write_file(path: &Path, text: &str) -> Result<()> {
+    fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
 
-    println!("Paste simulated.");
-    Ok(())
+/// The options every pipeline runs with: `--join-with`, the `[[rule]]`s, the plugins, the
+/// `[[command]]`s and the `[[script]]`s.
+fn transform_options(args: &Args, config: &Config) -> Result<TransformOptions> {
+    let mut custom = CustomTransforms::default();
+    let plugin_dir = args
+        .plugin_dir
+        .clone()
+        .or_else(|| plugin::default_dir(args.config.as_deref()));
+    if let Some(dir) = plugin_dir {
+        plugin::load_dir(&dir, &mut custom)?;
+    }
+    for step in &config.commands {
+        let command = ExternalCommand::new(step)?;
+        if custom.contains(command.name()) {
+            return Err(anyhow!(
+                "[[command]] {:?} has the name of a plugin or another [[command]]",
+                command.name()
+            ));
+        }
+        custom.insert(command.name().to_string(), command);
+    }
+    for step in &config.scripts {
+        let script = Script::new(step)?;
+        if custom.contains(script.name()) {
+            return Err(anyhow!(
+                "[[script]] {:?} has the name of a plugin, a [[command]] or another [[script]]",
+                script.name()
+            ));
+        }
+        custom.insert(script.name().to_string(), script);
+    }
+    Ok(TransformOptions {
+        join_with: args.join_with.clone(),
+        rules: config.compile_rules()?,
+        custom,
+    })
 }
 
-// --- Simulation Helpers ---
+/// Fails if `pipeline` has a step that's neither built in nor added by a plugin.
+fn check_pipeline(pipeline: &[TransformStep], options: &TransformOptions) -> Result<()> {
+    match options.unknown_step(pipeline) {
+        Some(name) => Err(anyhow!(
+            "Unknown transform step {:?}. It's not built in, and the plugins and [[command]]s are {:?}",
+            name,
+            options.custom
+        )),
+        None => Ok(()),
+    }
+}
 
-// Helper function to simulate Ctrl+C
-fn send_ctrl_c() -> Result<(), rdev::SimulateError> {
-    let delay = Duration::from_millis(30);
-    simulate(&EventType::KeyPress(Key::ControlLeft))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyPress(Key::KeyC))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::KeyC))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::ControlLeft))?;
-    Ok(())
+/// Parses the command line again and fills in what it leaves out from `config`.
+fn resolve_args(matches: &ArgMatches, config: &Config) -> Result<Args> {
+    let mut args = Args::from_arg_matches(matches)?;
+    args.apply_config(matches, config);
+    // Set here so a reload or profile switch that changes them takes effect.
+    privacy::set(
+        !args.show_content,
+        privacy::Redactor::new(&args.redact, config.compile_secrets()?),
+    );
+    alerts::set(&args.errors_to, args.error_webhook.as_deref())?;
+    if let Some(name) = &args.named_pipeline {
+        args.pipeline = config.named_pipeline(name)?.clone();
+    }
+    if args.collapse_spaces && !args.pipeline.contains(&TransformStep::CollapseSpaces) {
+        args.pipeline.push(TransformStep::CollapseSpaces);
+    }
+    Ok(args)
 }
 
-// Helper function to simulate Ctrl+V (same as before)
-fn send_ctrl_v() -> Result<(), rdev::SimulateError> {
-    let delay = Duration::from_millis(30);
-    simulate(&EventType::KeyPress(Key::ControlLeft))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyPress(Key::KeyV))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::KeyV))?;
-    thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::ControlLeft))?;
-    Ok(())
+/// Builds the hotkey -> pipeline dispatch table. The CLI trigger goes first, so it
+/// wins over a config binding for the same chord.
+fn build_bindings(args: &Args, config: &Config) -> Result<Vec<HotkeyBinding>> {
+    let mut bindings = Vec::new();
+    if let Some(key) = args.trigger_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::Transform,
+            pipeline: args.pipeline.clone(),
+        });
+    }
+    if let Some(key) = args.clipboard_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::TransformClipboard,
+            pipeline: args.pipeline.clone(),
+        });
+    }
+    if let Some(key) = args.undo_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::Undo,
+            pipeline: Vec::new(),
+        });
+    }
+    if let Some(key) = args.history_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::History,
+            pipeline: Vec::new(),
+        });
+    }
+    if let Some(key) = args.profile_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::NextProfile,
+            pipeline: Vec::new(),
+        });
+    }
+    if let Some(key) = args.pause_key {
+        bindings.push(HotkeyBinding {
+            key,
+            action: Action::TogglePause,
+            pipeline: Vec::new(),
+        });
+    }
+    bindings.extend(config.hotkeys.iter().cloned());
+    if bindings.is_empty() && !args.watch {
+        return Err(anyhow!(
+            "No trigger configured. Pass --trigger-key or --watch, or set trigger_key/[[hotkey]] in the config file."
+        ));
+    }
+    Ok(bindings)
 }
 
 // --- Main Function ---
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn main() -> ExitCode {
+    match run() {
+        Ok(status) => status.into(),
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            Status::of(&error).into()
+        }
+    }
+}
+
+fn run() -> Result<Status> {
+    let matches = cli().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    logging::init(
+        args.log_level.as_deref(),
+        args.log_file.as_deref(),
+        retention(&args),
+    )?;
+
+    // Commands that don't need the config file loaded.
+    let finished = match &args.command {
+        Some(Command::ListKeys { json }) => Some(list_keys::list_keys(*json)),
+        Some(Command::Config { command }) => {
+            let path = args
+                .config
+                .clone()
+                .or_else(config::default_path)
+                .ok_or_else(|| anyhow!("No default config location. Pass --config <FILE>."))?;
+            Some(match command {
+                ConfigCommand::Init { force } => config::init(&path, *force),
+                ConfigCommand::Validate => config::validate(&path),
+            })
+        }
+        Some(Command::History { clear }) => Some(history::show_saved(*clear)),
+        Some(Command::Stats { json }) => Some(show_stats(*json)),
+        Some(Command::Status { json }) => Some(show_status(*json)),
+        Some(Command::Autostart { command }) => Some(match command {
+            AutostartCommand::Enable { elevated } => {
+                autostart::enable(&autostart::listener_args(), *elevated)
+            }
+            AutostartCommand::Disable => autostart::disable(),
+        }),
+        Some(Command::Completions { shell }) => {
+            completions::print_script(cli(), *shell);
+            Some(Ok(()))
+        }
+        Some(Command::Complete { names, prefix }) => {
+            completions::print_names(*names, prefix, args.config.as_deref());
+            Some(Ok(()))
+        }
+        Some(Command::Run | Command::Transform { .. }) | None => None,
+    };
+    if let Some(result) = finished {
+        return result.map(|()| Status::Success);
+    }
+    if args.capture_key {
+        let path = args.config.clone().or_else(config::default_path);
+        capture::capture_key(path.as_deref()).status(Status::SimulationError)?;
+        return Ok(Status::Success);
+    }
+
+    // An explicit --config has to exist; the default one is optional.
+    let config_path = args
+        .config
+        .clone()
+        .or_else(|| config::default_path().filter(|path| path.exists()));
+    let config = match &config_path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let profile = match resolve_args(&matches, &config)?.profile {
+        Some(name) => Some(config.profile_index(&name)?),
+        None => None,
+    };
+    let source = Source {
+        matches,
+        config,
+        profile,
+    };
+    let once_input = match &args.command {
+        Some(Command::Transform {
+            text,
+            input,
+            out,
+            in_place,
+        }) => Some(OnceInput::from_args(text, input, out.clone(), *in_place)?),
+        _ if args.once => Some(OnceInput::Clipboard),
+        _ => None,
+    };
+    if let Some(input) = once_input {
+        let config = source.config.with_profile(source.profile);
+        return transform_once(&resolve_args(&source.matches, &config)?, &config, input);
+    }
+    if args.detach {
+        let config_path = args.config.clone().or_else(config::default_path);
+        detach::relaunch(&detach::log_path(config_path.as_deref()), retention(&args))?;
+        return Ok(Status::Success);
+    }
+    // Before `build`, which starts the first threads.
+    cleanup::install();
+    let (args, bindings, settings) = source.build()?;
+    // Two listeners would both react to every hotkey press.
+    let _instance = match args.allow_multiple {
+        true => None,
+        false => Some(instance::acquire()?.ok_or_else(|| {
+            anyhow!("Another flatten-string is already listening, so every hotkey would fire twice. Quit it first, or pass --allow-multiple.")
+        })?),
+    };
+    stats::start();
+    if let Err(e) = ipc::serve(answer_request) {
+        log::warn!("`stats` and `status` won't reach this listener: {:?}", e);
+    }
+    let uses_regex = bindings
+        .iter()
+        .any(|binding| binding.pipeline.contains(&TransformStep::Regex));
+    if uses_regex && settings.transform.rules.is_empty() {
+        log::warn!("A pipeline has a regex step but no [[rule]]s are configured.");
+    }
 
-    let target_key: rdev::Key = args.trigger_key.into();
+    if args.watch {
+        watch_clipboard(&args.pipeline, &settings).status(Status::ClipboardError)?;
+        return Ok(Status::Success);
+    }
+    if is_wayland_session() {
+        log::warn!("This looks like a Wayland session. Global hotkeys and simulated Ctrl+C/Ctrl+V only reach XWayland windows; consider --watch.");
+    }
 
-    println!("Remove Newlines & Paste Listener Started.");
-    println!("Trigger Key: {:?}", args.trigger_key);
+    println!("Transform & Paste Listener Started.");
+    if let Some(path) = &config_path {
+        let watching = args.hotkey_backend == HotkeyBackend::Hook && !args.no_reload;
+        let note = if watching {
+            " (reloaded on change)"
+        } else {
+            ""
+        };
+        println!("Config: {}{}", path.display(), note);
+    }
+    health::set_profile(source.profile_name());
+    if let Some(name) = source.profile_name() {
+        println!("Profile: {}", name);
+    }
+    println!("Hotkeys:");
+    for binding in &bindings {
+        println!("  {} -> {}", binding.key, binding.describe());
+    }
+    if let Some(ms) = args.double_tap {
+        println!("Double Tap: within {} ms", ms);
+    }
+    println!("Hotkey Backend: {:?}", args.hotkey_backend);
+    if args.grab {
+        println!("Grab: hotkeys are swallowed");
+    }
+    if args.dry_run {
+        println!("Dry Run: results are printed, not pasted");
+    }
+    if args.preserve_formats || args.transform_html {
+        println!("Preserve Formats: on");
+    }
+    if args.transform_html {
+        println!("Transform HTML: on");
+    }
+    if let Some(shortcut) = settings.paste_with {
+        println!("Paste With: {}", shortcut);
+    }
+    if args.primary {
+        println!("Selection: PRIMARY (pasted with a middle click)");
+    }
+    if args.type_out {
+        println!("Type Out: {} ms per character", args.type_delay);
+    }
+    if args.copy_only {
+        println!("Copy Only: results are left on the clipboard");
+    }
+    if shows_tray(&args) {
+        println!("Tray: icon in the notification area");
+    }
+    if !args.notify.is_empty() {
+        println!("Notify: {:?}", args.notify);
+    }
+    if args.errors_to != [alerts::Destination::Console] {
+        println!("Errors To: {:?}", args.errors_to);
+    }
+    if args.overlay {
+        println!("Overlay: near the cursor after each activation");
+    }
+    if args.preview {
+        println!("Preview: confirm each result before it's pasted");
+    }
+    if !args.sounds.is_empty() {
+        println!("Sounds: {:?} at {}% volume", args.sounds, args.volume);
+    }
+    if args.history_key.is_some() {
+        println!("History: last {} results", args.history_size);
+    }
+    if args.restore_clipboard {
+        println!("Restore Clipboard: {} ms after pasting", args.restore_delay);
+    }
+    println!("Join With: {:?}", args.join_with);
+    println!("Regex Rules: {}", settings.transform.rules.len());
+    if !settings.transform.custom.is_empty() {
+        println!("Custom Steps: {:?}", settings.transform.custom);
+    }
     println!("---");
-    println!("Select text and press '{:?}' to copy it, remove newlines (replacing with spaces), and paste it back.", args.trigger_key);
-    println!("NOTE: This program likely requires administrator privileges to capture global key presses and simulate input.");
+    println!("Select text and press a hotkey to copy it, run it through that hotkey's pipeline, and paste it back.");
+    if cfg!(windows) && args.hotkey_backend == HotkeyBackend::Hook {
+        println!("NOTE: This program likely requires administrator privileges to capture global key presses and simulate input.");
+    }
+    if cfg!(target_os = "macos") {
+        println!("NOTE: macOS only delivers global key presses and accepts simulated input from apps granted access. Add your terminal under System Settings > Privacy & Security > Accessibility (and Input Monitoring), then restart it.");
+    }
+    println!("Press Esc during an activation to cancel it before it pastes.");
     println!("Ctrl+C in this window to exit.");
     println!("---");
 
-    let callback = move |event: Event| {
-        match event.event_type {
-            EventType::KeyPress(key) if key == target_key => {
-                // Call the core logic
-                if let Err(e) = remove_newlines_and_paste() {
-                    eprintln!("ERROR: {:?}", e);
-                    // Maybe add a small visual/audio cue for error? (Optional)
+    let cooldown = Duration::from_millis(args.cooldown);
+    let listener = Arc::new(Mutex::new(Listener::new(
+        bindings,
+        double_tap(&args),
+        cooldown,
+    )));
+    #[cfg(windows)]
+    session::spawn(Arc::clone(&listener));
+    // RegisterHotKey registrations are fixed at startup, so only the hook reloads.
+    let configs = match config_path {
+        Some(path) if args.hotkey_backend == HotkeyBackend::Hook && !args.no_reload => {
+            Some(reload::watch(path))
+        }
+        _ => None,
+    };
+    let runtime = Runtime {
+        listener: Arc::clone(&listener),
+        settings: RefCell::new(settings),
+        source: RefCell::new(source),
+        configs,
+    };
+    let activations = supervisor::spawn(runtime);
+    #[cfg(windows)]
+    if shows_tray(&args) {
+        tray::spawn(tray::Tray {
+            listener: Arc::clone(&listener),
+            requests: activations.clone(),
+            config_path: args.config.clone().or_else(config::default_path),
+        });
+    }
+
+    // Blocks the thread until an error occurs
+    let result = match args.hotkey_backend {
+        HotkeyBackend::Hook => run_hook(listener, activations, args.grab),
+        #[cfg(windows)]
+        HotkeyBackend::RegisterHotkey => {
+            let bindings = lock(&listener).bindings.clone();
+            register_hotkey::listen(&bindings, |id| {
+                let mut listener = lock(&listener);
+                if listener.try_activate(id) {
+                    let _ = activations.send(Request::Activate(listener.bindings[id].clone()));
+                }
+            })
+        }
+        #[cfg(not(windows))]
+        HotkeyBackend::RegisterHotkey => Err(anyhow!(
+            "The register-hotkey backend is only available on Windows"
+        )),
+    };
+    result.status(Status::SimulationError)?;
+    Ok(Status::Success)
+}
+
+fn double_tap(args: &Args) -> Option<DoubleTap> {
+    args.double_tap
+        .map(|ms| DoubleTap::new(Duration::from_millis(ms)))
+}
+
+fn shows_tray(args: &Args) -> bool {
+    args.tray
+        || !args.notify.is_empty()
+        || args.errors_to.contains(&alerts::Destination::Notification)
+}
+
+fn retention(args: &Args) -> Retention {
+    Retention {
+        max_size: (args.log_max_size > 0).then(|| args.log_max_size * 1_000_000),
+        max_age: (args.log_max_age > 0).then(|| Duration::from_secs(args.log_max_age * 86_400)),
+        keep: args.log_keep,
+    }
+}
+
+/// What the hotkeys and settings are built from, kept to rebuild them when the active
+/// profile or the config file changes.
+struct Source {
+    /// The command line, whose flags still win over the config file.
+    matches: ArgMatches,
+    config: Config,
+    /// Index into `config.profiles`, or `None` for the top-level options.
+    profile: Option<usize>,
+}
+
+impl Source {
+    fn build(&self) -> Result<(Args, Vec<HotkeyBinding>, ActivationSettings)> {
+        let config = self.config.with_profile(self.profile);
+        let args = resolve_args(&self.matches, &config)?;
+        let bindings = build_bindings(&args, &config)?;
+        let settings = ActivationSettings::new(&args, &config)?;
+        let app_pipelines = settings.apps.iter().filter_map(|app| app.pipeline.as_ref());
+        for pipeline in bindings.iter().map(|b| &b.pipeline).chain(app_pipelines) {
+            check_pipeline(pipeline, &settings.transform)?;
+        }
+        Ok((args, bindings, settings))
+    }
+
+    fn profile_name(&self) -> Option<&str> {
+        let profile = self.config.profiles.get(self.profile?)?;
+        Some(&profile.name)
+    }
+}
+
+/// How often the worker looks for config file changes while no hotkey is pressed.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Locks the listener shared by the input hook and the worker.
+fn lock(listener: &Mutex<Listener>) -> MutexGuard<'_, Listener> {
+    // The listener is always left consistent, so a panic elsewhere doesn't invalidate it.
+    listener
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Everything activations need, owned by the worker thread that runs them.
+struct Runtime {
+    /// Shared with the input hook, which matches events against its bindings.
+    listener: Arc<Mutex<Listener>>,
+    settings: RefCell<ActivationSettings>,
+    source: RefCell<Source>,
+    /// New versions of the config file, when it's being watched.
+    configs: Option<Receiver<Config>>,
+}
+
+/// What the worker is asked to do.
+enum Request {
+    /// Run a binding that `Listener::try_activate` started.
+    Activate(HotkeyBinding),
+    /// Switch to the next profile, as the profile hotkey does.
+    #[cfg_attr(not(windows), allow(dead_code))] // Only sent by the Windows tray
+    NextProfile,
+}
+
+// The worker component runs each binding sent to it, so the input hook can return right
+// away instead of stalling every key press system-wide while an activation copies, waits
+// and pastes.
+impl Component for Runtime {
+    type Message = Request;
+    const NAME: &'static str = "worker";
+    const IDLE_INTERVAL: Duration = RELOAD_CHECK_INTERVAL;
+
+    fn handle(&mut self, request: Request) {
+        match request {
+            Request::Activate(binding) => self.activate(binding),
+            Request::NextProfile => {
+                if let Err(e) = self.next_profile() {
+                    alerts::error(&e);
                 }
             }
-            _ => (), // Ignore other events
         }
+    }
+
+    fn idle(&mut self) {
+        self.check_reload();
+    }
+
+    // The activation that crashed never called `finished`, so the listener still thinks
+    // it's running.
+    fn recover(&mut self) {
+        cleanup::run();
+        while lock(&self.listener).finished().is_some() {
+            println!("Dropping a trigger queued behind the crashed activation.");
+        }
+    }
+}
+
+impl Runtime {
+    // Runs a hotkey's action, then whatever was queued while it ran.
+    fn activate(&self, binding: HotkeyBinding) {
+        let mut next = Some(binding);
+        while let Some(binding) = next {
+            self.run_action(&binding);
+            next = lock(&self.listener).finished();
+        }
+    }
+
+    // Runs one hotkey's action and reports any failure.
+    fn run_action(&self, binding: &HotkeyBinding) {
+        let (started_at, started) = (SystemTime::now(), Instant::now());
+        let app = foreground::current();
+        let result = if !self.settings.borrow().allows(&app) {
+            println!(
+                "Ignoring trigger: {} is excluded by --ignore-app/--only-app.",
+                app
+            );
+            Ok(Outcome::Skipped("App excluded by --ignore-app/--only-app"))
+        } else {
+            match binding.action {
+                Action::NextProfile => self.next_profile().map(|()| Outcome::ProfileSwitched),
+                // Call the core logic
+                _ => transform_and_paste(binding, &app, &self.settings.borrow()),
+            }
+        };
+        let (copied_chars, pasted_chars) = match result {
+            Ok(Outcome::Done { copied, chars }) => (copied, Some(chars)),
+            _ => (None, None),
+        };
+        let ending = match &result {
+            Ok(Outcome::Done { copied, chars }) => stats::Ending::Succeeded {
+                copied_chars: *copied,
+                pasted_chars: *chars,
+            },
+            Ok(Outcome::ProfileSwitched) => stats::Ending::Succeeded {
+                copied_chars: None,
+                pasted_chars: 0,
+            },
+            Ok(Outcome::Skipped(reason)) => stats::Ending::Skipped(reason),
+            Err(e) if e.downcast_ref::<cancel::Cancelled>().is_some() => {
+                stats::Ending::Skipped("Cancelled")
+            }
+            Err(e) => stats::Ending::Failed(match Status::of(e) {
+                Status::ClipboardError => "clipboard",
+                Status::SimulationError => "input",
+                _ => "other",
+            }),
+        };
+        stats::record(ending, started.elapsed());
+        let (kind, message) = match result {
+            Ok(Outcome::Done { chars, .. }) => (
+                activity::Kind::Success,
+                format!("{} chars, {}", thousands(chars), binding.describe()),
+            ),
+            Ok(Outcome::Skipped(reason)) => (activity::Kind::Skipped, reason.to_string()),
+            Ok(Outcome::ProfileSwitched) => {
+                (activity::Kind::Success, "Switched profile".to_string())
+            }
+            Err(e) if e.downcast_ref::<cancel::Cancelled>().is_some() => {
+                println!("Activation cancelled.");
+                (activity::Kind::Skipped, "Cancelled".to_string())
+            }
+            Err(e) => {
+                alerts::error(&e);
+                (activity::Kind::Failure, format!("{:#}", e))
+            }
+        };
+        // A failure halfway through may have left a key down or the clipboard unrestored.
+        if kind != activity::Kind::Success {
+            cleanup::run();
+        }
+        activity::record(kind, format!("{} (in {})", message, app));
+        let settings = self.settings.borrow();
+        if let Some(path) = &settings.event_log {
+            let event = events::Event {
+                timestamp: events::timestamp(started_at),
+                trigger: binding.key.to_string(),
+                action: binding.action,
+                pipeline: settings.pipeline_for(binding, &app),
+                app: app.to_string(),
+                copied_chars,
+                pasted_chars,
+                duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+                result: kind,
+                reason: (kind != activity::Kind::Success).then_some(message.as_str()),
+            };
+            if let Err(e) = event.append(path, settings.retention) {
+                log::warn!("{:?}", e);
+            }
+        }
+        settings.sounds.play(kind);
+        // A failure was already notified if errors are.
+        if settings.notify.contains(&kind)
+            && !(kind == activity::Kind::Failure && alerts::notifies())
+        {
+            #[cfg(windows)]
+            tray::notify(kind, &message);
+        }
+        if settings.overlay {
+            #[cfg(windows)]
+            overlay::show(kind, &message);
+        }
+    }
+
+    // Rebuilds the hotkeys and settings from `source` and makes it current. Nothing is
+    // replaced unless all of it is valid.
+    fn apply(&self, source: Source) -> Result<()> {
+        let (args, bindings, settings) = source.build()?;
+
+        // Past results outlive the rebuild.
+        settings.history.swap(&self.settings.borrow().history);
+        *self.settings.borrow_mut() = settings;
+        let cooldown = Duration::from_millis(args.cooldown);
+        lock(&self.listener).reconfigure(bindings, double_tap(&args), cooldown);
+        health::set_profile(source.profile_name());
+        *self.source.borrow_mut() = source;
+        Ok(())
+    }
+
+    // Applies the newest version of the config file, if it changed since the last event.
+    fn check_reload(&self) {
+        // Only the newest version matters if the file changed several times since.
+        let Some(config) = self.configs.as_ref().and_then(|c| c.try_iter().last()) else {
+            return;
+        };
+        let current = self.source.borrow();
+        let profile = current
+            .profile_name()
+            .and_then(|name| config.profile_index(name).ok());
+        let candidate = Source {
+            matches: current.matches.clone(),
+            config,
+            profile,
+        };
+        drop(current);
+        if let Err(e) = self.apply(candidate) {
+            alerts::error(&e.context("Not reloading config"));
+            return;
+        }
+
+        println!("Config reloaded. Hotkeys:");
+        for binding in &lock(&self.listener).bindings {
+            println!("  {} -> {}", binding.key, binding.describe());
+        }
+    }
+
+    // Cycles through every `[[profile]]`, then back to the top-level options.
+    fn next_profile(&self) -> Result<()> {
+        let current = self.source.borrow();
+        let count = current.config.profiles.len();
+        if count == 0 {
+            return Err(anyhow!("No [[profile]]s are configured"));
+        }
+        let profile = match current.profile {
+            None => Some(0),
+            Some(i) if i + 1 < count => Some(i + 1),
+            Some(_) => None,
+        };
+        let candidate = Source {
+            matches: current.matches.clone(),
+            config: current.config.clone(),
+            profile,
+        };
+        drop(current);
+        self.apply(candidate)
+            .context("Failed to switch profile; keeping the current one")?;
+
+        match (self.source.borrow().profile_name(), profile) {
+            (Some(name), Some(i)) => println!("Profile: {} ({} of {})", name, i + 1, count),
+            _ => println!("Profile: none (top-level options)"),
+        }
+        Ok(())
+    }
+}
+
+// Listens through rdev's global hook, optionally grabbing (swallowing) hotkey events.
+fn run_hook(
+    listener: Arc<Mutex<Listener>>,
+    activations: Sender<Request>,
+    grab_input: bool,
+) -> Result<()> {
+    // Shared by listen and grab. Returns whether the event was part of a hotkey.
+    let handle_event = move |event: &Event| -> bool {
+        #[cfg(windows)]
+        injected::watch_extra_info();
+        // A panic must not unwind into the OS hook, so it's reported and the event let through.
+        let handled = supervisor::catch(|| {
+            let mut listener = lock(&listener);
+            let verdict = listener.handle(&event.event_type);
+            if let Some(id) = verdict.activate {
+                // The worker runs it; the hook has to return as fast as possible.
+                let _ = activations.send(Request::Activate(listener.bindings[id].clone()));
+            }
+            verdict.is_trigger
+        });
+        handled.unwrap_or_else(|panic| {
+            log::error!(
+                "The listener crashed on {:?} ({}). Carrying on.",
+                event.event_type,
+                supervisor::describe(&*panic)
+            );
+            false
+        })
     };
 
-    // Blocks the thread until an error occurs
-    if let Err(error) = listen(callback) {
-        eprintln!(
-            "FATAL ERROR setting up global keyboard listener: {:?}",
-            error
-        );
-        eprintln!("This might be a permissions issue. Try running the program as administrator.");
-        return Err(anyhow!("Keyboard listener error: {:?}", error));
+    if grab_input {
+        run_grab(handle_event)?;
+    } else {
+        let callback = move |event: Event| {
+            handle_event(&event);
+        };
+        if let Err(error) = listen(callback) {
+            log::error!("Failed to set up the global keyboard listener: {:?}", error);
+            log::error!(
+                "This might be a permissions issue. Try running the program as administrator."
+            );
+            return Err(anyhow!("Keyboard listener error: {:?}", error));
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clipboard::MemoryClipboard;
+    use rdev::{EventType, Key};
+
+    /// An app with `selection` highlighted that copies and pastes through `clipboard` when
+    /// the shortcuts reach it. With nothing selected, copying leaves the clipboard alone.
+    struct FakeApp {
+        selection: String,
+        clipboard: MemoryClipboard,
+        pasted: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl InputSimulator for FakeApp {
+        fn send(&mut self, event_type: &EventType) -> Result<()> {
+            match event_type {
+                EventType::KeyPress(Key::KeyC) if !self.selection.is_empty() => {
+                    self.clipboard.set_text(&self.selection)?
+                }
+                EventType::KeyPress(Key::KeyV) => {
+                    let text = self.clipboard.get_text(false)?;
+                    self.pasted.lock().unwrap().push(text);
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn key_delay(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    /// Runs one Ctrl+Shift+J activation of `pipeline` over `selection` with the command-line
+    /// `flags`, returning what it did, what was pasted and what's left on the clipboard.
+    fn activate(
+        flags: &[&str],
+        pipeline: &[TransformStep],
+        selection: &str,
+    ) -> (Outcome, Vec<String>, String) {
+        let config = Config::default();
+        let matches = cli()
+            .try_get_matches_from(["strflatten"].iter().chain(flags))
+            .unwrap();
+        let args = resolve_args(&matches, &config).unwrap();
+        let mut clipboard = MemoryClipboard::default();
+        clipboard.set_text("copied earlier").unwrap();
+        let pasted = Arc::default();
+        let app = FakeApp {
+            selection: selection.to_string(),
+            clipboard: clipboard.clone(),
+            pasted: Arc::clone(&pasted),
+        };
+        let settings = ActivationSettings::with_backends(
+            &args,
+            &config,
+            Box::new(clipboard.clone()),
+            Box::new(app),
+        )
+        .unwrap();
+        let binding = HotkeyBinding {
+            key: "Ctrl+Shift+J".parse().unwrap(),
+            action: Action::Transform,
+            pipeline: pipeline.to_vec(),
+        };
+        let outcome = transform_and_paste(&binding, &ForegroundApp::default(), &settings).unwrap();
+        let pasted = pasted.lock().unwrap().clone();
+        (outcome, pasted, clipboard.get_text(false).unwrap())
+    }
+
+    #[test]
+    fn pastes_the_transformed_selection() {
+        let (outcome, pasted, clipboard) =
+            activate(&[], &[TransformStep::Flatten], "one\ntwo\nthree");
+        assert!(matches!(
+            outcome,
+            Outcome::Done {
+                copied: Some(13),
+                chars: 13
+            }
+        ));
+        assert_eq!(pasted, ["one two three"]);
+        assert_eq!(clipboard, "one two three");
+    }
+
+    #[test]
+    fn restores_the_clipboard_after_pasting() {
+        let flags = ["--restore-clipboard", "--restore-delay", "0"];
+        let (_, pasted, clipboard) = activate(&flags, &[TransformStep::Flatten], "a\nb");
+        assert_eq!(pasted, ["a b"]);
+        assert_eq!(clipboard, "copied earlier");
+    }
+
+    #[test]
+    fn leaves_the_result_on_the_clipboard_with_copy_only() {
+        let (_, pasted, clipboard) = activate(&["--copy-only"], &[TransformStep::Flatten], "a\nb");
+        assert!(pasted.is_empty());
+        assert_eq!(clipboard, "a b");
+    }
+
+    #[test]
+    fn skips_when_nothing_is_copied() {
+        let flags = ["--copy-timeout", "50"];
+        let (outcome, pasted, clipboard) = activate(&flags, &[TransformStep::Flatten], "");
+        assert!(matches!(outcome, Outcome::Skipped("Nothing selected")));
+        assert!(pasted.is_empty());
+        assert_eq!(clipboard, "copied earlier");
+    }
+}
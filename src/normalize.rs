@@ -0,0 +1,281 @@
+//! Partial Unicode normalization for the `nfc` and `nfkc` steps, covering the scripts copied
+//! text mostly comes in without pulling the full Unicode tables into every build.
+//!
+//! Accents are composed onto Latin letters, including Vietnamese's stacked ones when they
+//! come in the canonical order. Other scripts' combining marks pass through unchanged, and
+//! nothing is decomposed or reordered, so the result isn't always what full NFC gives.
+
+/// Composes each Latin letter with the combining accents after it, as NFC would.
+pub(crate) fn nfc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last: Option<char> = None;
+    for c in text.chars() {
+        if let Some(composed) = last.and_then(|base| compose(base, c)) {
+            out.pop();
+            out.push(composed);
+            last = Some(composed);
+            continue;
+        }
+        let c = canonical_singleton(c);
+        out.push(c);
+        last = Some(c);
+    }
+    out
+}
+
+/// Also folds the common compatibility characters to their plain forms first, as NFKC
+/// would: `ﬁ` to `fi`, fullwidth `Ａ` to `A`, `½` to `1⁄2`, `²` to `2`.
+pub(crate) fn nfkc(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            // Fullwidth ASCII.
+            '\u{FF01}'..='\u{FF5E}' => {
+                folded.push(char::from_u32(c as u32 - 0xFEE0).expect("maps to ASCII"))
+            }
+            '\u{3000}' | '\u{2000}'..='\u{200A}' => folded.push(' '),
+            _ => match COMPATIBILITY.binary_search_by_key(&c, |&(from, _)| from) {
+                Ok(i) => folded.push_str(COMPATIBILITY[i].1),
+                Err(_) => folded.push(c),
+            },
+        }
+    }
+    nfc(&folded)
+}
+
+fn compose(base: char, mark: char) -> Option<char> {
+    if !('\u{300}'..='\u{36F}').contains(&mark) {
+        return None;
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(base, mark), |&(base, mark, _)| (base, mark))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+// Characters that NFC replaces with another, identical-looking one.
+fn canonical_singleton(c: char) -> char {
+    match c {
+        '\u{2126}' => 'Ω', // Ohm sign
+        '\u{212A}' => 'K', // Kelvin sign
+        '\u{212B}' => 'Å', // Angstrom sign
+        _ => c,
+    }
+}
+
+// Generated from Python's `unicodedata`.
+
+/// Each letter and combining accent that compose to one character, sorted for a binary
+/// search: Latin-1, Latin Extended-A and B, and Latin Extended Additional (Vietnamese).
+#[rustfmt::skip]
+static COMPOSITIONS: [(char, char, char); 497] = [
+    ('A', '\u{300}', 'À'), ('A', '\u{301}', 'Á'), ('A', '\u{302}', 'Â'), ('A', '\u{303}', 'Ã'),
+    ('A', '\u{304}', 'Ā'), ('A', '\u{306}', 'Ă'), ('A', '\u{307}', 'Ȧ'), ('A', '\u{308}', 'Ä'),
+    ('A', '\u{309}', 'Ả'), ('A', '\u{30a}', 'Å'), ('A', '\u{30c}', 'Ǎ'), ('A', '\u{30f}', 'Ȁ'),
+    ('A', '\u{311}', 'Ȃ'), ('A', '\u{323}', 'Ạ'), ('A', '\u{325}', 'Ḁ'), ('A', '\u{328}', 'Ą'),
+    ('B', '\u{307}', 'Ḃ'), ('B', '\u{323}', 'Ḅ'), ('B', '\u{331}', 'Ḇ'), ('C', '\u{301}', 'Ć'),
+    ('C', '\u{302}', 'Ĉ'), ('C', '\u{307}', 'Ċ'), ('C', '\u{30c}', 'Č'), ('C', '\u{327}', 'Ç'),
+    ('D', '\u{307}', 'Ḋ'), ('D', '\u{30c}', 'Ď'), ('D', '\u{323}', 'Ḍ'), ('D', '\u{327}', 'Ḑ'),
+    ('D', '\u{32d}', 'Ḓ'), ('D', '\u{331}', 'Ḏ'), ('E', '\u{300}', 'È'), ('E', '\u{301}', 'É'),
+    ('E', '\u{302}', 'Ê'), ('E', '\u{303}', 'Ẽ'), ('E', '\u{304}', 'Ē'), ('E', '\u{306}', 'Ĕ'),
+    ('E', '\u{307}', 'Ė'), ('E', '\u{308}', 'Ë'), ('E', '\u{309}', 'Ẻ'), ('E', '\u{30c}', 'Ě'),
+    ('E', '\u{30f}', 'Ȅ'), ('E', '\u{311}', 'Ȇ'), ('E', '\u{323}', 'Ẹ'), ('E', '\u{327}', 'Ȩ'),
+    ('E', '\u{328}', 'Ę'), ('E', '\u{32d}', 'Ḙ'), ('E', '\u{330}', 'Ḛ'), ('F', '\u{307}', 'Ḟ'),
+    ('G', '\u{301}', 'Ǵ'), ('G', '\u{302}', 'Ĝ'), ('G', '\u{304}', 'Ḡ'), ('G', '\u{306}', 'Ğ'),
+    ('G', '\u{307}', 'Ġ'), ('G', '\u{30c}', 'Ǧ'), ('G', '\u{327}', 'Ģ'), ('H', '\u{302}', 'Ĥ'),
+    ('H', '\u{307}', 'Ḣ'), ('H', '\u{308}', 'Ḧ'), ('H', '\u{30c}', 'Ȟ'), ('H', '\u{323}', 'Ḥ'),
+    ('H', '\u{327}', 'Ḩ'), ('H', '\u{32e}', 'Ḫ'), ('I', '\u{300}', 'Ì'), ('I', '\u{301}', 'Í'),
+    ('I', '\u{302}', 'Î'), ('I', '\u{303}', 'Ĩ'), ('I', '\u{304}', 'Ī'), ('I', '\u{306}', 'Ĭ'),
+    ('I', '\u{307}', 'İ'), ('I', '\u{308}', 'Ï'), ('I', '\u{309}', 'Ỉ'), ('I', '\u{30c}', 'Ǐ'),
+    ('I', '\u{30f}', 'Ȉ'), ('I', '\u{311}', 'Ȋ'), ('I', '\u{323}', 'Ị'), ('I', '\u{328}', 'Į'),
+    ('I', '\u{330}', 'Ḭ'), ('J', '\u{302}', 'Ĵ'), ('K', '\u{301}', 'Ḱ'), ('K', '\u{30c}', 'Ǩ'),
+    ('K', '\u{323}', 'Ḳ'), ('K', '\u{327}', 'Ķ'), ('K', '\u{331}', 'Ḵ'), ('L', '\u{301}', 'Ĺ'),
+    ('L', '\u{30c}', 'Ľ'), ('L', '\u{323}', 'Ḷ'), ('L', '\u{327}', 'Ļ'), ('L', '\u{32d}', 'Ḽ'),
+    ('L', '\u{331}', 'Ḻ'), ('M', '\u{301}', 'Ḿ'), ('M', '\u{307}', 'Ṁ'), ('M', '\u{323}', 'Ṃ'),
+    ('N', '\u{300}', 'Ǹ'), ('N', '\u{301}', 'Ń'), ('N', '\u{303}', 'Ñ'), ('N', '\u{307}', 'Ṅ'),
+    ('N', '\u{30c}', 'Ň'), ('N', '\u{323}', 'Ṇ'), ('N', '\u{327}', 'Ņ'), ('N', '\u{32d}', 'Ṋ'),
+    ('N', '\u{331}', 'Ṉ'), ('O', '\u{300}', 'Ò'), ('O', '\u{301}', 'Ó'), ('O', '\u{302}', 'Ô'),
+    ('O', '\u{303}', 'Õ'), ('O', '\u{304}', 'Ō'), ('O', '\u{306}', 'Ŏ'), ('O', '\u{307}', 'Ȯ'),
+    ('O', '\u{308}', 'Ö'), ('O', '\u{309}', 'Ỏ'), ('O', '\u{30b}', 'Ő'), ('O', '\u{30c}', 'Ǒ'),
+    ('O', '\u{30f}', 'Ȍ'), ('O', '\u{311}', 'Ȏ'), ('O', '\u{31b}', 'Ơ'), ('O', '\u{323}', 'Ọ'),
+    ('O', '\u{328}', 'Ǫ'), ('P', '\u{301}', 'Ṕ'), ('P', '\u{307}', 'Ṗ'), ('R', '\u{301}', 'Ŕ'),
+    ('R', '\u{307}', 'Ṙ'), ('R', '\u{30c}', 'Ř'), ('R', '\u{30f}', 'Ȑ'), ('R', '\u{311}', 'Ȓ'),
+    ('R', '\u{323}', 'Ṛ'), ('R', '\u{327}', 'Ŗ'), ('R', '\u{331}', 'Ṟ'), ('S', '\u{301}', 'Ś'),
+    ('S', '\u{302}', 'Ŝ'), ('S', '\u{307}', 'Ṡ'), ('S', '\u{30c}', 'Š'), ('S', '\u{323}', 'Ṣ'),
+    ('S', '\u{326}', 'Ș'), ('S', '\u{327}', 'Ş'), ('T', '\u{307}', 'Ṫ'), ('T', '\u{30c}', 'Ť'),
+    ('T', '\u{323}', 'Ṭ'), ('T', '\u{326}', 'Ț'), ('T', '\u{327}', 'Ţ'), ('T', '\u{32d}', 'Ṱ'),
+    ('T', '\u{331}', 'Ṯ'), ('U', '\u{300}', 'Ù'), ('U', '\u{301}', 'Ú'), ('U', '\u{302}', 'Û'),
+    ('U', '\u{303}', 'Ũ'), ('U', '\u{304}', 'Ū'), ('U', '\u{306}', 'Ŭ'), ('U', '\u{308}', 'Ü'),
+    ('U', '\u{309}', 'Ủ'), ('U', '\u{30a}', 'Ů'), ('U', '\u{30b}', 'Ű'), ('U', '\u{30c}', 'Ǔ'),
+    ('U', '\u{30f}', 'Ȕ'), ('U', '\u{311}', 'Ȗ'), ('U', '\u{31b}', 'Ư'), ('U', '\u{323}', 'Ụ'),
+    ('U', '\u{324}', 'Ṳ'), ('U', '\u{328}', 'Ų'), ('U', '\u{32d}', 'Ṷ'), ('U', '\u{330}', 'Ṵ'),
+    ('V', '\u{303}', 'Ṽ'), ('V', '\u{323}', 'Ṿ'), ('W', '\u{300}', 'Ẁ'), ('W', '\u{301}', 'Ẃ'),
+    ('W', '\u{302}', 'Ŵ'), ('W', '\u{307}', 'Ẇ'), ('W', '\u{308}', 'Ẅ'), ('W', '\u{323}', 'Ẉ'),
+    ('X', '\u{307}', 'Ẋ'), ('X', '\u{308}', 'Ẍ'), ('Y', '\u{300}', 'Ỳ'), ('Y', '\u{301}', 'Ý'),
+    ('Y', '\u{302}', 'Ŷ'), ('Y', '\u{303}', 'Ỹ'), ('Y', '\u{304}', 'Ȳ'), ('Y', '\u{307}', 'Ẏ'),
+    ('Y', '\u{308}', 'Ÿ'), ('Y', '\u{309}', 'Ỷ'), ('Y', '\u{323}', 'Ỵ'), ('Z', '\u{301}', 'Ź'),
+    ('Z', '\u{302}', 'Ẑ'), ('Z', '\u{307}', 'Ż'), ('Z', '\u{30c}', 'Ž'), ('Z', '\u{323}', 'Ẓ'),
+    ('Z', '\u{331}', 'Ẕ'), ('a', '\u{300}', 'à'), ('a', '\u{301}', 'á'), ('a', '\u{302}', 'â'),
+    ('a', '\u{303}', 'ã'), ('a', '\u{304}', 'ā'), ('a', '\u{306}', 'ă'), ('a', '\u{307}', 'ȧ'),
+    ('a', '\u{308}', 'ä'), ('a', '\u{309}', 'ả'), ('a', '\u{30a}', 'å'), ('a', '\u{30c}', 'ǎ'),
+    ('a', '\u{30f}', 'ȁ'), ('a', '\u{311}', 'ȃ'), ('a', '\u{323}', 'ạ'), ('a', '\u{325}', 'ḁ'),
+    ('a', '\u{328}', 'ą'), ('b', '\u{307}', 'ḃ'), ('b', '\u{323}', 'ḅ'), ('b', '\u{331}', 'ḇ'),
+    ('c', '\u{301}', 'ć'), ('c', '\u{302}', 'ĉ'), ('c', '\u{307}', 'ċ'), ('c', '\u{30c}', 'č'),
+    ('c', '\u{327}', 'ç'), ('d', '\u{307}', 'ḋ'), ('d', '\u{30c}', 'ď'), ('d', '\u{323}', 'ḍ'),
+    ('d', '\u{327}', 'ḑ'), ('d', '\u{32d}', 'ḓ'), ('d', '\u{331}', 'ḏ'), ('e', '\u{300}', 'è'),
+    ('e', '\u{301}', 'é'), ('e', '\u{302}', 'ê'), ('e', '\u{303}', 'ẽ'), ('e', '\u{304}', 'ē'),
+    ('e', '\u{306}', 'ĕ'), ('e', '\u{307}', 'ė'), ('e', '\u{308}', 'ë'), ('e', '\u{309}', 'ẻ'),
+    ('e', '\u{30c}', 'ě'), ('e', '\u{30f}', 'ȅ'), ('e', '\u{311}', 'ȇ'), ('e', '\u{323}', 'ẹ'),
+    ('e', '\u{327}', 'ȩ'), ('e', '\u{328}', 'ę'), ('e', '\u{32d}', 'ḙ'), ('e', '\u{330}', 'ḛ'),
+    ('f', '\u{307}', 'ḟ'), ('g', '\u{301}', 'ǵ'), ('g', '\u{302}', 'ĝ'), ('g', '\u{304}', 'ḡ'),
+    ('g', '\u{306}', 'ğ'), ('g', '\u{307}', 'ġ'), ('g', '\u{30c}', 'ǧ'), ('g', '\u{327}', 'ģ'),
+    ('h', '\u{302}', 'ĥ'), ('h', '\u{307}', 'ḣ'), ('h', '\u{308}', 'ḧ'), ('h', '\u{30c}', 'ȟ'),
+    ('h', '\u{323}', 'ḥ'), ('h', '\u{327}', 'ḩ'), ('h', '\u{32e}', 'ḫ'), ('h', '\u{331}', 'ẖ'),
+    ('i', '\u{300}', 'ì'), ('i', '\u{301}', 'í'), ('i', '\u{302}', 'î'), ('i', '\u{303}', 'ĩ'),
+    ('i', '\u{304}', 'ī'), ('i', '\u{306}', 'ĭ'), ('i', '\u{308}', 'ï'), ('i', '\u{309}', 'ỉ'),
+    ('i', '\u{30c}', 'ǐ'), ('i', '\u{30f}', 'ȉ'), ('i', '\u{311}', 'ȋ'), ('i', '\u{323}', 'ị'),
+    ('i', '\u{328}', 'į'), ('i', '\u{330}', 'ḭ'), ('j', '\u{302}', 'ĵ'), ('j', '\u{30c}', 'ǰ'),
+    ('k', '\u{301}', 'ḱ'), ('k', '\u{30c}', 'ǩ'), ('k', '\u{323}', 'ḳ'), ('k', '\u{327}', 'ķ'),
+    ('k', '\u{331}', 'ḵ'), ('l', '\u{301}', 'ĺ'), ('l', '\u{30c}', 'ľ'), ('l', '\u{323}', 'ḷ'),
+    ('l', '\u{327}', 'ļ'), ('l', '\u{32d}', 'ḽ'), ('l', '\u{331}', 'ḻ'), ('m', '\u{301}', 'ḿ'),
+    ('m', '\u{307}', 'ṁ'), ('m', '\u{323}', 'ṃ'), ('n', '\u{300}', 'ǹ'), ('n', '\u{301}', 'ń'),
+    ('n', '\u{303}', 'ñ'), ('n', '\u{307}', 'ṅ'), ('n', '\u{30c}', 'ň'), ('n', '\u{323}', 'ṇ'),
+    ('n', '\u{327}', 'ņ'), ('n', '\u{32d}', 'ṋ'), ('n', '\u{331}', 'ṉ'), ('o', '\u{300}', 'ò'),
+    ('o', '\u{301}', 'ó'), ('o', '\u{302}', 'ô'), ('o', '\u{303}', 'õ'), ('o', '\u{304}', 'ō'),
+    ('o', '\u{306}', 'ŏ'), ('o', '\u{307}', 'ȯ'), ('o', '\u{308}', 'ö'), ('o', '\u{309}', 'ỏ'),
+    ('o', '\u{30b}', 'ő'), ('o', '\u{30c}', 'ǒ'), ('o', '\u{30f}', 'ȍ'), ('o', '\u{311}', 'ȏ'),
+    ('o', '\u{31b}', 'ơ'), ('o', '\u{323}', 'ọ'), ('o', '\u{328}', 'ǫ'), ('p', '\u{301}', 'ṕ'),
+    ('p', '\u{307}', 'ṗ'), ('r', '\u{301}', 'ŕ'), ('r', '\u{307}', 'ṙ'), ('r', '\u{30c}', 'ř'),
+    ('r', '\u{30f}', 'ȑ'), ('r', '\u{311}', 'ȓ'), ('r', '\u{323}', 'ṛ'), ('r', '\u{327}', 'ŗ'),
+    ('r', '\u{331}', 'ṟ'), ('s', '\u{301}', 'ś'), ('s', '\u{302}', 'ŝ'), ('s', '\u{307}', 'ṡ'),
+    ('s', '\u{30c}', 'š'), ('s', '\u{323}', 'ṣ'), ('s', '\u{326}', 'ș'), ('s', '\u{327}', 'ş'),
+    ('t', '\u{307}', 'ṫ'), ('t', '\u{308}', 'ẗ'), ('t', '\u{30c}', 'ť'), ('t', '\u{323}', 'ṭ'),
+    ('t', '\u{326}', 'ț'), ('t', '\u{327}', 'ţ'), ('t', '\u{32d}', 'ṱ'), ('t', '\u{331}', 'ṯ'),
+    ('u', '\u{300}', 'ù'), ('u', '\u{301}', 'ú'), ('u', '\u{302}', 'û'), ('u', '\u{303}', 'ũ'),
+    ('u', '\u{304}', 'ū'), ('u', '\u{306}', 'ŭ'), ('u', '\u{308}', 'ü'), ('u', '\u{309}', 'ủ'),
+    ('u', '\u{30a}', 'ů'), ('u', '\u{30b}', 'ű'), ('u', '\u{30c}', 'ǔ'), ('u', '\u{30f}', 'ȕ'),
+    ('u', '\u{311}', 'ȗ'), ('u', '\u{31b}', 'ư'), ('u', '\u{323}', 'ụ'), ('u', '\u{324}', 'ṳ'),
+    ('u', '\u{328}', 'ų'), ('u', '\u{32d}', 'ṷ'), ('u', '\u{330}', 'ṵ'), ('v', '\u{303}', 'ṽ'),
+    ('v', '\u{323}', 'ṿ'), ('w', '\u{300}', 'ẁ'), ('w', '\u{301}', 'ẃ'), ('w', '\u{302}', 'ŵ'),
+    ('w', '\u{307}', 'ẇ'), ('w', '\u{308}', 'ẅ'), ('w', '\u{30a}', 'ẘ'), ('w', '\u{323}', 'ẉ'),
+    ('x', '\u{307}', 'ẋ'), ('x', '\u{308}', 'ẍ'), ('y', '\u{300}', 'ỳ'), ('y', '\u{301}', 'ý'),
+    ('y', '\u{302}', 'ŷ'), ('y', '\u{303}', 'ỹ'), ('y', '\u{304}', 'ȳ'), ('y', '\u{307}', 'ẏ'),
+    ('y', '\u{308}', 'ÿ'), ('y', '\u{309}', 'ỷ'), ('y', '\u{30a}', 'ẙ'), ('y', '\u{323}', 'ỵ'),
+    ('z', '\u{301}', 'ź'), ('z', '\u{302}', 'ẑ'), ('z', '\u{307}', 'ż'), ('z', '\u{30c}', 'ž'),
+    ('z', '\u{323}', 'ẓ'), ('z', '\u{331}', 'ẕ'), ('Â', '\u{300}', 'Ầ'), ('Â', '\u{301}', 'Ấ'),
+    ('Â', '\u{303}', 'Ẫ'), ('Â', '\u{309}', 'Ẩ'), ('Ä', '\u{304}', 'Ǟ'), ('Å', '\u{301}', 'Ǻ'),
+    ('Æ', '\u{301}', 'Ǽ'), ('Æ', '\u{304}', 'Ǣ'), ('Ç', '\u{301}', 'Ḉ'), ('Ê', '\u{300}', 'Ề'),
+    ('Ê', '\u{301}', 'Ế'), ('Ê', '\u{303}', 'Ễ'), ('Ê', '\u{309}', 'Ể'), ('Ï', '\u{301}', 'Ḯ'),
+    ('Ô', '\u{300}', 'Ồ'), ('Ô', '\u{301}', 'Ố'), ('Ô', '\u{303}', 'Ỗ'), ('Ô', '\u{309}', 'Ổ'),
+    ('Õ', '\u{301}', 'Ṍ'), ('Õ', '\u{304}', 'Ȭ'), ('Õ', '\u{308}', 'Ṏ'), ('Ö', '\u{304}', 'Ȫ'),
+    ('Ø', '\u{301}', 'Ǿ'), ('Ü', '\u{300}', 'Ǜ'), ('Ü', '\u{301}', 'Ǘ'), ('Ü', '\u{304}', 'Ǖ'),
+    ('Ü', '\u{30c}', 'Ǚ'), ('â', '\u{300}', 'ầ'), ('â', '\u{301}', 'ấ'), ('â', '\u{303}', 'ẫ'),
+    ('â', '\u{309}', 'ẩ'), ('ä', '\u{304}', 'ǟ'), ('å', '\u{301}', 'ǻ'), ('æ', '\u{301}', 'ǽ'),
+    ('æ', '\u{304}', 'ǣ'), ('ç', '\u{301}', 'ḉ'), ('ê', '\u{300}', 'ề'), ('ê', '\u{301}', 'ế'),
+    ('ê', '\u{303}', 'ễ'), ('ê', '\u{309}', 'ể'), ('ï', '\u{301}', 'ḯ'), ('ô', '\u{300}', 'ồ'),
+    ('ô', '\u{301}', 'ố'), ('ô', '\u{303}', 'ỗ'), ('ô', '\u{309}', 'ổ'), ('õ', '\u{301}', 'ṍ'),
+    ('õ', '\u{304}', 'ȭ'), ('õ', '\u{308}', 'ṏ'), ('ö', '\u{304}', 'ȫ'), ('ø', '\u{301}', 'ǿ'),
+    ('ü', '\u{300}', 'ǜ'), ('ü', '\u{301}', 'ǘ'), ('ü', '\u{304}', 'ǖ'), ('ü', '\u{30c}', 'ǚ'),
+    ('Ă', '\u{300}', 'Ằ'), ('Ă', '\u{301}', 'Ắ'), ('Ă', '\u{303}', 'Ẵ'), ('Ă', '\u{309}', 'Ẳ'),
+    ('ă', '\u{300}', 'ằ'), ('ă', '\u{301}', 'ắ'), ('ă', '\u{303}', 'ẵ'), ('ă', '\u{309}', 'ẳ'),
+    ('Ē', '\u{300}', 'Ḕ'), ('Ē', '\u{301}', 'Ḗ'), ('ē', '\u{300}', 'ḕ'), ('ē', '\u{301}', 'ḗ'),
+    ('Ō', '\u{300}', 'Ṑ'), ('Ō', '\u{301}', 'Ṓ'), ('ō', '\u{300}', 'ṑ'), ('ō', '\u{301}', 'ṓ'),
+    ('Ś', '\u{307}', 'Ṥ'), ('ś', '\u{307}', 'ṥ'), ('Š', '\u{307}', 'Ṧ'), ('š', '\u{307}', 'ṧ'),
+    ('Ũ', '\u{301}', 'Ṹ'), ('ũ', '\u{301}', 'ṹ'), ('Ū', '\u{308}', 'Ṻ'), ('ū', '\u{308}', 'ṻ'),
+    ('ſ', '\u{307}', 'ẛ'), ('Ơ', '\u{300}', 'Ờ'), ('Ơ', '\u{301}', 'Ớ'), ('Ơ', '\u{303}', 'Ỡ'),
+    ('Ơ', '\u{309}', 'Ở'), ('Ơ', '\u{323}', 'Ợ'), ('ơ', '\u{300}', 'ờ'), ('ơ', '\u{301}', 'ớ'),
+    ('ơ', '\u{303}', 'ỡ'), ('ơ', '\u{309}', 'ở'), ('ơ', '\u{323}', 'ợ'), ('Ư', '\u{300}', 'Ừ'),
+    ('Ư', '\u{301}', 'Ứ'), ('Ư', '\u{303}', 'Ữ'), ('Ư', '\u{309}', 'Ử'), ('Ư', '\u{323}', 'Ự'),
+    ('ư', '\u{300}', 'ừ'), ('ư', '\u{301}', 'ứ'), ('ư', '\u{303}', 'ữ'), ('ư', '\u{309}', 'ử'),
+    ('ư', '\u{323}', 'ự'), ('Ʒ', '\u{30c}', 'Ǯ'), ('Ǫ', '\u{304}', 'Ǭ'), ('ǫ', '\u{304}', 'ǭ'),
+    ('Ȧ', '\u{304}', 'Ǡ'), ('ȧ', '\u{304}', 'ǡ'), ('Ȩ', '\u{306}', 'Ḝ'), ('ȩ', '\u{306}', 'ḝ'),
+    ('Ȯ', '\u{304}', 'Ȱ'), ('ȯ', '\u{304}', 'ȱ'), ('ʒ', '\u{30c}', 'ǯ'), ('Ḷ', '\u{304}', 'Ḹ'),
+    ('ḷ', '\u{304}', 'ḹ'), ('Ṛ', '\u{304}', 'Ṝ'), ('ṛ', '\u{304}', 'ṝ'), ('Ṣ', '\u{307}', 'Ṩ'),
+    ('ṣ', '\u{307}', 'ṩ'), ('Ạ', '\u{302}', 'Ậ'), ('Ạ', '\u{306}', 'Ặ'), ('ạ', '\u{302}', 'ậ'),
+    ('ạ', '\u{306}', 'ặ'), ('Ẹ', '\u{302}', 'Ệ'), ('ẹ', '\u{302}', 'ệ'), ('Ọ', '\u{302}', 'Ộ'),
+    ('ọ', '\u{302}', 'ộ'),
+];
+
+/// Compatibility characters and what NFKC folds them to, sorted for a binary search:
+/// Latin-1's, general punctuation, super- and subscripts, letterlike symbols, number
+/// forms and the ligatures. Fullwidth forms and the typographic spaces are done apart.
+#[rustfmt::skip]
+static COMPATIBILITY: [(char, &str); 183] = [
+    ('\u{a0}', " "), ('¨', " \u{308}"), ('ª', "a"), ('¯', " \u{304}"),
+    ('²', "2"), ('³', "3"), ('´', " \u{301}"), ('µ', "μ"),
+    ('¸', " \u{327}"), ('¹', "1"), ('º', "o"), ('¼', "1⁄4"),
+    ('½', "1⁄2"), ('¾', "3⁄4"), ('‑', "‐"), ('‗', " \u{333}"),
+    ('․', "."), ('‥', ".."), ('…', "..."), ('\u{202f}', " "),
+    ('″', "′′"), ('‴', "′′′"), ('‶', "‵‵"), ('‷', "‵‵‵"),
+    ('‼', "!!"), ('‾', " \u{305}"), ('⁇', "??"), ('⁈', "?!"),
+    ('⁉', "!?"), ('⁗', "′′′′"), ('\u{205f}', " "), ('⁰', "0"),
+    ('ⁱ', "i"), ('⁴', "4"), ('⁵', "5"), ('⁶', "6"),
+    ('⁷', "7"), ('⁸', "8"), ('⁹', "9"), ('⁺', "+"),
+    ('⁻', "−"), ('⁼', "="), ('⁽', "("), ('⁾', ")"),
+    ('ⁿ', "n"), ('₀', "0"), ('₁', "1"), ('₂', "2"),
+    ('₃', "3"), ('₄', "4"), ('₅', "5"), ('₆', "6"),
+    ('₇', "7"), ('₈', "8"), ('₉', "9"), ('₊', "+"),
+    ('₋', "−"), ('₌', "="), ('₍', "("), ('₎', ")"),
+    ('ₐ', "a"), ('ₑ', "e"), ('ₒ', "o"), ('ₓ', "x"),
+    ('ₔ', "ə"), ('ₕ', "h"), ('ₖ', "k"), ('ₗ', "l"),
+    ('ₘ', "m"), ('ₙ', "n"), ('ₚ', "p"), ('ₛ', "s"),
+    ('ₜ', "t"), ('₨', "Rs"), ('℀', "a/c"), ('℁', "a/s"),
+    ('ℂ', "C"), ('℃', "°C"), ('℅', "c/o"), ('℆', "c/u"),
+    ('ℇ', "Ɛ"), ('℉', "°F"), ('ℊ', "g"), ('ℋ', "H"),
+    ('ℌ', "H"), ('ℍ', "H"), ('ℎ', "h"), ('ℏ', "ħ"),
+    ('ℐ', "I"), ('ℑ', "I"), ('ℒ', "L"), ('ℓ', "l"),
+    ('ℕ', "N"), ('№', "No"), ('ℙ', "P"), ('ℚ', "Q"),
+    ('ℛ', "R"), ('ℜ', "R"), ('ℝ', "R"), ('℠', "SM"),
+    ('℡', "TEL"), ('™', "TM"), ('ℤ', "Z"), ('ℨ', "Z"),
+    ('ℬ', "B"), ('ℭ', "C"), ('ℯ', "e"), ('ℰ', "E"),
+    ('ℱ', "F"), ('ℳ', "M"), ('ℴ', "o"), ('ℵ', "א"),
+    ('ℶ', "ב"), ('ℷ', "ג"), ('ℸ', "ד"), ('ℹ', "i"),
+    ('℻', "FAX"), ('ℼ', "π"), ('ℽ', "γ"), ('ℾ', "Γ"),
+    ('ℿ', "Π"), ('⅀', "∑"), ('ⅅ', "D"), ('ⅆ', "d"),
+    ('ⅇ', "e"), ('ⅈ', "i"), ('ⅉ', "j"), ('⅐', "1⁄7"),
+    ('⅑', "1⁄9"), ('⅒', "1⁄10"), ('⅓', "1⁄3"), ('⅔', "2⁄3"),
+    ('⅕', "1⁄5"), ('⅖', "2⁄5"), ('⅗', "3⁄5"), ('⅘', "4⁄5"),
+    ('⅙', "1⁄6"), ('⅚', "5⁄6"), ('⅛', "1⁄8"), ('⅜', "3⁄8"),
+    ('⅝', "5⁄8"), ('⅞', "7⁄8"), ('⅟', "1⁄"), ('Ⅰ', "I"),
+    ('Ⅱ', "II"), ('Ⅲ', "III"), ('Ⅳ', "IV"), ('Ⅴ', "V"),
+    ('Ⅵ', "VI"), ('Ⅶ', "VII"), ('Ⅷ', "VIII"), ('Ⅸ', "IX"),
+    ('Ⅹ', "X"), ('Ⅺ', "XI"), ('Ⅻ', "XII"), ('Ⅼ', "L"),
+    ('Ⅽ', "C"), ('Ⅾ', "D"), ('Ⅿ', "M"), ('ⅰ', "i"),
+    ('ⅱ', "ii"), ('ⅲ', "iii"), ('ⅳ', "iv"), ('ⅴ', "v"),
+    ('ⅵ', "vi"), ('ⅶ', "vii"), ('ⅷ', "viii"), ('ⅸ', "ix"),
+    ('ⅹ', "x"), ('ⅺ', "xi"), ('ⅻ', "xii"), ('ⅼ', "l"),
+    ('ⅽ', "c"), ('ⅾ', "d"), ('ⅿ', "m"), ('↉', "0⁄3"),
+    ('ﬀ', "ff"), ('ﬁ', "fi"), ('ﬂ', "fl"), ('ﬃ', "ffi"),
+    ('ﬄ', "ffl"), ('ﬅ', "st"), ('ﬆ', "st"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nfc_composes_latin_accents() {
+        assert_eq!(nfc("Cafe\u{301} cre\u{300}me"), "Café crème");
+        assert_eq!(nfc("A\u{30A}ngstro\u{308}m"), "Ångström");
+        // Vietnamese stacks a second accent on an already composed letter.
+        assert_eq!(nfc("Vie\u{323}\u{302}t"), "Việt");
+        assert_eq!(nfc("\u{2126} \u{212A} \u{212B}"), "Ω K Å");
+    }
+
+    #[test]
+    fn nfc_leaves_the_rest_alone() {
+        for text in ["Café", "plain ASCII", "x\u{301}", "α\u{301}", "e\u{20DD}"] {
+            assert_eq!(nfc(text), text);
+        }
+    }
+
+    #[test]
+    fn nfkc_folds_compatibility_characters() {
+        assert_eq!(nfkc("ﬁne ﬂow"), "fine flow");
+        assert_eq!(nfkc("ＡＢＣ１２３！"), "ABC123!");
+        assert_eq!(nfkc("½ x² H₂O"), "1⁄2 x2 H2O");
+        assert_eq!(nfkc("a\u{3000}b\u{2003}c"), "a b c");
+        // Folded first, then composed.
+        assert_eq!(nfkc("ｅ\u{301}"), "é");
+    }
+}
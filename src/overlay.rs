@@ -0,0 +1,234 @@
+//! A brief label near the mouse cursor confirming what an activation did (Windows only).
+//!
+//! The label is a click-through, never-focused popup, so it shows over full-screen apps
+//! without taking the focus from the window that was just pasted into.
+
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Once;
+use std::thread;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{
+    BeginPaint, CreateFontW, CreateSolidBrush, DeleteObject, DrawTextW, EndPaint, FillRect, GetDC,
+    GetMonitorInfoW, GetTextExtentPoint32W, MonitorFromPoint, ReleaseDC, SelectObject, SetBkMode,
+    SetTextColor, CLEARTYPE_QUALITY, CLIP_DEFAULT_PRECIS, DEFAULT_CHARSET, DEFAULT_PITCH,
+    DT_CENTER, DT_SINGLELINE, DT_VCENTER, FW_SEMIBOLD, HFONT, MONITORINFO,
+    MONITOR_DEFAULTTONEAREST, OUT_DEFAULT_PRECIS, PAINTSTRUCT, TRANSPARENT,
+};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect, GetCursorPos,
+    GetMessageW, PostMessageW, PostQuitMessage, RegisterClassW, SetLayeredWindowAttributes,
+    SetTimer, ShowWindow, TranslateMessage, LWA_ALPHA, MSG, SW_SHOWNOACTIVATE, WM_CLOSE,
+    WM_DESTROY, WM_PAINT, WM_TIMER, WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW,
+    WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
+};
+
+use crate::activity::Kind;
+
+const CLASS_NAME: &str = "flatten-string-overlay";
+/// How long the label stays up.
+const SHOWN_FOR_MS: u32 = 1200;
+/// Longer messages are cut short, e.g. error chains.
+const MAX_CHARS: usize = 60;
+/// Space between the text and the label's edges, and between the label and the cursor.
+const PADDING: i32 = 12;
+
+/// The label that's up, so a newer one can replace it.
+static CURRENT: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(ptr::null_mut());
+
+thread_local! {
+    // Each label has a thread of its own, where its window procedure finds its text.
+    static LABEL: RefCell<Label> = const {
+        RefCell::new(Label { text: Vec::new(), font: ptr::null_mut() })
+    };
+}
+
+struct Label {
+    text: Vec<u16>,
+    font: HFONT,
+}
+
+/// Shows `message` near the cursor for a moment, marked with a tick, dash or cross for
+/// `kind`, in place of any label that's still up.
+pub fn show(kind: Kind, message: &str) {
+    let mark = match kind {
+        Kind::Success => '✓',
+        Kind::Skipped => '–',
+        Kind::Failure => '✗',
+    };
+    let mut text: String = message.chars().take(MAX_CHARS).collect();
+    if message.chars().count() > MAX_CHARS {
+        text.push('…');
+    }
+    let text = format!("{} {}", mark, text);
+    let spawned = thread::Builder::new()
+        .name("overlay".to_string())
+        .spawn(move || {
+            if let Err(e) = run(&text) {
+                log::warn!("Failed to show the overlay: {:?}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("Failed to show the overlay: {}", e);
+    }
+}
+
+fn run(text: &str) -> Result<()> {
+    let text: Vec<u16> = text.encode_utf16().collect();
+    let font = unsafe {
+        CreateFontW(
+            -20,
+            0,
+            0,
+            0,
+            FW_SEMIBOLD as i32,
+            0,
+            0,
+            0,
+            DEFAULT_CHARSET as u32,
+            OUT_DEFAULT_PRECIS as u32,
+            CLIP_DEFAULT_PRECIS as u32,
+            CLEARTYPE_QUALITY as u32,
+            DEFAULT_PITCH as u32,
+            wide("Segoe UI").as_ptr(),
+        )
+    };
+    let window = create_window(&text, font)?;
+    LABEL.with(|label| *label.borrow_mut() = Label { text, font });
+
+    // Replaces the previous label, whose thread then ends.
+    let previous = CURRENT.swap(window, Ordering::AcqRel);
+    if !previous.is_null() {
+        unsafe { PostMessageW(previous, WM_CLOSE, 0, 0) };
+    }
+    unsafe {
+        SetLayeredWindowAttributes(window, 0, 220, LWA_ALPHA);
+        ShowWindow(window, SW_SHOWNOACTIVATE);
+        SetTimer(window, 1, SHOWN_FOR_MS, None);
+    }
+
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+    while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+        unsafe {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+    let _ = CURRENT.compare_exchange(window, ptr::null_mut(), Ordering::AcqRel, Ordering::Acquire);
+    unsafe { DeleteObject(font) };
+    Ok(())
+}
+
+// A popup sized to `text`, just below and right of the cursor but inside its screen.
+fn create_window(text: &[u16], font: HFONT) -> Result<HWND> {
+    static REGISTER: Once = Once::new();
+    let class_name = wide(CLASS_NAME);
+    unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        REGISTER.call_once(|| {
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance,
+                lpszClassName: class_name.as_ptr(),
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+        });
+
+        let mut size = SIZE::default();
+        let screen = GetDC(ptr::null_mut());
+        let previous_font = SelectObject(screen, font);
+        GetTextExtentPoint32W(screen, text.as_ptr(), text.len() as i32, &mut size);
+        SelectObject(screen, previous_font);
+        ReleaseDC(ptr::null_mut(), screen);
+        let (width, height) = (size.cx + 2 * PADDING, size.cy + PADDING);
+
+        let mut cursor = POINT::default();
+        GetCursorPos(&mut cursor);
+        let mut monitor = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        GetMonitorInfoW(
+            MonitorFromPoint(cursor, MONITOR_DEFAULTTONEAREST),
+            &mut monitor,
+        );
+        let work = monitor.rcWork;
+        let x = (cursor.x + PADDING).min(work.right - width).max(work.left);
+        let y = (cursor.y + PADDING).min(work.bottom - height).max(work.top);
+
+        let window = CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            WS_POPUP,
+            x,
+            y,
+            width,
+            height,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            instance,
+            ptr::null(),
+        );
+        if window.is_null() {
+            return Err(io::Error::last_os_error()).context("Failed to create the overlay");
+        }
+        Ok(window)
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_PAINT => {
+            let mut paint: PAINTSTRUCT = std::mem::zeroed();
+            let dc = BeginPaint(window, &mut paint);
+            let background = CreateSolidBrush(rgb(32, 32, 32));
+            FillRect(dc, &paint.rcPaint, background);
+            DeleteObject(background);
+            LABEL.with(|label| {
+                let label = label.borrow();
+                let mut area = RECT::default();
+                GetClientRect(window, &mut area);
+                SelectObject(dc, label.font);
+                SetBkMode(dc, TRANSPARENT as i32);
+                SetTextColor(dc, rgb(255, 255, 255));
+                DrawTextW(
+                    dc,
+                    label.text.as_ptr(),
+                    label.text.len() as i32,
+                    &mut area,
+                    DT_CENTER | DT_VCENTER | DT_SINGLELINE,
+                );
+            });
+            EndPaint(window, &paint);
+            0
+        }
+        WM_TIMER => {
+            DestroyWindow(window);
+            0
+        }
+        WM_DESTROY => {
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(window, message, wparam, lparam),
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> u32 {
+    u32::from(r) | (u32::from(g) << 8) | (u32::from(b) << 16)
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
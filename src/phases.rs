@@ -0,0 +1,85 @@
+//! `--timing`: how long each phase of an activation took, printed after it, so delays can
+//! be tuned from measurements instead of guesses.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use strflatten::transform::TransformStep;
+
+/// The phases of the activation running now. Does nothing unless enabled.
+pub struct Phases {
+    enabled: bool,
+    current: RefCell<Current>,
+}
+
+struct Current {
+    /// When the last phase ended.
+    since: Instant,
+    done: Vec<(String, Duration)>,
+}
+
+impl Phases {
+    pub fn new(enabled: bool) -> Self {
+        Phases {
+            enabled,
+            current: RefCell::new(Current {
+                since: Instant::now(),
+                done: Vec::new(),
+            }),
+        }
+    }
+
+    /// Starts timing a new activation.
+    pub fn start(&self) {
+        if self.enabled {
+            *self.current.borrow_mut() = Current {
+                since: Instant::now(),
+                done: Vec::new(),
+            };
+        }
+    }
+
+    /// Ends the phase called `name`, which began when the last one ended.
+    pub fn end(&self, name: &str) {
+        if self.enabled {
+            let mut current = self.current.borrow_mut();
+            let duration = current.since.elapsed();
+            current.done.push((name.to_string(), duration));
+            current.since = Instant::now();
+        }
+    }
+
+    /// Ends the transform phase with a phase per step, as `apply_pipeline_timed` measured
+    /// them. Handing the text to the transformer and back isn't counted.
+    pub fn end_steps(&self, pipeline: &[TransformStep], timings: &[Duration]) {
+        if self.enabled {
+            let mut current = self.current.borrow_mut();
+            for (step, &duration) in pipeline.iter().zip(timings) {
+                current.done.push((format!("{:?}", step), duration));
+            }
+            current.since = Instant::now();
+        }
+    }
+
+    /// Prints the phases so far, e.g. `Timing: copy 2.1 ms, copy wait 48.0 ms, ...`.
+    pub fn report(&self) {
+        let current = self.current.borrow();
+        if !self.enabled || current.done.is_empty() {
+            return;
+        }
+        let mut line = String::from("Timing:");
+        for (i, (name, duration)) in current.done.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            let _ = write!(
+                line,
+                "{}{} {:.1} ms",
+                separator,
+                name,
+                duration.as_secs_f64() * 1000.0
+            );
+        }
+        let total: Duration = current.done.iter().map(|(_, duration)| *duration).sum();
+        let _ = write!(line, " (total {:.1} ms)", total.as_secs_f64() * 1000.0);
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,248 @@
+//! Native transform plugins: dynamic libraries in the plugin directory, each adding the
+//! pipeline step named after its file, e.g. `company_cleanup.dll` adds `company_cleanup`.
+//!
+//! A plugin exports three functions with the C ABI:
+//!
+//! ```c
+//! // Must return 1, the version of this interface.
+//! uint32_t strflatten_abi_version(void);
+//!
+//! // Transforms `len` bytes of UTF-8 at `text`. Returns a buffer holding the UTF-8 result
+//! // and stores its length in `*out_len`, or returns NULL to leave the text unchanged.
+//! // Called from any thread, but never from two at once.
+//! uint8_t *strflatten_transform(const uint8_t *text, size_t len, size_t *out_len);
+//!
+//! // Frees a buffer returned by strflatten_transform.
+//! void strflatten_free(uint8_t *buffer, size_t len);
+//! ```
+//!
+//! Libraries stay loaded until the program exits.
+//!
+//! Only native libraries are plugins. WebAssembly modules aren't supported: this build has
+//! no WebAssembly runtime to run them in a sandbox, so `.wasm` files are ignored like any
+//! other file.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::{c_void, CStr};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config;
+use strflatten::transform::{CustomTransforms, Transform, TransformStep};
+
+/// The version of the interface above that plugins have to implement.
+const ABI_VERSION: u32 = 1;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type TransformFn = unsafe extern "C" fn(*const u8, usize, *mut usize) -> *mut u8;
+type FreeFn = unsafe extern "C" fn(*mut u8, usize);
+
+/// Every plugin loaded so far by path, so rebuilding the settings on a reload or profile
+/// switch doesn't load them again.
+static LOADED: Mutex<BTreeMap<PathBuf, NativePlugin>> = Mutex::new(BTreeMap::new());
+
+/// `plugins` next to the config file at `config_path`, or else next to the default one.
+pub fn default_dir(config_path: Option<&Path>) -> Option<PathBuf> {
+    let config_path = config_path
+        .map(Path::to_path_buf)
+        .or_else(config::default_path)?;
+    Some(config_path.with_file_name("plugins"))
+}
+
+/// Adds every plugin in `dir` to `custom`. A missing directory just has no plugins. Only
+/// plugins that weren't there last time are loaded.
+pub fn load_dir(dir: &Path, custom: &mut CustomTransforms) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read plugin directory {}", dir.display()))
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == DLL_EXTENSION))
+        .collect();
+    paths.sort();
+
+    let mut loaded = LOADED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for path in paths {
+        let plugin = match loaded.get(&path) {
+            Some(plugin) => plugin.clone(),
+            None => {
+                let plugin = NativePlugin::load(&path)
+                    .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+                loaded.insert(path.clone(), plugin.clone());
+                plugin
+            }
+        };
+        if TransformStep::from_name(&plugin.name).is_builtin() {
+            return Err(anyhow!(
+                "Plugin {} is named like the built-in {:?} step; rename the file",
+                path.display(),
+                plugin.name
+            ));
+        }
+        custom.insert(plugin.name.clone(), plugin);
+    }
+    Ok(())
+}
+
+/// A plugin library's transform.
+#[derive(Clone)]
+struct NativePlugin {
+    /// The step name, from the file name.
+    name: String,
+    transform: TransformFn,
+    free: FreeFn,
+    /// Held while calling in, so the plugin never runs on two threads at once. Shared by
+    /// every copy.
+    lock: Arc<Mutex<()>>,
+}
+
+impl NativePlugin {
+    fn load(path: &Path) -> Result<Self> {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("The file name isn't valid UTF-8"))?;
+        // Unix linkers name libraries libNAME.so.
+        let name = match cfg!(windows) {
+            true => stem,
+            false => stem.strip_prefix("lib").unwrap_or(stem),
+        };
+
+        let library = open(path)?;
+        // SAFETY: plugins export these symbols with the signatures documented above.
+        let (version, transform, free) = unsafe {
+            (
+                std::mem::transmute::<*mut c_void, AbiVersionFn>(symbol(
+                    library,
+                    c"strflatten_abi_version",
+                )?),
+                std::mem::transmute::<*mut c_void, TransformFn>(symbol(
+                    library,
+                    c"strflatten_transform",
+                )?),
+                std::mem::transmute::<*mut c_void, FreeFn>(symbol(library, c"strflatten_free")?),
+            )
+        };
+        // SAFETY: as above.
+        let version = unsafe { version() };
+        if version != ABI_VERSION {
+            return Err(anyhow!(
+                "It implements version {} of the plugin interface, but version {} is needed",
+                version,
+                ABI_VERSION
+            ));
+        }
+
+        Ok(NativePlugin {
+            name: name.to_string(),
+            transform,
+            free,
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+}
+
+impl Transform for NativePlugin {
+    fn apply(&self, text: &str) -> String {
+        let _lock = self
+            .lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut len = 0;
+        // SAFETY: the plugin reads `text.len()` bytes of `text` and returns a buffer of `len`
+        // bytes (or NULL) that's ours until we hand it back to `free`.
+        let bytes = unsafe {
+            let result = (self.transform)(text.as_ptr(), text.len(), &mut len);
+            if result.is_null() {
+                return text.to_string();
+            }
+            let bytes = std::slice::from_raw_parts(result, len).to_vec();
+            (self.free)(result, len);
+            bytes
+        };
+        String::from_utf8(bytes).unwrap_or_else(|_| {
+            log::warn!(
+                "Plugin {:?} returned invalid UTF-8; leaving the text unchanged.",
+                self.name
+            );
+            text.to_string()
+        })
+    }
+}
+
+#[cfg(windows)]
+fn open(path: &Path) -> Result<*mut c_void> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::System::LibraryLoader::LoadLibraryW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `wide` is a NUL-terminated UTF-16 path.
+    let library = unsafe { LoadLibraryW(wide.as_ptr()) };
+    if library.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(library)
+}
+
+#[cfg(windows)]
+fn symbol(library: *mut c_void, name: &CStr) -> Result<*mut c_void> {
+    use windows_sys::Win32::System::LibraryLoader::GetProcAddress;
+
+    // SAFETY: `library` is a loaded module and `name` is NUL-terminated.
+    let address = unsafe { GetProcAddress(library, name.as_ptr().cast()) };
+    address
+        .map(|function| function as *mut c_void)
+        .ok_or_else(|| anyhow!("It doesn't export {}", name.to_string_lossy()))
+}
+
+#[cfg(unix)]
+fn open(path: &Path) -> Result<*mut c_void> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: `path` is NUL-terminated.
+    let library = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if library.is_null() {
+        return Err(anyhow!(dl_error()));
+    }
+    Ok(library)
+}
+
+#[cfg(unix)]
+fn symbol(library: *mut c_void, name: &CStr) -> Result<*mut c_void> {
+    // SAFETY: `library` came from dlopen and `name` is NUL-terminated.
+    let address = unsafe { libc::dlsym(library, name.as_ptr()) };
+    if address.is_null() {
+        return Err(anyhow!(
+            "It doesn't export {}: {}",
+            name.to_string_lossy(),
+            dl_error()
+        ));
+    }
+    Ok(address)
+}
+
+// The message of the last failed dlopen or dlsym.
+#[cfg(unix)]
+fn dl_error() -> String {
+    // SAFETY: dlerror returns NULL or a NUL-terminated message.
+    unsafe {
+        let message = libc::dlerror();
+        match message.is_null() {
+            true => "unknown error".to_string(),
+            false => CStr::from_ptr(message).to_string_lossy().into_owned(),
+        }
+    }
+}
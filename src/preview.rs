@@ -0,0 +1,228 @@
+//! A window showing a result and what it changed, to confirm before pasting (Windows only).
+
+use anyhow::{Context, Result};
+use std::cell::Cell;
+use std::io;
+use std::ptr;
+use std::sync::Once;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{
+    CreateFontW, DeleteObject, CLEARTYPE_QUALITY, CLIP_DEFAULT_PRECIS, COLOR_BTNFACE,
+    DEFAULT_CHARSET, FIXED_PITCH, FW_NORMAL, HBRUSH, HFONT, OUT_DEFAULT_PRECIS,
+};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect, GetDlgItem,
+    GetForegroundWindow, GetMessageW, IsDialogMessageW, MoveWindow, PostQuitMessage,
+    RegisterClassW, SendMessageW, SetForegroundWindow, SetWindowTextW, ShowWindow,
+    TranslateMessage, BS_DEFPUSHBUTTON, BS_PUSHBUTTON, CW_USEDEFAULT, ES_AUTOHSCROLL,
+    ES_AUTOVSCROLL, ES_MULTILINE, ES_READONLY, IDCANCEL, IDOK, MSG, SW_SHOW, WM_COMMAND,
+    WM_DESTROY, WM_SETFONT, WM_SIZE, WNDCLASSW, WS_CHILD, WS_EX_CLIENTEDGE, WS_EX_TOPMOST,
+    WS_HSCROLL, WS_OVERLAPPEDWINDOW, WS_TABSTOP, WS_VISIBLE, WS_VSCROLL,
+};
+
+use crate::diff;
+
+const CLASS_NAME: &str = "flatten-string-preview";
+const TEXT_ID: i32 = 100;
+/// 0 lifts the edit control's default 32K character limit.
+const EM_SETLIMITTEXT: u32 = 0x00C5;
+const BUTTON_WIDTH: i32 = 90;
+const BUTTON_HEIGHT: i32 = 28;
+const MARGIN: i32 = 8;
+
+thread_local! {
+    // Whether Paste was chosen, for the window procedure to set.
+    static CONFIRMED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Shows `modified` and its changes from `original`, and waits until the user picks Paste
+/// (or Enter) or Cancel (or Esc, or closing the window). Focus then goes back to the window
+/// that had it, so the paste lands there.
+pub fn confirm(original: &str, modified: &str) -> Result<bool> {
+    let changes = diff::render(&diff::words(original, modified), diff::Style::Plain);
+    // Edit controls only break lines at CRLF.
+    let crlf = |text: &str| text.replace("\r\n", "\n").replace('\n', "\r\n");
+    let text = format!(
+        "{}\r\n\r\n---------- Changes ----------\r\n{}",
+        crlf(modified),
+        crlf(&changes)
+    );
+
+    let previous = unsafe { GetForegroundWindow() };
+    CONFIRMED.with(|confirmed| confirmed.set(false));
+    let font = unsafe {
+        CreateFontW(
+            -15,
+            0,
+            0,
+            0,
+            FW_NORMAL as i32,
+            0,
+            0,
+            0,
+            DEFAULT_CHARSET as u32,
+            OUT_DEFAULT_PRECIS as u32,
+            CLIP_DEFAULT_PRECIS as u32,
+            CLEARTYPE_QUALITY as u32,
+            FIXED_PITCH as u32,
+            wide("Consolas").as_ptr(),
+        )
+    };
+    let shown = create_window(&text, font);
+    let result = shown.map(|window| {
+        unsafe {
+            ShowWindow(window, SW_SHOW);
+            SetForegroundWindow(window);
+        }
+        let mut msg: MSG = unsafe { std::mem::zeroed() };
+        while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+            // Gives Enter, Esc and Tab their dialog meanings.
+            if unsafe { IsDialogMessageW(window, &msg) } == 0 {
+                unsafe {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+        }
+        CONFIRMED.with(Cell::get)
+    });
+    unsafe {
+        DeleteObject(font);
+        if !previous.is_null() {
+            SetForegroundWindow(previous);
+        }
+    }
+    result
+}
+
+fn create_window(text: &str, font: HFONT) -> Result<HWND> {
+    static REGISTER: Once = Once::new();
+    let class_name = wide(CLASS_NAME);
+    unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        REGISTER.call_once(|| {
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance,
+                lpszClassName: class_name.as_ptr(),
+                hbrBackground: (COLOR_BTNFACE + 1) as usize as HBRUSH,
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+        });
+
+        let window = CreateWindowExW(
+            WS_EX_TOPMOST,
+            class_name.as_ptr(),
+            wide("flatten-string: Paste this?").as_ptr(),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            720,
+            480,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            instance,
+            ptr::null(),
+        );
+        if window.is_null() {
+            return Err(io::Error::last_os_error()).context("Failed to create the preview");
+        }
+        let child = |class: &str, text: &str, style: u32, ex_style: u32, id: i32| {
+            CreateWindowExW(
+                ex_style,
+                wide(class).as_ptr(),
+                wide(text).as_ptr(),
+                WS_CHILD | WS_VISIBLE | WS_TABSTOP | style,
+                0,
+                0,
+                0,
+                0,
+                window,
+                id as usize as _,
+                instance,
+                ptr::null(),
+            )
+        };
+        let edit = child(
+            "EDIT",
+            "",
+            (ES_MULTILINE | ES_READONLY | ES_AUTOVSCROLL | ES_AUTOHSCROLL) as u32
+                | WS_VSCROLL
+                | WS_HSCROLL,
+            WS_EX_CLIENTEDGE,
+            TEXT_ID,
+        );
+        child("BUTTON", "Paste", BS_DEFPUSHBUTTON as u32, 0, IDOK);
+        child("BUTTON", "Cancel", BS_PUSHBUTTON as u32, 0, IDCANCEL);
+        SendMessageW(edit, WM_SETFONT, font as usize, 1);
+        SendMessageW(edit, EM_SETLIMITTEXT, 0, 0);
+        SetWindowTextW(edit, wide(text).as_ptr());
+        layout(window);
+        Ok(window)
+    }
+}
+
+// The text fills the window above a row of buttons at the bottom right.
+unsafe fn layout(window: HWND) {
+    let mut area = RECT::default();
+    GetClientRect(window, &mut area);
+    let buttons_top = area.bottom - MARGIN - BUTTON_HEIGHT;
+    MoveWindow(
+        GetDlgItem(window, TEXT_ID),
+        MARGIN,
+        MARGIN,
+        area.right - 2 * MARGIN,
+        buttons_top - 2 * MARGIN,
+        1,
+    );
+    let mut left = area.right - MARGIN - BUTTON_WIDTH;
+    for id in [IDCANCEL, IDOK] {
+        MoveWindow(
+            GetDlgItem(window, id),
+            left,
+            buttons_top,
+            BUTTON_WIDTH,
+            BUTTON_HEIGHT,
+            1,
+        );
+        left -= MARGIN + BUTTON_WIDTH;
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_COMMAND => {
+            match (wparam & 0xFFFF) as i32 {
+                IDOK => {
+                    CONFIRMED.with(|confirmed| confirmed.set(true));
+                    DestroyWindow(window);
+                }
+                IDCANCEL => {
+                    DestroyWindow(window);
+                }
+                _ => (),
+            }
+            0
+        }
+        WM_SIZE => {
+            layout(window);
+            0
+        }
+        WM_DESTROY => {
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(window, message, wparam, lparam),
+    }
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
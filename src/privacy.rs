@@ -0,0 +1,196 @@
+//! Keeps copied text out of the console and the logs, which tend to outlive it and get
+//! pasted into bug reports.
+//!
+//! With privacy on (the default) text is shown as its length and a hash, which is still
+//! enough to tell whether two activations saw the same text. `--show-content` turns it off,
+//! and text that looks like a credential is still redacted then, as it is in the saved
+//! history.
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+static PRIVATE: AtomicBool = AtomicBool::new(true);
+
+static REDACTOR: RwLock<Option<Arc<Redactor>>> = RwLock::new(None);
+
+/// Sets whether text is hidden, and what's redacted when it isn't, from now on.
+pub fn set(private: bool, redactor: Redactor) {
+    PRIVATE.store(private, Ordering::Relaxed);
+    *REDACTOR
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(redactor));
+}
+
+/// Whether copied text, keystrokes and other input may be shown.
+pub fn shows_content() -> bool {
+    !PRIVATE.load(Ordering::Relaxed)
+}
+
+/// `text` with every credential the configured detectors find replaced by a marker.
+pub fn redact(text: &str) -> String {
+    let redactor = REDACTOR
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    match redactor {
+        Some(redactor) => redactor.redact(text).into_owned(),
+        None => text.to_string(),
+    }
+}
+
+/// Kinds of credential recognized by their shape.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Secret {
+    /// PEM private key blocks
+    PrivateKey,
+    /// JSON Web Tokens
+    Jwt,
+    /// Keys with a well-known prefix: GitHub, GitLab, Slack, Stripe, AWS, Google, OpenAI
+    ApiKey,
+    /// The token after "Bearer" or "Basic", as in an Authorization header
+    Bearer,
+    /// The value of password=, secret=, api_key= or token= and the like
+    Password,
+}
+
+impl Secret {
+    pub const ALL: [Secret; 5] = [
+        Secret::PrivateKey,
+        Secret::Jwt,
+        Secret::ApiKey,
+        Secret::Bearer,
+        Secret::Password,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Secret::PrivateKey => "private-key",
+            Secret::Jwt => "jwt",
+            Secret::ApiKey => "api-key",
+            Secret::Bearer => "bearer",
+            Secret::Password => "password",
+        }
+    }
+
+    // Only the `secret` group is redacted when there is one, so the label stays readable.
+    fn pattern(self) -> &'static str {
+        match self {
+            Secret::PrivateKey => {
+                r"(?s)-----BEGIN [A-Z ]*PRIVATE KEY-----.*?-----END [A-Z ]*PRIVATE KEY-----"
+            }
+            Secret::Jwt => r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+            Secret::ApiKey => concat!(
+                r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,}",
+                r"|glpat-[A-Za-z0-9_-]{20,}|xox[abposr]-[A-Za-z0-9-]{10,}",
+                r"|[sprk]k_(?:live|test)_[A-Za-z0-9]{16,}|AKIA[0-9A-Z]{16}",
+                r"|AIza[0-9A-Za-z_-]{35}|sk-[A-Za-z0-9_-]{20,})"
+            ),
+            Secret::Bearer => r"(?i)\b(?:bearer|basic)\s+(?P<secret>[A-Za-z0-9._~+/-]{16,}=*)",
+            Secret::Password => concat!(
+                r#"(?i)\b(?:password|passwd|pwd|secret|api[_-]?key|access[_-]?token|token)"#,
+                r#"["']?\s*[:=]\s*["']?(?P<secret>[^\s"',;]+)"#
+            ),
+        }
+    }
+}
+
+/// Replaces credentials with `[REDACTED <kind>]`.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    detectors: Vec<(String, Regex)>,
+}
+
+impl Redactor {
+    /// The built-in `secrets`, then `custom` (name, pattern) detectors from the config.
+    pub fn new(secrets: &[Secret], custom: Vec<(String, Regex)>) -> Self {
+        let builtin = Secret::ALL
+            .into_iter()
+            .filter(|secret| secrets.contains(secret))
+            .map(|secret| {
+                let regex = Regex::new(secret.pattern()).expect("built-in pattern is valid");
+                (secret.name().to_string(), regex)
+            });
+        Redactor {
+            detectors: builtin.chain(custom).collect(),
+        }
+    }
+
+    pub fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let mut text = Cow::Borrowed(text);
+        for (name, regex) in &self.detectors {
+            let marker = format!("[REDACTED {}]", name);
+            let replace = |caps: &Captures| {
+                let all = caps.get(0).expect("group 0 always matches");
+                match caps.name("secret") {
+                    Some(secret) => format!(
+                        "{}{}{}",
+                        &all.as_str()[..secret.start() - all.start()],
+                        marker,
+                        &all.as_str()[secret.end() - all.start()..]
+                    ),
+                    None => marker.clone(),
+                }
+            };
+            if let Cow::Owned(redacted) = regex.replace_all(&text, replace) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        text
+    }
+}
+
+/// How much of the text an excerpt shows with privacy off.
+const EXCERPT_CHARS: usize = 100;
+
+/// Shows the start of the text, redacted, or its length and hash with privacy on.
+pub struct Excerpt<'a> {
+    text: &'a str,
+    concealed: bool,
+}
+
+impl<'a> Excerpt<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Excerpt {
+            text,
+            concealed: false,
+        }
+    }
+
+    /// Shows only the length and hash if `concealed`, i.e. the app that copied the text
+    /// marked it as a secret, even with privacy off.
+    pub fn concealed(self, concealed: bool) -> Self {
+        Excerpt { concealed, ..self }
+    }
+}
+
+impl fmt::Display for Excerpt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.concealed || !shows_content() {
+            return write!(
+                f,
+                "<{} chars, hash {:016x}>",
+                self.text.chars().count(),
+                fnv1a(self.text.as_bytes())
+            );
+        }
+        let redacted = redact(self.text);
+        let excerpt: String = redacted.chars().take(EXCERPT_CHARS).collect();
+        write!(f, "{}", excerpt)?;
+        if redacted.chars().count() > EXCERPT_CHARS {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+// FNV-1a, which unlike `DefaultHasher` gives the same hash in every build and run.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,93 @@
+//! Hotkey backend built on the Win32 `RegisterHotKey` API.
+//!
+//! Unlike rdev's low-level hook this needs no elevated permissions, but it can only bind
+//! key chords (no mouse buttons) and each chord must not already be registered by another
+//! program. Registered hotkeys are always swallowed.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::io;
+use std::ptr;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT,
+    MOD_SHIFT, MOD_WIN,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY};
+
+use crate::config::HotkeyBinding;
+use crate::hotkey::{Modifiers, Trigger};
+use crate::vk::virtual_key;
+
+/// Registers every binding and calls `on_hotkey` with the binding's index each time one fires.
+///
+/// Blocks the thread running its message loop.
+pub fn listen(bindings: &[HotkeyBinding], mut on_hotkey: impl FnMut(usize)) -> Result<()> {
+    let registered = Registered::new(bindings)?;
+
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+    loop {
+        match unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } {
+            -1 => return Err(io::Error::last_os_error()).context("Message loop failed"),
+            0 => break, // WM_QUIT
+            _ if msg.message == WM_HOTKEY => on_hotkey(msg.wParam),
+            _ => (),
+        }
+    }
+
+    drop(registered);
+    Ok(())
+}
+
+/// Unregisters the hotkeys again when dropped, including on early errors.
+struct Registered(usize);
+
+impl Registered {
+    fn new(bindings: &[HotkeyBinding]) -> Result<Self> {
+        let mut registered = Registered(0);
+        for (id, binding) in bindings.iter().enumerate() {
+            let Trigger::Key(key) = binding.key.trigger else {
+                bail!(
+                    "{} uses a mouse button, which the register-hotkey backend can't bind",
+                    binding.key
+                );
+            };
+            let vk = virtual_key(key.into())
+                .ok_or_else(|| anyhow!("{} has no virtual-key code", binding.key))?;
+            let flags = modifier_flags(binding.key.modifiers) | MOD_NOREPEAT;
+            if unsafe { RegisterHotKey(ptr::null_mut(), id as i32, flags, vk.into()) } == 0 {
+                return Err(io::Error::last_os_error()).with_context(|| {
+                    format!(
+                        "Failed to register {} (is another program already using it?)",
+                        binding.key
+                    )
+                });
+            }
+            registered.0 += 1;
+        }
+        Ok(registered)
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        for id in 0..self.0 {
+            unsafe { UnregisterHotKey(ptr::null_mut(), id as i32) };
+        }
+    }
+}
+
+fn modifier_flags(modifiers: Modifiers) -> HOT_KEY_MODIFIERS {
+    let mut flags = 0;
+    if modifiers.ctrl {
+        flags |= MOD_CONTROL;
+    }
+    if modifiers.shift {
+        flags |= MOD_SHIFT;
+    }
+    if modifiers.alt {
+        flags |= MOD_ALT;
+    }
+    if modifiers.meta {
+        flags |= MOD_WIN;
+    }
+    flags
+}
@@ -0,0 +1,55 @@
+//! Watches the config file so edits apply without restarting the listener.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::alerts;
+use crate::config::Config;
+
+/// How often the config file's modification time is checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Editors often save by truncating and rewriting, so give the write a moment to finish.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Sends each new version of the config file at `path` that parses. Versions that don't
+/// parse are reported and skipped, so the running config stays in place until it's fixed.
+pub fn watch(path: PathBuf) -> Receiver<Config> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            if current.is_none() {
+                println!(
+                    "Config file {} disappeared; keeping the current config.",
+                    path.display()
+                );
+                continue;
+            }
+
+            thread::sleep(SETTLE_DELAY);
+            match Config::load(&path) {
+                Ok(config) => {
+                    if tx.send(config).is_err() {
+                        return; // The listener has stopped
+                    }
+                }
+                Err(e) => alerts::error(&e.context("Not reloading config")),
+            }
+        }
+    });
+    rx
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,156 @@
+//! Rotation for the files that grow while listening (`--log-file`, `--event-log` and the
+//! `--detach` listener.log), so a listener left running for months doesn't slowly fill the
+//! disk.
+//!
+//! A file that's too big or too old is renamed to `<name>.1`, the one before it to
+//! `<name>.2` and so on, and writing starts over in a new file. Only `--log-keep` old files
+//! are kept.
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// When to rotate and how many old files to keep.
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    /// Rotate once a file would grow past this many bytes.
+    pub max_size: Option<u64>,
+    /// Rotate once a file was started this long ago.
+    pub max_age: Option<Duration>,
+    pub keep: usize,
+}
+
+impl Retention {
+    // Whether a file of `size` bytes started at `created` must be rotated before `incoming`
+    // more bytes are written to it. An empty file is never rotated.
+    fn due(&self, size: u64, created: Option<SystemTime>, incoming: u64) -> bool {
+        if size == 0 {
+            return false;
+        }
+        let too_big = self.max_size.is_some_and(|max| size + incoming > max);
+        let too_old = match (self.max_age, created) {
+            (Some(max), Some(created)) => created.elapsed().is_ok_and(|age| age > max),
+            _ => false,
+        };
+        too_big || too_old
+    }
+
+    /// Rotates the file at `path` if it's due, before writing to it. A missing file is fine.
+    pub fn check(&self, path: &Path) -> Result<()> {
+        let Ok(metadata) = fs::metadata(path) else {
+            return Ok(());
+        };
+        if self.due(metadata.len(), metadata.created().ok(), 0) {
+            self.rotate(path)?;
+            // Started now, so it's dated from now.
+            open_append(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    // Shifts `path` to `<path>.1`, each older file one further, and deletes the ones past
+    // `keep`.
+    fn rotate(&self, path: &Path) -> Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(path)
+                .with_context(|| format!("Failed to delete {}", path.display()));
+        }
+        let _ = fs::remove_file(numbered(path, self.keep));
+        for n in (1..self.keep).rev() {
+            let older = numbered(path, n);
+            if older.exists() {
+                fs::rename(&older, numbered(path, n + 1))
+                    .with_context(|| format!("Failed to rotate {}", older.display()))?;
+            }
+        }
+        fs::rename(path, numbered(path, 1))
+            .with_context(|| format!("Failed to rotate {}", path.display()))
+    }
+}
+
+// `path` with `.n` appended, e.g. listener.log.2.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// A file appended to for as long as the program runs, rotated as it goes: the sink of
+/// `--log-file`.
+pub struct RotatingFile {
+    path: PathBuf,
+    retention: Retention,
+    file: File,
+    size: u64,
+    created: Option<SystemTime>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, retention: Retention) -> Result<Self> {
+        retention.check(path)?;
+        let (file, size, created) = open_append(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            retention,
+            file,
+            size,
+            created,
+        })
+    }
+
+    fn start_over(&mut self) -> Result<()> {
+        self.retention.rotate(&self.path)?;
+        let (file, size, created) = open_append(&self.path)?;
+        (self.file, self.size, self.created) = (file, size, created);
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self
+            .retention
+            .due(self.size, self.created, buf.len() as u64)
+        {
+            if let Err(e) = self.start_over() {
+                // E.g. another process has the file open on Windows. Keep writing to it,
+                // without trying again until it has grown as much again.
+                //
+                // Straight to stderr rather than through `log`: this is the logger's own
+                // file, and logging from inside its write would lock it again. `check`, used
+                // for every other file, returns the error to its caller instead.
+                eprintln!("WARNING: {:#}", e);
+                self.size = 0;
+                self.created = Some(SystemTime::now());
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Opens `path` to append to, with its size and when it was started.
+fn open_append(path: &Path) -> io::Result<(File, u64, Option<SystemTime>)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    if metadata.len() == 0 {
+        // Windows gives a file created where another was just renamed from that file's
+        // creation time, which would make it look just as old.
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::FileTimesExt;
+            let _ = file.set_times(fs::FileTimes::new().set_created(SystemTime::now()));
+        }
+        return Ok((file, 0, Some(SystemTime::now())));
+    }
+    Ok((file, metadata.len(), metadata.created().ok()))
+}
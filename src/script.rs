@@ -0,0 +1,353 @@
+//! `[[script]]` steps: small transforms written inline in the config file, for conditional
+//! replacements and joining rules that `[[rule]]`s alone can't express.
+//!
+//! A script is one statement per line, run in order over the text. `#` starts a comment.
+//!
+//! ```text
+//! replace /pattern/flags "replacement"   $1 and ${name} refer to capture groups
+//! delete /pattern/flags                  the same as replacing with ""
+//! trim, upper, lower
+//! prepend "text", append "text"
+//! join "separator"                       every line into one
+//! lines <statement>                      runs the statement on each line on its own
+//! drop                                   after `lines`: removes the line
+//! if /pattern/ <statement>               only when the text (or line) matches
+//! unless /pattern/ <statement>           only when it doesn't
+//! ```
+//!
+//! Flags are those of `[[rule]]`: `i`, `m`, `s`, `x` and `U`. Inside a pattern, `\/` stands
+//! for a slash. Strings take `\"`, `\\`, `\n`, `\t` and `\r`.
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+
+use crate::config::{RegexRule, ScriptStep};
+use strflatten::transform::{ReplaceRule, Transform, TransformStep};
+
+/// A compiled `[[script]]`.
+pub struct Script {
+    name: String,
+    statements: Vec<Statement>,
+}
+
+struct Statement {
+    /// Run on each line instead of the whole text.
+    per_line: bool,
+    /// Patterns that must match (`true`) or must not (`false`) for the action to run.
+    guards: Vec<(bool, Regex)>,
+    action: Action,
+}
+
+enum Action {
+    Replace(ReplaceRule),
+    Trim,
+    Upper,
+    Lower,
+    Prepend(String),
+    Append(String),
+    Join(String),
+    Drop,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Pattern { pattern: String, flags: String },
+    Text(String),
+}
+
+impl Script {
+    pub fn new(step: &ScriptStep) -> Result<Self> {
+        if TransformStep::from_name(&step.name).is_builtin() {
+            return Err(anyhow!(
+                "[[script]] {:?} is named like a built-in step",
+                step.name
+            ));
+        }
+        let mut statements = Vec::new();
+        for (i, line) in step.code.lines().enumerate() {
+            let statement = tokenize(line)
+                .and_then(|tokens| parse(&tokens))
+                .with_context(|| format!("[[script]] {:?} line {}", step.name, i + 1))?;
+            statements.extend(statement);
+        }
+        Ok(Script {
+            name: step.name.clone(),
+            statements,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Transform for Script {
+    fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for statement in &self.statements {
+            text = if statement.per_line {
+                let mut lines = Vec::new();
+                for line in text.split('\n') {
+                    // A Windows line ending stays out of reach of the statement.
+                    let (line, cr) = match line.strip_suffix('\r') {
+                        Some(line) => (line, "\r"),
+                        None => (line, ""),
+                    };
+                    if let Some(line) = statement.run(line) {
+                        lines.push(line + cr);
+                    }
+                }
+                lines.join("\n")
+            } else {
+                statement.run(&text).unwrap_or_default()
+            };
+        }
+        text
+    }
+}
+
+impl Statement {
+    /// The statement's result for `text`, or `None` for a dropped line.
+    fn run(&self, text: &str) -> Option<String> {
+        let applies = self
+            .guards
+            .iter()
+            .all(|(wanted, regex)| regex.is_match(text) == *wanted);
+        if !applies {
+            return Some(text.to_string());
+        }
+        Some(match &self.action {
+            Action::Replace(rule) => rule
+                .regex
+                .replace_all(text, rule.replacement.as_str())
+                .into_owned(),
+            Action::Trim => text.trim().to_string(),
+            Action::Upper => text.to_uppercase(),
+            Action::Lower => text.to_lowercase(),
+            Action::Prepend(prefix) => format!("{}{}", prefix, text),
+            Action::Append(suffix) => format!("{}{}", text, suffix),
+            Action::Join(separator) => text
+                .split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                .collect::<Vec<_>>()
+                .join(separator),
+            Action::Drop => return None,
+        })
+    }
+}
+
+/// Parses one line's tokens into a statement, or `None` for a blank or comment line.
+fn parse(tokens: &[Token]) -> Result<Option<Statement>> {
+    let mut tokens = tokens.iter().peekable();
+    if tokens.peek().is_none() {
+        return Ok(None);
+    }
+    let per_line = tokens.next_if(|token| is_word(token, "lines")).is_some();
+    let mut guards = Vec::new();
+    while let Some(Token::Word(word)) = tokens.peek() {
+        let wanted = match word.as_str() {
+            "if" => true,
+            "unless" => false,
+            _ => break,
+        };
+        tokens.next();
+        let rule = pattern(tokens.next(), word, "")?;
+        guards.push((wanted, rule.regex));
+    }
+
+    let Some(Token::Word(word)) = tokens.next() else {
+        return Err(anyhow!("Expected an action such as replace, trim or join"));
+    };
+    let action = match word.as_str() {
+        "replace" => {
+            let (found, replacement) = (tokens.next(), text(tokens.next(), word)?);
+            Action::Replace(pattern(found, word, &replacement)?)
+        }
+        "delete" => Action::Replace(pattern(tokens.next(), word, "")?),
+        "trim" => Action::Trim,
+        "upper" => Action::Upper,
+        "lower" => Action::Lower,
+        "prepend" => Action::Prepend(text(tokens.next(), word)?),
+        "append" => Action::Append(text(tokens.next(), word)?),
+        "join" if per_line => return Err(anyhow!("join can't run on each line")),
+        "join" => Action::Join(text(tokens.next(), word)?),
+        "drop" if !per_line => return Err(anyhow!("drop only works after lines")),
+        "drop" => Action::Drop,
+        other => return Err(anyhow!("Unknown action {:?}", other)),
+    };
+    if let Some(extra) = tokens.next() {
+        return Err(anyhow!("Unexpected {:?} after {}", extra, word));
+    }
+    Ok(Some(Statement {
+        per_line,
+        guards,
+        action,
+    }))
+}
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w == word)
+}
+
+fn pattern(token: Option<&Token>, after: &str, replacement: &str) -> Result<ReplaceRule> {
+    let Some(Token::Pattern { pattern, flags }) = token else {
+        return Err(anyhow!("Expected a /pattern/ after {}", after));
+    };
+    let rule = RegexRule {
+        pattern: pattern.clone(),
+        replacement: replacement.to_string(),
+        flags: flags.clone(),
+    };
+    rule.compile()
+        .with_context(|| format!("Invalid pattern /{}/", pattern))
+}
+
+fn text(token: Option<&Token>, after: &str) -> Result<String> {
+    match token {
+        Some(Token::Text(text)) => Ok(text.clone()),
+        _ => Err(anyhow!("Expected a \"string\" after {}", after)),
+    }
+}
+
+/// Splits a line into words, `/patterns/flags` and `"strings"`, up to any `#` comment.
+fn tokenize(line: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => break,
+            '/' => {
+                chars.next();
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some('/') => break,
+                        Some('\\') if chars.peek() == Some(&'/') => {
+                            pattern.push('/');
+                            chars.next();
+                        }
+                        Some(c) => pattern.push(c),
+                        None => return Err(anyhow!("Unterminated /pattern/")),
+                    }
+                }
+                let mut flags = String::new();
+                while let Some(flag) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                    flags.push(flag);
+                }
+                tokens.push(Token::Pattern { pattern, flags });
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.push(match chars.next() {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some(c @ ('"' | '\\')) => c,
+                            Some(c) => return Err(anyhow!("Unknown escape \\{}", c)),
+                            None => return Err(anyhow!("Unterminated \"string\"")),
+                        }),
+                        Some(c) => text.push(c),
+                        None => return Err(anyhow!("Unterminated \"string\"")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '-') {
+                    word.push(c);
+                }
+                if word.is_empty() {
+                    return Err(anyhow!("Unexpected {:?}", c));
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(code: &str) -> Script {
+        Script::new(&ScriptStep {
+            name: "test".to_string(),
+            code: code.to_string(),
+        })
+        .unwrap()
+    }
+
+    fn error(code: &str) -> String {
+        let step = ScriptStep {
+            name: "test".to_string(),
+            code: code.to_string(),
+        };
+        format!("{:#}", Script::new(&step).err().unwrap())
+    }
+
+    #[test]
+    fn replaces_with_groups() {
+        let tidy = script(r#"replace /\s+([,.])/ "$1""#);
+        assert_eq!(tidy.apply("a , b ."), "a, b.");
+    }
+
+    #[test]
+    fn runs_statements_in_order_and_skips_comments() {
+        let code = "# Shout it\ntrim\nupper # loudly\nappend \"!\"";
+        assert_eq!(script(code).apply("  hi "), "HI!");
+    }
+
+    #[test]
+    fn guards_decide_whether_it_runs() {
+        let only_code = script(r#"if /^\s*fn / replace /\s+/ " ""#);
+        assert_eq!(only_code.apply("fn  main()"), "fn main()");
+        assert_eq!(only_code.apply("plain  text"), "plain  text");
+        let unless = script("unless /keep/i lower");
+        assert_eq!(unless.apply("KEEP Me"), "KEEP Me");
+        assert_eq!(unless.apply("Lower Me"), "lower me");
+    }
+
+    #[test]
+    fn lines_runs_on_each_line() {
+        let unquote = script("lines if /^>/ delete /^>\\s?/\nlines if /^\\s*$/ drop\njoin \" \"");
+        assert_eq!(unquote.apply("> one\r\n\r\n> two\nthree"), "one two three");
+    }
+
+    #[test]
+    fn escapes_in_patterns_and_strings() {
+        let paths = script(r#"replace /\// "\\""#);
+        assert_eq!(paths.apply("a/b/c"), r"a\b\c");
+        let quoted = script(r#"prepend "\"\t""#);
+        assert_eq!(quoted.apply("x"), "\"\tx");
+    }
+
+    #[test]
+    fn reports_mistakes_with_their_line() {
+        assert!(error("trim\nreplace \"a\" \"b\"").contains("line 2: Expected a /pattern/"));
+        assert!(error("shout").contains("Unknown action \"shout\""));
+        assert!(error("lines join \" \"").contains("join can't run on each line"));
+        assert!(error("drop").contains("drop only works after lines"));
+        assert!(error("upper \"x\"").contains("Unexpected"));
+        assert!(error("delete /[/").contains("Invalid pattern"));
+        assert!(error("append \"x").contains("Unterminated"));
+    }
+
+    #[test]
+    fn rejects_built_in_names() {
+        let step = ScriptStep {
+            name: "flatten".to_string(),
+            code: "trim".to_string(),
+        };
+        assert!(Script::new(&step).is_err());
+    }
+}
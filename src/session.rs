@@ -0,0 +1,115 @@
+//! Keeps the listener right across session changes: locking and unlocking the workstation,
+//! and connecting to the session again, locally or over Remote Desktop (Windows only).
+//!
+//! The hook sees nothing while the secure desktop or another session has the keyboard, so
+//! it misses the releases of keys held when the session went away (Win from Win+L, say)
+//! and would otherwise think they're still down.
+
+use anyhow::{anyhow, Context, Result};
+use std::cell::RefCell;
+use std::io;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW,
+    TranslateMessage, MSG, WM_WTSSESSION_CHANGE, WNDCLASSW, WTS_CONSOLE_CONNECT,
+    WTS_REMOTE_CONNECT, WTS_SESSION_LOGON, WTS_SESSION_UNLOCK,
+};
+
+use crate::listener::Listener;
+use crate::lock;
+
+thread_local! {
+    // The window procedure can't be handed anything of ours, so it finds the listener here.
+    static LISTENER: RefCell<Option<Arc<Mutex<Listener>>>> = const { RefCell::new(None) };
+}
+
+/// Watches for session changes on a thread of its own until the program exits.
+pub fn spawn(listener: Arc<Mutex<Listener>>) {
+    thread::Builder::new()
+        .name("session".to_string())
+        .spawn(move || {
+            if let Err(e) = run(listener) {
+                log::warn!("Not watching for session changes: {:?}", e);
+            }
+        })
+        .expect("failed to spawn the session thread");
+}
+
+fn run(listener: Arc<Mutex<Listener>>) -> Result<()> {
+    LISTENER.with(|cell| *cell.borrow_mut() = Some(listener));
+    let class_name: Vec<u16> = "flatten-string-session"
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    let window = unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: class_name.as_ptr(),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err(io::Error::last_os_error()).context("Failed to register the window");
+        }
+        CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            instance,
+            ptr::null(),
+        )
+    };
+    if window.is_null() {
+        return Err(io::Error::last_os_error()).context("Failed to create the window");
+    }
+    if unsafe { WTSRegisterSessionNotification(window, NOTIFY_FOR_THIS_SESSION) } == 0 {
+        return Err(anyhow!(io::Error::last_os_error()));
+    }
+
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+    while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+        unsafe {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == WM_WTSSESSION_CHANGE
+        && matches!(
+            wparam as u32,
+            WTS_SESSION_UNLOCK | WTS_SESSION_LOGON | WTS_CONSOLE_CONNECT | WTS_REMOTE_CONNECT
+        )
+    {
+        LISTENER.with(|cell| {
+            if let Some(listener) = cell.borrow().as_ref() {
+                lock(listener).forget_held_keys();
+            }
+        });
+        println!("Session is back. Forgot the keys held before it went away.");
+        return 0;
+    }
+    DefWindowProcW(window, message, wparam, lparam)
+}
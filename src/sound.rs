@@ -0,0 +1,252 @@
+//! Short sound cues for activation outcomes, from WAV files or built in.
+//!
+//! Windows plays them with PlaySound, Linux pipes them to `aplay` (alsa-utils) and
+//! macOS hands them to `afplay`. The volume is applied to the samples themselves,
+//! so only uncompressed 8- or 16-bit PCM files are accepted.
+
+use anyhow::{anyhow, Context, Result};
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use crate::activity::Kind;
+
+const SAMPLE_RATE: u32 = 22_050;
+
+/// A cue ready to play: a whole WAV file with the volume already applied.
+#[derive(Clone)]
+pub struct Sound(Arc<Vec<u8>>);
+
+impl Sound {
+    /// Reads the WAV file at `path`, scaled to `volume` percent.
+    pub fn load(path: &Path, volume: u8) -> Result<Self> {
+        let mut wav =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        scale(&mut wav, volume).with_context(|| format!("Can't play {}", path.display()))?;
+        Ok(Sound(Arc::new(wav)))
+    }
+
+    /// The built-in cue for `kind`: a click for success, a low tone for a skip and two
+    /// falling tones for a failure.
+    pub fn builtin(kind: Kind, volume: u8) -> Self {
+        // (frequency in Hz, length in ms), one after the other.
+        let tones: &[(f32, u32)] = match kind {
+            Kind::Success => &[(2000.0, 15)],
+            Kind::Skipped => &[(330.0, 120)],
+            Kind::Failure => &[(440.0, 120), (294.0, 180)],
+        };
+        let mut samples = Vec::new();
+        for &(frequency, ms) in tones {
+            let count = SAMPLE_RATE * ms / 1000;
+            for i in 0..count {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                // Fades out over the tone so it ends without a pop.
+                let envelope = 1.0 - i as f32 / count as f32;
+                samples.push((TAU * frequency * t).sin() * envelope * 0.5);
+            }
+        }
+        let mut wav = encode(&samples);
+        scale(&mut wav, volume).expect("built-in cues are 16-bit PCM");
+        Sound(Arc::new(wav))
+    }
+
+    /// Starts playing the cue and returns without waiting for it to finish.
+    pub fn play(&self) {
+        let wav = Arc::clone(&self.0);
+        let spawned = thread::Builder::new()
+            .name("sound".to_string())
+            .spawn(move || {
+                if let Err(e) = play(&wav) {
+                    log::warn!("Failed to play a sound: {:#}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to play a sound: {}", e);
+        }
+    }
+}
+
+/// The cue for each outcome that has one.
+#[derive(Clone, Default)]
+pub struct Cues {
+    success: Option<Sound>,
+    skipped: Option<Sound>,
+    failure: Option<Sound>,
+}
+
+impl Cues {
+    /// Cues for the outcomes in `kinds`, from the file given for each or built in.
+    pub fn new(kinds: &[Kind], files: [Option<&Path>; 3], volume: u8) -> Result<Self> {
+        let [success, skipped, failure] = files;
+        let cue = |kind, file: Option<&Path>| -> Result<Option<Sound>> {
+            if !kinds.contains(&kind) {
+                return Ok(None);
+            }
+            match file {
+                Some(path) => Sound::load(path, volume).map(Some),
+                None => Ok(Some(Sound::builtin(kind, volume))),
+            }
+        };
+        Ok(Cues {
+            success: cue(Kind::Success, success)?,
+            skipped: cue(Kind::Skipped, skipped)?,
+            failure: cue(Kind::Failure, failure)?,
+        })
+    }
+
+    /// Plays the cue for `kind`, if it has one.
+    pub fn play(&self, kind: Kind) {
+        let cue = match kind {
+            Kind::Success => &self.success,
+            Kind::Skipped => &self.skipped,
+            Kind::Failure => &self.failure,
+        };
+        if let Some(sound) = cue {
+            sound.play();
+        }
+    }
+}
+
+// A mono 16-bit PCM WAV file holding `samples`, each between -1 and 1.
+fn encode(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // Bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // Bytes per frame
+    wav.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+// Multiplies the samples of the PCM WAV file in `wav` by `volume` percent.
+fn scale(wav: &mut [u8], volume: u8) -> Result<()> {
+    if wav.len() < 12 || &wav[..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return Err(anyhow!("Not a WAV file"));
+    }
+    let factor = f32::from(volume.min(100)) / 100.0;
+    let mut bits = None;
+    let mut at = 12;
+    while at + 8 <= wav.len() {
+        let id = &wav[at..at + 4];
+        let len = u32::from_le_bytes(wav[at + 4..at + 8].try_into().unwrap()) as usize;
+        let body = at + 8..(at + 8 + len).min(wav.len());
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let format = u16::from_le_bytes([wav[body.start], wav[body.start + 1]]);
+                let bits_per_sample =
+                    u16::from_le_bytes([wav[body.start + 14], wav[body.start + 15]]);
+                if format != 1 || !matches!(bits_per_sample, 8 | 16) {
+                    return Err(anyhow!(
+                        "Only uncompressed 8- or 16-bit PCM WAV files are supported"
+                    ));
+                }
+                bits = Some(bits_per_sample);
+            }
+            b"data" => {
+                let data = &mut wav[body];
+                match bits {
+                    // 8-bit samples are unsigned, centred on 128.
+                    Some(8) => {
+                        for sample in data.iter_mut() {
+                            *sample = ((f32::from(*sample) - 128.0) * factor + 128.0) as u8;
+                        }
+                    }
+                    Some(_) => {
+                        for pair in data.chunks_exact_mut(2) {
+                            let sample = i16::from_le_bytes([pair[0], pair[1]]);
+                            let scaled = (f32::from(sample) * factor) as i16;
+                            pair.copy_from_slice(&scaled.to_le_bytes());
+                        }
+                    }
+                    None => return Err(anyhow!("The data comes before the format")),
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even length.
+        at += 8 + len + len % 2;
+    }
+    Err(anyhow!("No audio data"))
+}
+
+#[cfg(windows)]
+fn play(wav: &[u8]) -> Result<()> {
+    use windows_sys::Win32::Media::Audio::{PlaySoundW, SND_MEMORY, SND_NODEFAULT, SND_SYNC};
+
+    // Synchronous, so `wav` outlives the playback.
+    let played = unsafe {
+        PlaySoundW(
+            wav.as_ptr().cast(),
+            std::ptr::null_mut(),
+            SND_MEMORY | SND_NODEFAULT | SND_SYNC,
+        )
+    };
+    if played == 0 {
+        return Err(anyhow!("PlaySound failed"));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn play(wav: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut aplay = Command::new("aplay")
+        .args(["-q", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run aplay; is alsa-utils installed?")?;
+    aplay
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(wav)
+        .context("Failed to send the sound to aplay")?;
+    let status = aplay.wait().context("Failed to wait for aplay")?;
+    if !status.success() {
+        return Err(anyhow!("aplay exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn play(wav: &[u8]) -> Result<()> {
+    use std::process::Command;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // afplay only reads files. Each cue gets its own, in case they overlap.
+    static PLAYED: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "flatten-string-{}-{}.wav",
+        std::process::id(),
+        PLAYED.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&path, wav).with_context(|| format!("Failed to write {}", path.display()))?;
+    let status = Command::new("afplay")
+        .arg(&path)
+        .status()
+        .context("Failed to run afplay");
+    let _ = fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        return Err(anyhow!("afplay exited with {}", status));
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Counters kept while listening, for `stats` to ask the running listener for: how
+//! activations ended, how long they took, how much text went through, and how often the
+//! clipboard was busy.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static ACTIVATIONS: Mutex<Activations> = Mutex::new(Activations {
+    succeeded: 0,
+    skipped: BTreeMap::new(),
+    failed: BTreeMap::new(),
+    total_ms: 0.0,
+    copied_chars: 0,
+    pasted_chars: 0,
+});
+
+/// Attempts to open the clipboard that found another app holding it.
+static CLIPBOARD_RETRIES: AtomicU64 = AtomicU64::new(0);
+
+/// Times the clipboard stayed busy for all of `--clipboard-timeout`.
+static CLIPBOARD_TIMEOUTS: AtomicU64 = AtomicU64::new(0);
+
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+struct Activations {
+    succeeded: u64,
+    /// By the reason given, e.g. "Nothing selected".
+    skipped: BTreeMap<&'static str, u64>,
+    /// By what failed: "clipboard", "input" or "other".
+    failed: BTreeMap<&'static str, u64>,
+    total_ms: f64,
+    copied_chars: u64,
+    pasted_chars: u64,
+}
+
+/// Starts the uptime clock. Counting works without it.
+pub fn start() {
+    *STARTED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Instant::now());
+}
+
+/// How one activation ended, for `record`.
+pub enum Ending {
+    Succeeded {
+        copied_chars: Option<usize>,
+        pasted_chars: usize,
+    },
+    Skipped(&'static str),
+    Failed(&'static str),
+}
+
+/// Counts an activation that took `duration`.
+pub fn record(ending: Ending, duration: Duration) {
+    let mut activations = ACTIVATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    activations.total_ms += duration.as_secs_f64() * 1000.0;
+    match ending {
+        Ending::Succeeded {
+            copied_chars,
+            pasted_chars,
+        } => {
+            activations.succeeded += 1;
+            activations.copied_chars += copied_chars.unwrap_or(0) as u64;
+            activations.pasted_chars += pasted_chars as u64;
+        }
+        Ending::Skipped(reason) => *activations.skipped.entry(reason).or_default() += 1,
+        Ending::Failed(category) => *activations.failed.entry(category).or_default() += 1,
+    }
+}
+
+/// Another app had the clipboard open, so opening it is tried again.
+pub fn clipboard_retried() {
+    CLIPBOARD_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The clipboard stayed busy until the timeout.
+pub fn clipboard_timed_out() {
+    CLIPBOARD_TIMEOUTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The counters as `stats` shows them.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    pub uptime_secs: u64,
+    pub activations: u64,
+    pub succeeded: u64,
+    pub skipped: BTreeMap<String, u64>,
+    pub failed: BTreeMap<String, u64>,
+    /// Over every activation, however it ended. Missing before the first one.
+    pub average_ms: Option<f64>,
+    pub copied_chars: u64,
+    pub pasted_chars: u64,
+    pub clipboard_retries: u64,
+    pub clipboard_timeouts: u64,
+}
+
+pub fn snapshot() -> Snapshot {
+    let activations = ACTIVATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let count = activations.succeeded
+        + activations.skipped.values().sum::<u64>()
+        + activations.failed.values().sum::<u64>();
+    let owned = |counts: &BTreeMap<&str, u64>| {
+        counts
+            .iter()
+            .map(|(name, &count)| (name.to_string(), count))
+            .collect()
+    };
+    let started = *STARTED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Snapshot {
+        uptime_secs: started.map_or(0, |started| started.elapsed().as_secs()),
+        activations: count,
+        succeeded: activations.succeeded,
+        skipped: owned(&activations.skipped),
+        failed: owned(&activations.failed),
+        average_ms: (count > 0).then(|| activations.total_ms / count as f64),
+        copied_chars: activations.copied_chars,
+        pasted_chars: activations.pasted_chars,
+        clipboard_retries: CLIPBOARD_RETRIES.load(Ordering::Relaxed),
+        clipboard_timeouts: CLIPBOARD_TIMEOUTS.load(Ordering::Relaxed),
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (hours, minutes) = (self.uptime_secs / 3600, self.uptime_secs / 60 % 60);
+        writeln!(f, "Listening for {}h {:02}m.", hours, minutes)?;
+        write!(f, "Activations: {}", self.activations)?;
+        if let Some(average) = self.average_ms {
+            write!(f, ", {:.0} ms on average", average)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  Succeeded: {}", self.succeeded)?;
+        for (label, counts) in [("Skipped", &self.skipped), ("Failed", &self.failed)] {
+            writeln!(f, "  {}: {}", label, counts.values().sum::<u64>())?;
+            for (name, count) in counts {
+                writeln!(f, "    {}: {}", name, count)?;
+            }
+        }
+        writeln!(
+            f,
+            "Characters copied: {}, pasted: {}",
+            self.copied_chars, self.pasted_chars
+        )?;
+        write!(
+            f,
+            "Clipboard busy: {} retries, {} timeouts",
+            self.clipboard_retries, self.clipboard_timeouts
+        )
+    }
+}
@@ -0,0 +1,68 @@
+//! Exit codes that tell scripts how a run went without parsing its output.
+
+use std::fmt;
+use std::process::ExitCode;
+
+/// How the process ended. The codes are stable, so scripts can rely on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Everything worked and the text was transformed.
+    Success = 0,
+    /// Anything not covered below, such as a bad config file.
+    Failure = 1,
+    // 2 is taken by clap for invalid arguments.
+    /// The clipboard held no text to transform.
+    ClipboardEmpty = 3,
+    /// The clipboard couldn't be opened, read or written.
+    ClipboardError = 4,
+    /// Keyboard input couldn't be hooked or simulated.
+    SimulationError = 5,
+    /// The pipeline left the text as it was, so nothing was written.
+    NoChange = 6,
+}
+
+impl Status {
+    /// The status an error should exit with: the one attached by `WithStatus`, or `Failure`.
+    pub fn of(error: &anyhow::Error) -> Status {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<StatusError>())
+            .map_or(Status::Failure, |e| e.status)
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// Marks the error it wraps with the status the process should exit with.
+#[derive(Debug)]
+struct StatusError {
+    status: Status,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for StatusError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+pub trait WithStatus<T> {
+    /// Exits with `status` if this is the error that ends the process.
+    fn status(self, status: Status) -> anyhow::Result<T>;
+}
+
+impl<T> WithStatus<T> for anyhow::Result<T> {
+    fn status(self, status: Status) -> anyhow::Result<T> {
+        self.map_err(|error| StatusError { status, error }.into())
+    }
+}
@@ -0,0 +1,81 @@
+//! Long-running components that work through messages on their own threads.
+//!
+//! Each component runs under a supervisor: a panic while it handles a message is caught and
+//! logged, the component is put back into a usable state, and it carries on with the next
+//! message instead of taking the whole process down.
+
+use anyhow::anyhow;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::alerts;
+
+/// A part of the program that owns its state and is driven by messages from a channel.
+pub trait Component: Send + 'static {
+    type Message: Send + 'static;
+
+    /// Names the component's thread and its crash reports.
+    const NAME: &'static str;
+
+    /// How long to wait for a message before calling `idle`.
+    const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn handle(&mut self, message: Self::Message);
+
+    /// Background work done whenever no message arrived for `IDLE_INTERVAL`.
+    fn idle(&mut self) {}
+
+    /// Called after `handle` or `idle` panicked, before the next message is handled.
+    fn recover(&mut self) {}
+}
+
+/// Moves `component` to a supervised thread of its own and returns the sender it takes
+/// messages from. The component stops once every sender is dropped.
+pub fn spawn<C: Component>(mut component: C) -> Sender<C::Message> {
+    let (tx, rx) = mpsc::channel::<C::Message>();
+    thread::Builder::new()
+        .name(C::NAME.to_string())
+        .spawn(move || {
+            let mut crashes = 0;
+            loop {
+                let result = match rx.recv_timeout(C::IDLE_INTERVAL) {
+                    Ok(message) => catch(|| component.handle(message)),
+                    Err(RecvTimeoutError::Timeout) => catch(|| component.idle()),
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+                if let Err(panic) = result {
+                    crashes += 1;
+                    alerts::error(&anyhow!(
+                        "The {} crashed ({}, {} so far). Restarting it.",
+                        C::NAME,
+                        describe(&*panic),
+                        crashes
+                    ));
+                    if catch(|| component.recover()).is_err() {
+                        alerts::error(&anyhow!("The {} couldn't recover. Stopping it.", C::NAME));
+                        return;
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn a component thread");
+    tx
+}
+
+/// Runs `f`, turning a panic into an error. The caller recovers whatever state `f` was
+/// changing, so it's fine to assume it's unwind safe.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Box<dyn Any + Send>> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+}
+
+/// The message a panic was raised with.
+pub fn describe(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
@@ -0,0 +1,114 @@
+//! Per-app copy timeouts learned from how long each app actually takes to copy.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config;
+
+/// How much of each new measurement goes into an app's smoothed latency.
+const SMOOTHING: f64 = 0.25;
+
+/// The timeout is this many times the smoothed latency, so normal jitter never trips it.
+const HEADROOM: f64 = 4.0;
+
+/// Bounds on a learned timeout. Even the fastest apps get a little slack.
+const MIN_TIMEOUT: Duration = Duration::from_millis(100);
+const MAX_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What has been learned about a single app.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct AppTiming {
+    /// Smoothed time from the simulated copy to the clipboard changing, in milliseconds.
+    copy_ms: f64,
+    samples: u32,
+}
+
+/// Learned copy latencies, keyed by app and kept in `timing.json` next to the config file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LearnedTiming {
+    apps: BTreeMap<String, AppTiming>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl LearnedTiming {
+    /// Loads the learned values, starting from scratch if there are none yet.
+    pub fn load() -> Result<Self> {
+        let Some(path) = config::default_path().map(|p| p.with_file_name("timing.json")) else {
+            return Ok(LearnedTiming::default());
+        };
+        let mut timing: LearnedTiming = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => LearnedTiming::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        timing.path = Some(path);
+        Ok(timing)
+    }
+
+    /// How long to wait for `app` to copy, or `fallback` until it has been measured.
+    pub fn copy_timeout(&self, app: &str, fallback: Duration) -> Duration {
+        match self.apps.get(app) {
+            Some(timing) => Duration::from_secs_f64(timing.copy_ms * HEADROOM / 1000.0)
+                .clamp(MIN_TIMEOUT, MAX_TIMEOUT),
+            None => fallback,
+        }
+    }
+
+    /// Records how long `app` took to copy, or `None` if it didn't before the timeout.
+    ///
+    /// A timeout may just mean nothing was selected, so it only doubles the wait for next time
+    /// instead of replacing what was learned.
+    pub fn record_copy(&mut self, app: &str, latency: Option<Duration>) {
+        log::debug!("{} copied in {:?}", app, latency);
+        let measured = latency.map(|latency| latency.as_secs_f64() * 1000.0);
+        match (self.apps.get_mut(app), measured) {
+            (Some(timing), Some(ms)) => {
+                timing.copy_ms += SMOOTHING * (ms - timing.copy_ms);
+                timing.samples += 1;
+            }
+            (Some(timing), None) => {
+                let max_ms = MAX_TIMEOUT.as_secs_f64() * 1000.0 / HEADROOM;
+                timing.copy_ms = (timing.copy_ms * 2.0).min(max_ms);
+            }
+            (None, Some(ms)) => {
+                let timing = AppTiming {
+                    copy_ms: ms,
+                    samples: 1,
+                };
+                self.apps.insert(app.to_string(), timing);
+            }
+            // Nothing to learn from until the app has copied once.
+            (None, None) => return,
+        }
+        if let Some(timing) = self.apps.get(app) {
+            println!(
+                "Copy timeout for {} is now {:?} ({} samples).",
+                app,
+                self.copy_timeout(app, MAX_TIMEOUT),
+                timing.samples
+            );
+        }
+        if let Err(e) = self.save() {
+            log::error!("Failed to save learned timing: {:?}", e);
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
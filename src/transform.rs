@@ -0,0 +1,1589 @@
+use clap::builder::{PossibleValue, TypedValueParser};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use crate::normalize;
+
+/// A single text transform. Transforms are chained into a pipeline with `--pipeline`
+/// and applied to the clipboard text in order.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransformStep {
+    /// Rejoin words split across lines with a hyphen ("infor-\nmation"), keeping genuine hyphens
+    Dehyphenate,
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Remove newlines, joining lines with the `--join-with` string (or nothing between CJK characters)
+    Flatten,
+    /// Like flatten, but keep blank lines between paragraphs
+    FlattenParagraphs,
+    /// Only join lines that look hard-wrapped, keeping headings, list items and short lines
+    SmartFlatten,
+    /// Replace runs of spaces and tabs with a single space
+    CollapseSpaces,
+    /// Run the `[[rule]]` find/replace rules from the config file
+    Regex,
+    /// Remove Markdown syntax (emphasis, headings, code, links), leaving plain text
+    StripMarkdown,
+    /// Remove HTML tags and decode entities such as `&amp;`
+    StripHtml,
+    /// Remove spaces and tabs from the end of each line, so joining lines doesn't leave double spaces
+    TrimLines,
+    /// Remove the `>` markers quoting each line of an email reply, however deeply nested
+    StripQuotes,
+    /// Remove the line numbers from copied code, with any `|` or `:` after them, when every line has one
+    StripLineNumbers,
+    /// Remove bullets and list numbering (•, -, *, –, 1., a), i.) from the start of each line of a list, so a flattened list reads as prose
+    StripBullets,
+    /// Remove citation markers like [12] and [3,4] and superscript footnote numbers, as copied from papers and Wikipedia
+    StripCitations,
+    /// Remove running headers, footers and page numbers repeated on each page of text copied from a PDF
+    StripPageHeaders,
+    /// Compose Latin letters and their accents into single characters, a partial NFC-style composition, so identical-looking text compares equal
+    Nfc,
+    /// Like nfc, but also fold ligatures such as ﬁ, fullwidth letters and other common compatibility characters to plain ones, a partial NFKC-style folding
+    Nfkc,
+    /// Remove zero-width spaces and joiners, soft hyphens and byte order marks, which break code and search when pasted
+    StripInvisible,
+    /// Replace non-breaking spaces, narrow ones and other typographic spaces with ordinary spaces
+    PlainSpaces,
+    /// Replace curly quotes, dashes and ellipses with their ASCII equivalents, for pasting into code and terminals
+    PlainPunctuation,
+    /// Any other name: a transform from `TransformOptions::custom`, such as a plugin's.
+    #[value(skip)]
+    #[serde(untagged)]
+    Custom(String),
+}
+
+/// Settings shared by all transform steps.
+#[derive(Clone, Debug)]
+pub struct TransformOptions {
+    /// Replaces each removed newline when flattening.
+    pub join_with: String,
+    /// Applied in order by the `regex` step.
+    pub rules: Vec<ReplaceRule>,
+    /// Run by `Custom` steps of the same name.
+    pub custom: CustomTransforms,
+}
+
+impl TransformOptions {
+    /// The first `Custom` step of `pipeline` without a transform in `custom`, if any.
+    pub fn unknown_step<'a>(&self, pipeline: &'a [TransformStep]) -> Option<&'a str> {
+        pipeline.iter().find_map(|step| match step {
+            TransformStep::Custom(name) if !self.custom.contains(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// A compiled regex substitution, see `config::RegexRule`.
+#[derive(Clone, Debug)]
+pub struct ReplaceRule {
+    pub regex: Regex,
+    pub replacement: String,
+}
+
+impl TransformStep {
+    /// The built-in step called `name`, or else a `Custom` one.
+    pub fn from_name(name: &str) -> Self {
+        TransformStep::from_str(name, false)
+            .unwrap_or_else(|_| TransformStep::Custom(name.to_string()))
+    }
+
+    /// Whether this is one of the steps every pipeline can use, not a `Custom` one.
+    pub fn is_builtin(&self) -> bool {
+        !matches!(self, TransformStep::Custom(_))
+    }
+
+    pub fn apply(&self, text: &str, options: &TransformOptions) -> String {
+        if let Some(transform) = self.unit() {
+            return transform.apply(text);
+        }
+        match self {
+            TransformStep::Flatten => flatten(text, &options.join_with),
+            TransformStep::FlattenParagraphs => flatten_paragraphs(text, &options.join_with),
+            TransformStep::SmartFlatten => smart_flatten(text, &options.join_with),
+            TransformStep::Regex => replace_all(text, &options.rules),
+            // Unknown names are rejected with `unknown_step` before anything runs.
+            TransformStep::Custom(name) => match options.custom.get(name) {
+                Some(transform) => transform.apply(text),
+                None => text.to_string(),
+            },
+            step => unreachable!("{:?} is missing from unit_steps!", step),
+        }
+    }
+}
+
+/// Parses `--pipeline` items: the built-in step names, shown as the possible values, and
+/// any other name as a `Custom` step.
+#[derive(Clone, Copy, Debug)]
+pub struct StepParser;
+
+impl TypedValueParser for StepParser {
+    type Value = TransformStep;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<TransformStep, clap::Error> {
+        match value.to_str() {
+            Some(name) => Ok(TransformStep::from_name(name)),
+            None => Err(clap::Error::new(clap::error::ErrorKind::InvalidUtf8).with_cmd(cmd)),
+        }
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            TransformStep::value_variants()
+                .iter()
+                .filter_map(|step| step.to_possible_value()),
+        ))
+    }
+}
+
+/// Runs every step of `pipeline` over `text`, feeding each step the previous step's output.
+pub fn apply_pipeline(
+    text: &str,
+    pipeline: &[TransformStep],
+    options: &TransformOptions,
+) -> String {
+    pipeline
+        .iter()
+        .fold(text.to_string(), |text, step| step.apply(&text, options))
+}
+
+/// Like `apply_pipeline`, but also returns how long each step took.
+pub fn apply_pipeline_timed(
+    text: &str,
+    pipeline: &[TransformStep],
+    options: &TransformOptions,
+) -> (String, Vec<Duration>) {
+    let mut text = text.to_string();
+    let mut timings = Vec::with_capacity(pipeline.len());
+    for step in pipeline {
+        let started = Instant::now();
+        text = step.apply(&text, options);
+        timings.push(started.elapsed());
+    }
+    (text, timings)
+}
+
+// --- Library Interface ---
+
+/// A single text-to-text step of a `Pipeline`. The built-in steps implement it, and so does
+/// any `Fn(&str) -> String`, so programs embedding this crate can mix in their own.
+pub trait Transform: Send + Sync {
+    fn apply(&self, text: &str) -> String;
+}
+
+impl<F: Fn(&str) -> String + Send + Sync> Transform for F {
+    fn apply(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
+/// Declares the steps that need nothing but the text: a unit struct for each, implementing
+/// `Transform` with the function given, and `TransformStep::unit` to find it by step.
+macro_rules! unit_steps {
+    ($($(#[$doc:meta])* $step:ident => $apply:expr;)*) => {
+        $(
+            $(#[$doc])*
+            pub struct $step;
+
+            impl Transform for $step {
+                fn apply(&self, text: &str) -> String {
+                    ($apply)(text)
+                }
+            }
+        )*
+
+        impl TransformStep {
+            /// The `Transform` of a step that needs nothing from `TransformOptions`.
+            fn unit(&self) -> Option<&'static dyn Transform> {
+                match self {
+                    $(TransformStep::$step => Some(&$step),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+unit_steps! {
+    /// The `dehyphenate` step.
+    Dehyphenate => dehyphenate;
+    /// The `trim` step.
+    Trim => |text: &str| text.trim().to_string();
+    /// The `collapse-spaces` step.
+    CollapseSpaces => collapse_spaces;
+    /// The `strip-markdown` step.
+    StripMarkdown => |text| replace_all(text, &MARKDOWN_RULES);
+    /// The `strip-html` step.
+    StripHtml => |text| decode_entities(&replace_all(text, &HTML_RULES));
+    /// The `trim-lines` step.
+    TrimLines => trim_lines;
+    /// The `strip-quotes` step.
+    StripQuotes => strip_quotes;
+    /// The `strip-line-numbers` step.
+    StripLineNumbers => strip_line_numbers;
+    /// The `strip-bullets` step.
+    StripBullets => strip_bullets;
+    /// The `strip-citations` step.
+    StripCitations => strip_citations;
+    /// The `strip-page-headers` step.
+    StripPageHeaders => strip_page_headers;
+    /// The `nfc` step.
+    Nfc => normalize::nfc;
+    /// The `nfkc` step.
+    Nfkc => normalize::nfkc;
+    /// The `strip-invisible` step.
+    StripInvisible => strip_invisible;
+    /// The `plain-spaces` step.
+    PlainSpaces => plain_spaces;
+    /// The `plain-punctuation` step.
+    PlainPunctuation => plain_punctuation;
+}
+
+/// The `flatten` step, joining lines with `join_with`.
+pub struct Flatten {
+    pub join_with: String,
+}
+
+/// The `flatten-paragraphs` step, joining lines with `join_with`.
+pub struct FlattenParagraphs {
+    pub join_with: String,
+}
+
+/// The `smart-flatten` step, joining lines with `join_with`.
+pub struct SmartFlatten {
+    pub join_with: String,
+}
+
+/// The `regex` step, running `rules` in order.
+pub struct Replace {
+    pub rules: Vec<ReplaceRule>,
+}
+
+impl Transform for Flatten {
+    fn apply(&self, text: &str) -> String {
+        flatten(text, &self.join_with)
+    }
+}
+
+impl Transform for FlattenParagraphs {
+    fn apply(&self, text: &str) -> String {
+        flatten_paragraphs(text, &self.join_with)
+    }
+}
+
+impl Transform for SmartFlatten {
+    fn apply(&self, text: &str) -> String {
+        smart_flatten(text, &self.join_with)
+    }
+}
+
+impl Transform for Replace {
+    fn apply(&self, text: &str) -> String {
+        replace_all(text, &self.rules)
+    }
+}
+
+/// Transforms that pipelines refer to by name, such as plugins.
+#[derive(Clone, Default)]
+pub struct CustomTransforms {
+    transforms: BTreeMap<String, Arc<dyn Transform>>,
+}
+
+impl CustomTransforms {
+    /// Adds `transform` as the `Custom` step called `name`, replacing any earlier one.
+    pub fn insert(&mut self, name: impl Into<String>, transform: impl Transform + 'static) {
+        self.transforms.insert(name.into(), Arc::new(transform));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Transform> {
+        self.transforms
+            .get(name)
+            .map(|transform| transform.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.transforms.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.transforms.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+impl fmt::Debug for CustomTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+impl TransformStep {
+    /// The `Transform` for this step, set up with `options`.
+    pub fn to_transform(&self, options: &TransformOptions) -> Box<dyn Transform> {
+        if let Some(transform) = self.unit() {
+            return Box::new(move |text: &str| transform.apply(text));
+        }
+        let join_with = options.join_with.clone();
+        match self {
+            TransformStep::Flatten => Box::new(Flatten { join_with }),
+            TransformStep::FlattenParagraphs => Box::new(FlattenParagraphs { join_with }),
+            TransformStep::SmartFlatten => Box::new(SmartFlatten { join_with }),
+            TransformStep::Regex => Box::new(Replace {
+                rules: options.rules.clone(),
+            }),
+            TransformStep::Custom(name) => match options.custom.transforms.get(name) {
+                Some(transform) => {
+                    let transform = Arc::clone(transform);
+                    Box::new(move |text: &str| transform.apply(text))
+                }
+                None => Box::new(|text: &str| text.to_string()),
+            },
+            step => unreachable!("{:?} is missing from unit_steps!", step),
+        }
+    }
+}
+
+/// Transforms run in order, each over the previous one's output.
+///
+/// ```
+/// use strflatten::{Dehyphenate, Flatten, Pipeline};
+///
+/// let pipeline = Pipeline::new()
+///     .then(Dehyphenate)
+///     .then(Flatten { join_with: " ".to_string() })
+///     .then(|text: &str| text.to_uppercase());
+/// println!("{}", pipeline.apply("infor-\nmation is\nwrapped"));
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    steps: Vec<Box<dyn Transform>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// The pipeline the command line would run for `steps`.
+    pub fn from_steps(steps: &[TransformStep], options: &TransformOptions) -> Self {
+        Pipeline {
+            steps: steps
+                .iter()
+                .map(|step| step.to_transform(options))
+                .collect(),
+        }
+    }
+
+    /// Appends `step` to the end of the pipeline.
+    pub fn then(mut self, step: impl Transform + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.steps
+            .iter()
+            .fold(text.to_string(), |text, step| step.apply(&text))
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+// --- Steps ---
+
+fn flatten(text: &str, join_with: &str) -> String {
+    // Replace both Windows (\r\n) and Unix (\n) newlines.
+    // Removing \r first means only \n needs to be joined.
+    let text = text.replace('\r', "");
+    let mut out = String::with_capacity(text.len());
+    let mut lines = text.split('\n');
+    if let Some(first) = lines.next() {
+        out.push_str(first);
+    }
+    for line in lines {
+        push_joined(&mut out, line, join_with);
+    }
+    out
+}
+
+/// Appends `line` to `out` as a continuation of its last line.
+fn push_joined(out: &mut String, line: &str, join_with: &str) {
+    // Chinese and Japanese don't put spaces between words, so a wrapped line
+    // continues directly when the break falls between two such characters.
+    let joins_cjk = out.chars().next_back().is_some_and(is_unspaced_cjk)
+        && line.chars().next().is_some_and(is_unspaced_cjk);
+    if !joins_cjk {
+        out.push_str(join_with);
+    }
+    out.push_str(line);
+}
+
+/// Characters from scripts written without spaces between words.
+///
+/// Hangul is deliberately excluded: Korean separates words with spaces, so its
+/// hard wraps land on a space and should be joined like Latin text.
+fn is_unspaced_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'     // CJK symbols and punctuation
+        | '\u{3040}'..='\u{309F}'   // Hiragana
+        | '\u{30A0}'..='\u{30FF}'   // Katakana
+        | '\u{31F0}'..='\u{31FF}'   // Katakana phonetic extensions
+        | '\u{3400}'..='\u{4DBF}'   // CJK unified ideographs extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}'   // CJK compatibility ideographs
+        | '\u{FF00}'..='\u{FFEF}'   // Halfwidth and fullwidth forms
+        | '\u{20000}'..='\u{2FA1F}' // Supplementary ideographs
+    )
+}
+
+fn flatten_paragraphs(text: &str, join_with: &str) -> String {
+    let text = text.replace('\r', "");
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    // A paragraph ends at a blank (or whitespace-only) line.
+    for line in text.split('\n') {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(flatten(&current.join("\n"), join_with));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(flatten(&current.join("\n"), join_with));
+    }
+
+    paragraphs.join("\n\n")
+}
+
+/// Hard-wrapped text rarely wraps narrower than this, so shorter columns are treated as intentional.
+const MIN_WRAP_WIDTH: usize = 30;
+
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:[-*+•]|\d+[.)]|[a-zA-Z][.)])\s").unwrap());
+
+fn smart_flatten(text: &str, join_with: &str) -> String {
+    let text = text.replace('\r', "");
+    let lines: Vec<&str> = text.split('\n').collect();
+
+    // The wrap column is roughly the longest line; wrapped lines end close to it.
+    let width = lines
+        .iter()
+        .map(|line| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i == 0 {
+            out.push_str(line);
+            continue;
+        }
+        if looks_wrapped(lines[i - 1], line, width) {
+            push_joined(&mut out, line.trim_start(), join_with);
+        } else {
+            out.push('\n');
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// Decides whether the break between `prev` and `next` is an artificial hard wrap.
+fn looks_wrapped(prev: &str, next: &str, width: usize) -> bool {
+    let prev = prev.trim_end();
+    let next_trimmed = next.trim_start();
+    if prev.is_empty() || next_trimmed.is_empty() {
+        return false; // Paragraph break
+    }
+    if prev.trim_start().starts_with('#') || LIST_ITEM.is_match(next) {
+        return false; // Heading or the start of a new list item
+    }
+
+    let near_width = width >= MIN_WRAP_WIDTH && prev.chars().count() * 4 >= width * 3;
+    let mid_sentence = !prev.ends_with(['.', '!', '?', ':', ';']);
+    let next_lowercase = next_trimmed.starts_with(char::is_lowercase);
+
+    near_width || (mid_sentence && next_lowercase)
+}
+
+/// Prefixes that are almost always followed by a real hyphen ("well-known", "self-evident").
+const COMPOUND_PREFIXES: &[&str] = &["all", "cross", "ex", "half", "non", "self", "well"];
+
+fn dehyphenate(text: &str) -> String {
+    // Lines keep their `\r`, except where a hyphenated word is joined across the break.
+    let mut out = String::with_capacity(text.len());
+    let mut lines = text.split('\n').peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_end();
+        let next = lines.peek().map(|next| next.trim_start());
+
+        match (hyphenated_fragment(trimmed), next) {
+            (Some(fragment), Some(next)) if next.starts_with(char::is_alphabetic) => {
+                if keep_hyphen(fragment, next) {
+                    out.push_str(trimmed);
+                } else {
+                    out.push_str(&trimmed[..trimmed.len() - 1]);
+                }
+                // The next line continues the word, so drop its indentation and the newline.
+                if let Some(next_line) = lines.peek_mut() {
+                    *next_line = next;
+                }
+            }
+            _ => {
+                out.push_str(line);
+                if lines.peek().is_some() {
+                    out.push('\n');
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// If `line` ends with a letter followed by a hyphen, returns the word before the hyphen.
+fn hyphenated_fragment(line: &str) -> Option<&str> {
+    let without_hyphen = line.strip_suffix('-')?;
+    if !without_hyphen.ends_with(char::is_alphabetic) {
+        return None;
+    }
+    let start = without_hyphen
+        .rfind(|c: char| !(c.is_alphabetic() || c == '-'))
+        .map_or(0, |i| {
+            i + without_hyphen[i..].chars().next().map_or(1, char::len_utf8)
+        });
+    Some(&without_hyphen[start..])
+}
+
+/// Decides whether a hyphen at a line break is part of the word rather than a wrap.
+fn keep_hyphen(fragment: &str, next: &str) -> bool {
+    let next_is_lowercase = next.chars().next().is_some_and(char::is_lowercase);
+    !next_is_lowercase
+        || fragment.contains('-')
+        || COMPOUND_PREFIXES.contains(&fragment.to_lowercase().as_str())
+}
+
+fn collapse_spaces(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_run = false;
+    for c in text.chars() {
+        if c == ' ' || c == '\t' {
+            if !in_run {
+                out.push(' ');
+            }
+            in_run = true;
+        } else {
+            out.push(c);
+            in_run = false;
+        }
+    }
+    out
+}
+
+fn trim_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        // Keep a Windows line ending's \r, but not the spaces before it.
+        let (line, cr) = match line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (line, ""),
+        };
+        out.push_str(line.trim_end_matches([' ', '\t']));
+        out.push_str(cr);
+    }
+    out
+}
+
+/// One or more `>` quoting markers at the start of a line, e.g. `> `, `>> ` or `> > `.
+static QUOTE_MARKERS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^[ \t]*>(?:[ \t]*>)*[ \t]?").unwrap());
+
+fn strip_quotes(text: &str) -> String {
+    QUOTE_MARKERS.replace_all(text, "").into_owned()
+}
+
+/// A line number at the start of a line, with the `|`, `:` or `│` and the space after it.
+static LINE_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[ \t]*(\d+)(?:[ \t]*[|:│][ \t]?|[ \t]|\r?$)").unwrap());
+
+fn strip_line_numbers(text: &str) -> String {
+    // Only numbered text: every non-blank line numbered, counting up. Otherwise the numbers
+    // are probably the text's own, like years or amounts.
+    let mut numbered = 0;
+    let mut last = None;
+    for line in text.split('\n').filter(|line| !line.trim().is_empty()) {
+        let Some(number) = LINE_NUMBER
+            .captures(line)
+            .and_then(|caps| caps[1].parse::<u64>().ok())
+        else {
+            return text.to_string();
+        };
+        if last.is_some_and(|last| number <= last) {
+            return text.to_string();
+        }
+        last = Some(number);
+        numbered += 1;
+    }
+    if numbered < 2 {
+        return text.to_string();
+    }
+    text.split('\n')
+        .map(|line| LINE_NUMBER.replace(line, ""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A bullet or an ordered list's label at the start of a line, such as `•`, `–`, `1.`, `a)`
+/// or `(iv)`, with the indentation before it. Group 1 is a bullet; otherwise group 2 is the
+/// label's `(`, group 3 the label and group 4 its `.` or `)`.
+static LIST_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[ \t]*(?:([-*+•◦▪‣–])|(\(?)(\d{1,3}|[a-zA-Z]+)([.)]))[ \t]+").unwrap()
+});
+
+/// Roman numerals up to 39, as far as lists go, all in one case.
+static ROMAN_NUMERAL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:x{0,3}(?:ix|iv|v?i{0,3})|X{0,3}(?:IX|IV|V?I{0,3}))$").unwrap()
+});
+
+/// Bullets nothing else starts a line with, stripped even without another item.
+const PLAIN_BULLETS: &str = "•◦▪‣";
+
+/// The kinds of list `line`'s marker could belong to, such as `•`, `1.`, `(a)` or `i)`. A
+/// lone `i.` could be a letter or a roman numeral. Empty for a line that isn't an item.
+fn list_kinds(line: &str) -> Vec<String> {
+    let Some(caps) = LIST_MARKER.captures(line) else {
+        return Vec::new();
+    };
+    if let Some(bullet) = caps.get(1) {
+        return vec![bullet.as_str().to_string()];
+    }
+    let (open, label, close) = (&caps[2], &caps[3], &caps[4]);
+    let mut styles = Vec::new();
+    if label.starts_with(|c: char| c.is_ascii_digit()) {
+        styles.push("1");
+    } else {
+        if label.len() == 1 {
+            styles.push(if label.chars().all(char::is_lowercase) {
+                "a"
+            } else {
+                "A"
+            });
+        }
+        if ROMAN_NUMERAL.is_match(label) {
+            styles.push(if label.chars().all(char::is_lowercase) {
+                "i"
+            } else {
+                "I"
+            });
+        }
+    }
+    styles
+        .into_iter()
+        .map(|style| format!("{}{}{}", open, style, close))
+        .collect()
+}
+
+fn strip_bullets(text: &str) -> String {
+    // Only lines of a list: a marker like `1.` or `-` has to start another line too, so a
+    // sentence like "2024. It was..." or "A. Smith wrote..." keeps its start.
+    let lines: Vec<&str> = text.split('\n').collect();
+    let kinds: Vec<Vec<String>> = lines.iter().map(|line| list_kinds(line)).collect();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for kind in kinds.iter().flatten() {
+        *counts.entry(kind).or_default() += 1;
+    }
+    lines
+        .iter()
+        .zip(&kinds)
+        .map(|(line, kinds)| {
+            let in_list = kinds
+                .iter()
+                .any(|kind| counts[kind.as_str()] >= 2 || PLAIN_BULLETS.contains(kind.as_str()));
+            match in_list {
+                true => LIST_MARKER.replace(line, "").into_owned(),
+                false => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A line holding nothing but a page number: `12`, `Page 12`, `12 of 30` or `- 12 -`.
+static PAGE_NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:page[ \t]+)?\d{1,4}(?:[ \t]+of[ \t]+\d{1,4})?|[-–—][ \t]*\d{1,4}[ \t]*[-–—])$",
+    )
+    .unwrap()
+});
+
+/// Running headers and footers are short lines, not sentences.
+const MAX_HEADER_LEN: usize = 80;
+
+/// How many non-blank lines at the top and bottom of a page can be a header or footer.
+const PAGE_EDGE_LINES: usize = 2;
+
+/// Without form feeds, pages are told apart by a line repeating at least this often...
+const MIN_REPEATS: usize = 3;
+
+/// ...at least this many lines apart, so a short list like "Step 1", "Step 2" isn't taken
+/// for pages.
+const MIN_PAGE_LINES: usize = 8;
+
+/// What a header or footer is compared by: the line with its digits masked, since the page
+/// number in it usually changes. Page numbers on their own all compare equal. `None` for
+/// lines that can't be either.
+fn furniture_key(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    if PAGE_NUMBER.is_match(trimmed) {
+        return Some("#page".to_string());
+    }
+    let candidate = trimmed.chars().count() <= MAX_HEADER_LEN
+        && trimmed.contains(char::is_alphabetic)
+        && !trimmed.ends_with(['.', '!', '?', ':', ';', ',']);
+    candidate.then(|| {
+        trimmed
+            .chars()
+            .map(|c| if c.is_ascii_digit() { '#' } else { c })
+            .collect()
+    })
+}
+
+fn strip_page_headers(text: &str) -> String {
+    // Form feeds, where the PDF reader keeps them, say exactly where each page starts.
+    let pages: Vec<Vec<&str>> = text
+        .split('\x0c')
+        .map(|page| page.split('\n').collect())
+        .collect();
+    let furniture = if pages.len() > 1 {
+        furniture_by_page(&pages)
+    } else {
+        furniture_by_spacing(&pages[0])
+    };
+
+    // The blank lines around a page break go with it, so a sentence split across two pages
+    // joins up again.
+    let mut out: Vec<&str> = Vec::new();
+    let mut at_break = false;
+    for (line, furniture) in pages.iter().flatten().zip(furniture) {
+        if furniture {
+            while out.last().is_some_and(|last| last.trim().is_empty()) {
+                out.pop();
+            }
+            at_break = true;
+        } else if !(at_break && line.trim().is_empty()) {
+            out.push(line);
+            at_break = false;
+        }
+    }
+    out.join("\n")
+}
+
+/// Indexes of the first and last `PAGE_EDGE_LINES` non-blank lines of `lines`.
+fn page_edges(lines: &[&str]) -> Vec<usize> {
+    let filled: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].trim().is_empty())
+        .collect();
+    let mut edges: Vec<usize> = filled.iter().take(PAGE_EDGE_LINES).copied().collect();
+    let bottom = filled
+        .len()
+        .saturating_sub(PAGE_EDGE_LINES)
+        .max(edges.len());
+    edges.extend(&filled[bottom..]);
+    edges
+}
+
+/// Which lines of the form-feed separated `pages` are furniture: page numbers at a page's
+/// edges, and lines at the edges of more than two pages.
+fn furniture_by_page(pages: &[Vec<&str>]) -> Vec<bool> {
+    let mut pages_with: BTreeMap<String, usize> = BTreeMap::new();
+    for page in pages {
+        let keys: BTreeSet<String> = page_edges(page)
+            .into_iter()
+            .filter_map(|i| furniture_key(page[i]))
+            .collect();
+        for key in keys {
+            *pages_with.entry(key).or_default() += 1;
+        }
+    }
+    let mut furniture = Vec::new();
+    for page in pages {
+        let edges = page_edges(page);
+        furniture.extend((0..page.len()).map(|i| {
+            edges.contains(&i)
+                && furniture_key(page[i])
+                    .is_some_and(|key| key == "#page" || pages_with[&key] >= MIN_REPEATS)
+        }));
+    }
+    furniture
+}
+
+/// Which of `lines` are furniture, going by lines that repeat evenly spaced, as they do at
+/// the same place on every page, and page numbers next to them.
+fn furniture_by_spacing(lines: &[&str]) -> Vec<bool> {
+    let mut positions: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(key) = furniture_key(line) {
+            positions.entry(key).or_default().push(i);
+        }
+    }
+    let mut furniture = vec![false; lines.len()];
+    for indexes in positions.values().filter(|indexes| evenly_spaced(indexes)) {
+        for &i in indexes {
+            furniture[i] = true;
+        }
+    }
+    // A page number between a footer and the next header, where no other page number
+    // lines repeat evenly.
+    let filled: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].trim().is_empty())
+        .collect();
+    for (n, &i) in filled.iter().enumerate() {
+        let near_edge = filled
+            [n.saturating_sub(PAGE_EDGE_LINES)..filled.len().min(n + PAGE_EDGE_LINES + 1)]
+            .iter()
+            .any(|&j| j != i && furniture[j] && !PAGE_NUMBER.is_match(lines[j].trim()));
+        if near_edge && PAGE_NUMBER.is_match(lines[i].trim()) {
+            furniture[i] = true;
+        }
+    }
+    furniture
+}
+
+/// Whether lines at `indexes` repeat like a header or footer does: often enough, far enough
+/// apart, and every gap within a fifth (or two lines) of the typical one.
+fn evenly_spaced(indexes: &[usize]) -> bool {
+    if indexes.len() < MIN_REPEATS {
+        return false;
+    }
+    let mut gaps: Vec<usize> = indexes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    gaps.sort_unstable();
+    let typical = gaps[gaps.len() / 2];
+    let tolerance = (typical / 5).max(2);
+    typical >= MIN_PAGE_LINES && gaps.iter().all(|gap| gap.abs_diff(typical) <= tolerance)
+}
+
+fn strip_invisible(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            // Zero-width space, word joiner, invisible math operators, soft hyphen, BOM and
+            // the Mongolian vowel separator.
+            '\u{200B}' | '\u{2060}'..='\u{2064}' | '\u{00AD}' | '\u{FEFF}' | '\u{180E}' => {}
+            // Zero-width joiner and non-joiner shape emoji and scripts like Persian and
+            // Hindi, so they stay between two non-ASCII characters.
+            '\u{200C}' | '\u{200D}' => {
+                let non_ascii = |c: Option<&char>| c.is_some_and(|c| !c.is_ascii());
+                if i > 0 && non_ascii(chars.get(i - 1)) && non_ascii(chars.get(i + 1)) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn plain_spaces(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            // No-break, narrow no-break and figure spaces, then the typographic spaces from
+            // en quad to hair space and the medium mathematical space.
+            '\u{00A0}' | '\u{202F}' | '\u{2000}'..='\u{200A}' | '\u{205F}' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+fn plain_punctuation(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '‘' | '’' | '‚' | '‛' | '′' | 'ʼ' => out.push('\''),
+            '“' | '”' | '„' | '‟' | '″' => out.push('"'),
+            // Hyphen, non-breaking hyphen, figure dash, en dash and minus sign.
+            '‐' | '‑' | '‒' | '–' | '−' => out.push('-'),
+            '—' | '―' => out.push_str("--"),
+            '…' => out.push_str("..."),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn replace_all(text: &str, rules: &[ReplaceRule]) -> String {
+    rules.iter().fold(text.to_string(), |text, rule| {
+        rule.regex
+            .replace_all(&text, rule.replacement.as_str())
+            .into_owned()
+    })
+}
+
+/// Builds a rule from a pattern that is known to be valid.
+fn rule(pattern: &str, replacement: &str) -> ReplaceRule {
+    ReplaceRule {
+        regex: Regex::new(pattern).expect("built-in pattern is valid"),
+        replacement: replacement.to_string(),
+    }
+}
+
+/// Applied in order; block-level syntax goes first so inline rules see plain lines.
+static MARKDOWN_RULES: LazyLock<Vec<ReplaceRule>> = LazyLock::new(|| {
+    vec![
+        // Code fences: drop the ``` / ~~~ lines but keep the code.
+        rule(r"(?m)^[ \t]*(?:```|~~~)[^\n]*\n?", ""),
+        // Horizontal rules.
+        rule(
+            r"(?m)^[ \t]*(?:(?:-[ \t]*){3,}|(?:\*[ \t]*){3,}|(?:_[ \t]*){3,})$\n?",
+            "",
+        ),
+        // ATX headings, including optional closing #s.
+        rule(r"(?m)^[ \t]*#{1,6}[ \t]+(.*?)[ \t]*#*[ \t]*$", "$1"),
+        // Block quotes.
+        rule(r"(?m)^[ \t]*>[ \t]?", ""),
+        // Images and links keep only their text.
+        rule(r"!\[([^\]]*)\]\([^)]*\)", "$1"),
+        rule(r"\[([^\]]+)\]\([^)]*\)", "$1"),
+        rule(r"\[([^\]]+)\]\[[^\]]*\]", "$1"),
+        // Inline code.
+        rule(r"`+([^`]+)`+", "$1"),
+        // Bold, italic and strikethrough.
+        rule(r"\*\*([^*]+)\*\*", "$1"),
+        rule(r"\b__([^_]+)__\b", "$1"),
+        // A single `*` only opens or closes next to a non-word character, so `2*3*4` stays.
+        rule(r"\B\*([^*\s](?:[^*]*[^*\s])?)\*\B", "$1"),
+        rule(r"\b_([^_\s](?:[^_]*[^_\s])?)_\b", "$1"),
+        rule(r"~~([^~]+)~~", "$1"),
+    ]
+});
+
+static HTML_RULES: LazyLock<Vec<ReplaceRule>> = LazyLock::new(|| {
+    vec![
+        // Contents of these elements are never readable text.
+        rule(
+            r"(?is)<(script|style|head)\b[^>]*>.*?</(script|style|head)\s*>",
+            "",
+        ),
+        rule(r"(?s)<!--.*?-->", ""),
+        // Line breaks and block-level closers become newlines so structure survives.
+        rule(r"(?i)<br\s*/?>", "\n"),
+        rule(r"(?i)</(p|div|li|tr|h[1-6]|blockquote|pre)\s*>", "\n"),
+        // A tag starts right after the `<`, so "1 < 2 and 3 > 2" stays.
+        rule(r"<[a-zA-Z/!?][^>]*>", ""),
+    ]
+});
+
+/// A bracketed marker like `[12]`, `[3,4]`, `[5–7]`, `[a]` or `[citation needed]`, with the
+/// space before it. Group 1 is the space, group 2 what's in the brackets.
+static CITATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"([ \t]?)\[(\d+(?:[ \t]*[,–-][ \t]*\d+)*|[a-z]|note \d+|citation needed)\]")
+        .unwrap()
+});
+
+/// Superscript footnote numbers, only after punctuation or a word of three letters or more,
+/// so km² and x² survive.
+static SUPERSCRIPT_FOOTNOTE: LazyLock<ReplaceRule> = LazyLock::new(|| {
+    rule(
+        r#"(\p{L}{3,}|[.,;:!?)"”’])[\u{2070}\u{00B9}\u{00B2}\u{00B3}\u{2074}-\u{2079}]+"#,
+        "$1",
+    )
+});
+
+fn strip_citations(text: &str) -> String {
+    let stripped = CITATION.replace_all(text, |caps: &regex::Captures| {
+        let whole = caps.get(0).unwrap();
+        let (before, after) = (&text[..whole.start()], &text[whole.end()..]);
+        let marker = &caps[2];
+        // `[citation needed]` and `[note 2]` are never code.
+        if marker.contains(' ') {
+            return String::new();
+        }
+        let is_citation = match before.chars().next_back() {
+            // `[1] Smith, J.` opening a line is a reference list's own numbering.
+            None | Some('\n') => false,
+            Some(_) if !caps[1].is_empty() => !before.ends_with(char::is_whitespace),
+            Some(c) if ".,;:!?)\"'”’".contains(c) => true,
+            // `arr[0]`, `list_1[2]` and `m[i]` are indexing; `Ulm[1]` is a citation.
+            Some(_) => {
+                let word = before
+                    .rsplit(|c: char| !c.is_alphanumeric() && c != '_')
+                    .next()
+                    .unwrap_or("");
+                word.chars().count() >= 2
+                    && word.chars().all(char::is_alphabetic)
+                    && !marker.starts_with(|c: char| c == '0' || c.is_ascii_lowercase())
+            }
+        };
+        if is_citation && !continues_expression(after) {
+            String::new()
+        } else {
+            whole.as_str().to_string()
+        }
+    });
+    let rule = &*SUPERSCRIPT_FOOTNOTE;
+    rule.regex
+        .replace_all(&stripped, rule.replacement.as_str())
+        .into_owned()
+}
+
+/// Whether `after` continues code, as in `a[1][2]`, `items[2].name` or `arr[1] = 5`.
+fn continues_expression(after: &str) -> bool {
+    let mut chars = after.chars();
+    match chars.next() {
+        Some(c) if c.is_alphanumeric() || "[(_".contains(c) => true,
+        Some('.') => chars.next().is_some_and(|c| c.is_alphanumeric()),
+        _ => after
+            .trim_start_matches([' ', '\t'])
+            .starts_with(['=', '+', '-', '*', '/', '%', '<', '>', '&', '|', '^']),
+    }
+}
+
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap());
+
+fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |caps: &regex::Captures| {
+            let name = &caps[1];
+            let decoded = if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = name.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                named_entity(name)
+            };
+            // Leave anything unrecognized exactly as it was.
+            decoded.map_or_else(|| caps[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "hellip" => '\u{2026}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "bull" => '\u{2022}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        "trade" => '\u{2122}',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_builtin_step_runs() {
+        let options = TransformOptions {
+            join_with: " ".to_string(),
+            rules: Vec::new(),
+            custom: CustomTransforms::default(),
+        };
+        for step in TransformStep::value_variants() {
+            let text = "a  line\nand-\nanother ";
+            assert_eq!(
+                step.to_transform(&options).apply(text),
+                step.apply(text, &options)
+            );
+        }
+    }
+
+    // --- flatten ---
+
+    #[test]
+    fn flatten_joins_every_line() {
+        assert_eq!(flatten("one\r\ntwo\nthree", " "), "one two three");
+        assert_eq!(flatten("a\nb\n", ", "), "a, b, ");
+        assert_eq!(flatten("a\nb", ""), "ab");
+    }
+
+    #[test]
+    fn flatten_leaves_single_lines_alone() {
+        assert_eq!(flatten("  just one line  ", " "), "  just one line  ");
+        assert_eq!(flatten("", " "), "");
+    }
+
+    #[test]
+    fn flatten_joins_cjk_without_a_space() {
+        assert_eq!(flatten("日本語の\n文章です", " "), "日本語の文章です");
+        assert_eq!(flatten("中文\n。", " "), "中文。");
+    }
+
+    #[test]
+    fn flatten_spaces_cjk_next_to_other_scripts() {
+        assert_eq!(flatten("東京\nTokyo", " "), "東京 Tokyo");
+        assert_eq!(flatten("한국어\n문장", " "), "한국어 문장");
+    }
+
+    // --- flatten-paragraphs ---
+
+    #[test]
+    fn flatten_paragraphs_joins_within_paragraphs() {
+        assert_eq!(
+            flatten_paragraphs("one\ntwo\n\nthree\r\nfour", " "),
+            "one two\n\nthree four"
+        );
+    }
+
+    #[test]
+    fn flatten_paragraphs_keeps_paragraph_breaks() {
+        assert_eq!(
+            flatten_paragraphs("\none\n \t\n\n\ntwo\n", " "),
+            "one\n\ntwo"
+        );
+        assert_eq!(flatten_paragraphs("one paragraph", " "), "one paragraph");
+    }
+
+    // --- smart-flatten ---
+
+    #[test]
+    fn smart_flatten_joins_wrapped_prose() {
+        let text = "The quick brown fox jumps over the lazy dog and\n\
+                    keeps on running through the field until it\n\
+                    reaches the river.";
+        assert_eq!(
+            smart_flatten(text, " "),
+            "The quick brown fox jumps over the lazy dog and keeps on running through the \
+             field until it reaches the river."
+        );
+    }
+
+    #[test]
+    fn smart_flatten_keeps_headings_and_list_items() {
+        let text = "## A heading that is long enough to be wide\n\
+                    then a short body.\n\
+                    Things to pack for the trip this weekend:\n\
+                    - a tent\n\
+                    - a stove\n\
+                    2) and maps";
+        assert_eq!(smart_flatten(text, " "), text);
+    }
+
+    #[test]
+    fn smart_flatten_stops_at_short_final_lines() {
+        let text = "The first paragraph ends on a short line\n\
+                    here.\n\
+                    Then a new sentence starts on this line.\n\
+                    \n\
+                    After a blank line.";
+        assert_eq!(
+            smart_flatten(text, " "),
+            "The first paragraph ends on a short line here.\n\
+             Then a new sentence starts on this line.\n\
+             \n\
+             After a blank line."
+        );
+    }
+
+    #[test]
+    fn looks_wrapped_below_the_wrap_width() {
+        // Too narrow to tell by width, so only a sentence running on into lowercase joins.
+        assert!(looks_wrapped("a short", "line", 12));
+        assert!(!looks_wrapped("It ended.", "then more", 12));
+        assert!(!looks_wrapped("Name", "Alice", 12));
+        assert!(!looks_wrapped("", "line", 12));
+    }
+
+    // --- dehyphenate ---
+
+    #[test]
+    fn dehyphenate_joins_wrapped_words() {
+        assert_eq!(dehyphenate("infor-\nmation"), "information");
+        assert_eq!(
+            dehyphenate("an infor-   \n    mation sheet"),
+            "an information sheet"
+        );
+    }
+
+    #[test]
+    fn dehyphenate_keeps_real_hyphens() {
+        assert_eq!(dehyphenate("well-\nknown"), "well-known");
+        assert_eq!(dehyphenate("Self-\nevident"), "Self-evident");
+        assert_eq!(dehyphenate("non-\nprofit"), "non-profit");
+        assert_eq!(dehyphenate("state-of-the-\nart"), "state-of-the-art");
+        assert_eq!(dehyphenate("Jean-\nPaul"), "Jean-Paul");
+        assert_eq!(dehyphenate("a list -\nitem"), "a list -\nitem");
+    }
+
+    #[test]
+    fn dehyphenate_keeps_line_endings_it_doesnt_join() {
+        assert_eq!(
+            dehyphenate("one\r\ninfor-\r\nmation\r\ntwo\r\n"),
+            "one\r\ninformation\r\ntwo\r\n"
+        );
+    }
+
+    // --- collapse-spaces ---
+
+    #[test]
+    fn collapse_spaces_shortens_runs() {
+        assert_eq!(collapse_spaces("a  b\t\t c"), "a b c");
+        assert_eq!(collapse_spaces("   lead"), " lead");
+    }
+
+    #[test]
+    fn collapse_spaces_keeps_line_breaks_and_single_spaces() {
+        assert_eq!(collapse_spaces("a b\n\nc\r\nd"), "a b\n\nc\r\nd");
+        assert_eq!(collapse_spaces("a \n b"), "a \n b");
+    }
+
+    // --- trim-lines ---
+
+    #[test]
+    fn trim_lines_drops_trailing_spaces() {
+        assert_eq!(trim_lines("a  \nb\t\nc "), "a\nb\nc");
+        assert_eq!(trim_lines("a \r\nb\t\r\n"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn trim_lines_keeps_indentation() {
+        assert_eq!(trim_lines("  fn x() {\n\t}\n"), "  fn x() {\n\t}\n");
+    }
+
+    // --- strip-quotes ---
+
+    #[test]
+    fn strip_quotes_removes_markers() {
+        assert_eq!(
+            strip_quotes("> one\n>> two\n> > three\n>"),
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(strip_quotes("  > indented"), "indented");
+    }
+
+    #[test]
+    fn strip_quotes_keeps_other_greater_thans() {
+        for text in ["a > b", "x\n->y", "Vec<T> -> T"] {
+            assert_eq!(strip_quotes(text), text);
+        }
+    }
+
+    // --- strip-markdown ---
+
+    fn strip_markdown(text: &str) -> String {
+        replace_all(text, &MARKDOWN_RULES)
+    }
+
+    #[test]
+    fn markdown_emphasis_removed() {
+        assert_eq!(strip_markdown("*one* and _two_"), "one and two");
+        assert_eq!(
+            strip_markdown("**bold**, __bold__ (*it*)."),
+            "bold, bold (it)."
+        );
+    }
+
+    #[test]
+    fn markdown_keeps_asterisks_and_underscores_inside_words() {
+        for text in [
+            "2*3*4",
+            "a*b*c",
+            "snake_case_name",
+            "x = a * b * c",
+            "f(*args, **kw)",
+        ] {
+            assert_eq!(strip_markdown(text), text);
+        }
+    }
+
+    // --- strip-html ---
+
+    fn strip_html(text: &str) -> String {
+        decode_entities(&replace_all(text, &HTML_RULES))
+    }
+
+    #[test]
+    fn strip_html_keeps_text_and_structure() {
+        assert_eq!(
+            strip_html("<p>One <b>bold</b> word</p><p>Two<br/>lines</p>"),
+            "One bold word\nTwo\nlines\n"
+        );
+        assert_eq!(
+            strip_html("<head><title>x</title></head><style>p {}</style><!-- c -->text"),
+            "text"
+        );
+    }
+
+    #[test]
+    fn strip_html_leaves_plain_text() {
+        assert_eq!(strip_html("1 < 2 and 3 > 2"), "1 < 2 and 3 > 2");
+    }
+
+    #[test]
+    fn decode_entities_known_forms() {
+        assert_eq!(
+            decode_entities("&lt;a&gt; &amp; &#65;&#x42;&#X43; &hellip;"),
+            "<a> & ABC …"
+        );
+    }
+
+    #[test]
+    fn decode_entities_leaves_unknown_ones() {
+        for text in ["&bogus;", "&#xZZ;", "&#99999999;", "AT&T", "a & b"] {
+            assert_eq!(decode_entities(text), text);
+        }
+    }
+
+    // --- strip-bullets ---
+
+    #[test]
+    fn bullets_removed_from_lists() {
+        assert_eq!(strip_bullets("• one\n  • two"), "one\ntwo");
+        assert_eq!(
+            strip_bullets("- one\n- two\n\n- three"),
+            "one\ntwo\n\nthree"
+        );
+        assert_eq!(
+            strip_bullets("1. First\n2. Second\n10. Tenth"),
+            "First\nSecond\nTenth"
+        );
+        assert_eq!(
+            strip_bullets("(i) alpha\n(ii) beta\n(iv) gamma"),
+            "alpha\nbeta\ngamma"
+        );
+        assert_eq!(strip_bullets("a) yes\nb) no"), "yes\nno");
+        assert_eq!(strip_bullets("• alone"), "alone");
+    }
+
+    #[test]
+    fn bullets_keep_sentences_that_look_like_items() {
+        for text in [
+            "2024. It was a year\n2025. And another",
+            "mid. term\nend. term",
+            "A. Smith wrote this.",
+            "- not a list",
+            "1. Only one item\nand its text",
+            "1. First\na) other kind",
+        ] {
+            assert_eq!(strip_bullets(text), text);
+        }
+    }
+
+    // --- strip-citations ---
+
+    #[test]
+    fn citations_removed() {
+        let text =
+            "Einstein was born in Ulm.[1] He moved [2, 3] to Munich[4] in 1880[citation needed].";
+        assert_eq!(
+            strip_citations(text),
+            "Einstein was born in Ulm. He moved to Munich in 1880."
+        );
+        assert_eq!(
+            strip_citations("A note,[a] and another.[b]"),
+            "A note, and another."
+        );
+    }
+
+    #[test]
+    fn citations_keep_indexing() {
+        for text in [
+            "arr[0]",
+            "arr[1] = 5",
+            "matrix[i]",
+            "x[a]",
+            "list_1[2]",
+            "a[1][2]",
+            "items[2].name",
+            "f(x)[1] + 2",
+            "[1] Smith, J. (2001).",
+        ] {
+            assert_eq!(strip_citations(text), text);
+        }
+    }
+
+    // --- strip-line-numbers ---
+
+    #[test]
+    fn strip_line_numbers_from_numbered_code() {
+        assert_eq!(
+            strip_line_numbers("1 fn main() {\n2     run();\n3 }"),
+            "fn main() {\n    run();\n}"
+        );
+        assert_eq!(
+            strip_line_numbers("  9 | let a = 1;\n 10 |\n 11 | let b = 2;"),
+            "let a = 1;\n\nlet b = 2;"
+        );
+        assert_eq!(
+            strip_line_numbers("12: first\r\n13: second\r\n"),
+            "first\r\nsecond\r\n"
+        );
+    }
+
+    #[test]
+    fn strip_line_numbers_keeps_the_texts_own_numbers() {
+        for text in [
+            "2024 was a good year.",
+            "2024 was a good year.\n2023 was not.",
+            "1 apple\n2 pears\nand a basket",
+            "3 | c\n3 | c again",
+        ] {
+            assert_eq!(strip_line_numbers(text), text);
+        }
+    }
+
+    // --- strip-page-headers ---
+
+    /// `pages` joined the way a PDF reader copies them, each starting with `header` (its
+    /// `#` replaced by the page number) and ending with the page number.
+    fn paged(header: &str, pages: &[&str], separator: &str) -> String {
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let number = (i + 1).to_string();
+                format!("{}\n\n{}\n\n{}", header.replace('#', &number), body, number)
+            })
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    /// Bodies of the pages, distinct like a real document's.
+    const PAGES: [&str; 3] = [
+        "The first page begins\nand carries on for a\nfew lines of text that\nwrap around as text\ndoes when it is",
+        "copied from a PDF with\nthe page break right in\nthe middle of a sentence\nwhich should join up\nagain once the headers",
+        "are gone, leaving just\nthe text itself with no\npage furniture at all\nbetween the pages\nof the document.",
+    ];
+
+    #[test]
+    fn page_headers_keep_repeated_headings() {
+        for text in [
+            "Chapter 1\nIntro text.\n\nChapter 2\nMore text.",
+            "Step 1\nDo this\nStep 2\nDo that",
+            "Step 1\nDo this\nStep 2\nDo that\nStep 3\nDo more",
+            "Yes\nNo\nYes",
+            "It happened in\n1984\nand again later.",
+        ] {
+            assert_eq!(strip_page_headers(text), text);
+        }
+    }
+
+    #[test]
+    fn page_headers_removed_between_form_feeds() {
+        let text = paged("The Journal of Things", &PAGES, "\n\x0c");
+        assert_eq!(strip_page_headers(&text), PAGES.join("\n"));
+    }
+
+    #[test]
+    fn page_numbers_removed_between_two_form_feed_pages() {
+        let text = "first page text\n\n1\n\x0csecond page text\n\n2";
+        assert_eq!(
+            strip_page_headers(text),
+            "first page text\nsecond page text"
+        );
+    }
+
+    #[test]
+    fn page_headers_removed_when_evenly_spaced() {
+        let text = paged("Journal of Things, page #", &PAGES, "\n\n");
+        assert_eq!(strip_page_headers(&text), PAGES.join("\n"));
+    }
+
+    #[test]
+    fn page_headers_need_more_than_two_pages() {
+        let text = paged("The Journal of Things", &PAGES[..2], "\n\n");
+        assert_eq!(strip_page_headers(&text), text);
+    }
+
+    // --- strip-invisible ---
+
+    #[test]
+    fn strip_invisible_removes_hidden_characters() {
+        assert_eq!(
+            strip_invisible("\u{FEFF}zero\u{200B}width soft\u{00AD}hyphen"),
+            "zerowidth softhyphen"
+        );
+        // Stray joiners, between ASCII letters or at either end.
+        assert_eq!(strip_invisible("a\u{200D}b\u{200C}c"), "abc");
+        assert_eq!(strip_invisible("\u{200D}👍\u{200D}"), "👍");
+    }
+
+    #[test]
+    fn strip_invisible_keeps_joined_sequences() {
+        for text in ["👨\u{200D}👩\u{200D}👧", "می\u{200C}خواهم", "क्\u{200D}ष"]
+        {
+            assert_eq!(strip_invisible(text), text);
+        }
+    }
+
+    // --- plain-spaces ---
+
+    #[test]
+    fn plain_spaces_replaces_unusual_spaces() {
+        assert_eq!(
+            plain_spaces("10\u{00A0}km, 5\u{202F}%, a\u{2009}b, c\u{200A}d"),
+            "10 km, 5 %, a b, c d"
+        );
+    }
+
+    #[test]
+    fn plain_spaces_keeps_other_whitespace() {
+        for text in ["a b\tc\nd", "\u{3000}全角", "zero\u{200B}width"] {
+            assert_eq!(plain_spaces(text), text);
+        }
+    }
+
+    // --- plain-punctuation ---
+
+    #[test]
+    fn plain_punctuation_uses_ascii() {
+        assert_eq!(
+            plain_punctuation("“It’s ‘fine’” — 1–2 pages…"),
+            "\"It's 'fine'\" -- 1-2 pages..."
+        );
+    }
+
+    #[test]
+    fn plain_punctuation_keeps_other_characters() {
+        for text in ["\"plain\" 'ascii' - ...", "«guillemets» ¿qué?", "café"] {
+            assert_eq!(plain_punctuation(text), text);
+        }
+    }
+}
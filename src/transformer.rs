@@ -0,0 +1,79 @@
+//! The transformer component, which runs pipelines on a supervised thread of its own.
+
+use anyhow::{anyhow, Result};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use crate::supervisor::{self, Component};
+use strflatten::transform::{apply_pipeline_timed, TransformOptions, TransformStep};
+
+/// A pipeline to run, and where to send its result.
+struct Job {
+    text: String,
+    pipeline: Vec<TransformStep>,
+    options: TransformOptions,
+    result: Sender<(String, Vec<Duration>)>,
+}
+
+struct Worker;
+
+impl Component for Worker {
+    type Message = Job;
+    const NAME: &'static str = "transformer";
+
+    fn handle(&mut self, job: Job) {
+        let (result, timings) = apply_pipeline_timed(&job.text, &job.pipeline, &job.options);
+        for (step, duration) in job.pipeline.iter().zip(&timings) {
+            log::debug!("{:?} took {:?}", step, duration);
+        }
+        // Nobody is waiting anymore if the activation gave up.
+        let _ = job.result.send((result, timings));
+    }
+}
+
+/// Hands pipelines to the transformer component, so a transform that panics fails the
+/// activation running it instead of the thread that simulates input.
+#[derive(Clone)]
+pub struct Transformer {
+    jobs: Sender<Job>,
+}
+
+impl Transformer {
+    pub fn spawn() -> Self {
+        Transformer {
+            jobs: supervisor::spawn(Worker),
+        }
+    }
+
+    /// Runs `pipeline` over `text` and waits for the result.
+    pub fn apply(
+        &self,
+        text: &str,
+        pipeline: &[TransformStep],
+        options: &TransformOptions,
+    ) -> Result<String> {
+        self.apply_timed(text, pipeline, options)
+            .map(|(result, _)| result)
+    }
+
+    /// Like `apply`, but also returns how long each step took.
+    pub fn apply_timed(
+        &self,
+        text: &str,
+        pipeline: &[TransformStep],
+        options: &TransformOptions,
+    ) -> Result<(String, Vec<Duration>)> {
+        let (tx, rx) = mpsc::channel();
+        let job = Job {
+            text: text.to_string(),
+            pipeline: pipeline.to_vec(),
+            options: options.clone(),
+            result: tx,
+        };
+        self.jobs
+            .send(job)
+            .map_err(|_| anyhow!("The transformer has stopped"))?;
+        rx.recv()
+            .map_err(|_| anyhow!("The transformer crashed while running {:?}", pipeline))
+    }
+}
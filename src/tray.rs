@@ -0,0 +1,363 @@
+//! A notification-area icon whose menu controls the running listener (Windows only).
+
+use anyhow::{anyhow, Context, Result};
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::Shell::{
+    ShellExecuteW, Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_ERROR,
+    NIIF_INFO, NIIF_WARNING, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DispatchMessageW,
+    GetCursorPos, GetMessageW, LoadIconW, MessageBoxW, PostMessageW, RegisterClassW,
+    SetForegroundWindow, TrackPopupMenu, TranslateMessage, HMENU, IDI_APPLICATION,
+    MB_ICONINFORMATION, MB_OK, MF_CHECKED, MF_SEPARATOR, MF_STRING, MSG, SW_SHOWNORMAL,
+    TPM_NONOTIFY, TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_APP, WM_LBUTTONUP, WM_NULL, WM_RBUTTONUP,
+    WNDCLASSW,
+};
+
+use crate::activity;
+use crate::config;
+use crate::listener::Listener;
+use crate::supervisor;
+use crate::{lock, Request};
+
+/// The message the icon sends our window when it's clicked.
+const WM_TRAY: u32 = WM_APP + 1;
+const ICON_ID: u32 = 1;
+
+// Menu item ids. 0 means the menu was dismissed.
+const PAUSE: usize = 1;
+const NEXT_PROFILE: usize = 2;
+const OPEN_CONFIG: usize = 3;
+const RECENT_ACTIVITY: usize = 4;
+const QUIT: usize = 5;
+
+/// What the menu acts on.
+pub struct Tray {
+    pub listener: Arc<Mutex<Listener>>,
+    /// The worker, which switches profiles.
+    pub requests: Sender<Request>,
+    /// Opened by "Open Config", after writing the defaults there if it doesn't exist yet.
+    pub config_path: Option<PathBuf>,
+}
+
+thread_local! {
+    // The window procedure can't be handed anything of ours, so it finds the tray here.
+    static TRAY: RefCell<Option<Tray>> = const { RefCell::new(None) };
+}
+
+// The icon's window, once it's added, for notifications from other threads.
+static WINDOW: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Shows the icon and runs its menu on a thread of its own until the program exits.
+pub fn spawn(tray: Tray) {
+    thread::Builder::new()
+        .name("tray".to_string())
+        .spawn(move || {
+            if let Err(e) = run(tray) {
+                log::error!("The tray icon failed: {:?}", e);
+            }
+        })
+        .expect("failed to spawn the tray thread");
+}
+
+fn run(tray: Tray) -> Result<()> {
+    TRAY.with(|cell| *cell.borrow_mut() = Some(tray));
+    let window = create_window()?;
+    if unsafe { Shell_NotifyIconW(NIM_ADD, &icon_data(window)) } == 0 {
+        return Err(anyhow!("Failed to add the tray icon"));
+    }
+    WINDOW.store(window, Ordering::Release);
+
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+    loop {
+        match unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } {
+            -1 => return Err(io::Error::last_os_error()).context("Tray message loop failed"),
+            0 => break, // WM_QUIT
+            _ => unsafe {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            },
+        }
+    }
+    WINDOW.store(ptr::null_mut(), Ordering::Release);
+    unsafe { Shell_NotifyIconW(NIM_DELETE, &icon_data(window)) };
+    Ok(())
+}
+
+/// Shows whether hotkeys are paused in the icon's tooltip. Does nothing until the icon is up.
+pub fn show_paused(paused: bool) {
+    let window = WINDOW.load(Ordering::Acquire);
+    if window.is_null() {
+        return;
+    }
+    let mut icon = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: window,
+        uID: ICON_ID,
+        uFlags: NIF_TIP,
+        ..Default::default()
+    };
+    copy_wide(&mut icon.szTip, tip(paused));
+    unsafe { Shell_NotifyIconW(NIM_MODIFY, &icon) };
+}
+
+fn tip(paused: bool) -> &'static str {
+    match paused {
+        true => "flatten-string (paused)",
+        false => "flatten-string",
+    }
+}
+
+/// Shows `text` as a balloon from the icon, or as a toast on Windows 10 and later.
+/// Does nothing until the icon is up.
+pub fn notify(kind: activity::Kind, text: &str) {
+    let window = WINDOW.load(Ordering::Acquire);
+    if window.is_null() {
+        return;
+    }
+    let mut icon = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: window,
+        uID: ICON_ID,
+        uFlags: NIF_INFO,
+        dwInfoFlags: match kind {
+            activity::Kind::Success => NIIF_INFO,
+            activity::Kind::Skipped => NIIF_WARNING,
+            activity::Kind::Failure => NIIF_ERROR,
+        },
+        ..Default::default()
+    };
+    copy_wide(&mut icon.szInfoTitle, "flatten-string");
+    copy_wide(&mut icon.szInfo, text);
+    if unsafe { Shell_NotifyIconW(NIM_MODIFY, &icon) } == 0 {
+        log::warn!("Failed to show a notification: {}", text);
+    }
+}
+
+// A window that's never shown, only there to receive the icon's messages.
+fn create_window() -> Result<HWND> {
+    let class_name = wide("flatten-string-tray");
+    unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: class_name.as_ptr(),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err(io::Error::last_os_error()).context("Failed to register the tray window");
+        }
+        let window = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            instance,
+            ptr::null(),
+        );
+        if window.is_null() {
+            return Err(io::Error::last_os_error()).context("Failed to create the tray window");
+        }
+        Ok(window)
+    }
+}
+
+fn icon_data(window: HWND) -> NOTIFYICONDATAW {
+    let mut icon = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: window,
+        uID: ICON_ID,
+        uFlags: NIF_ICON | NIF_MESSAGE | NIF_TIP,
+        uCallbackMessage: WM_TRAY,
+        hIcon: unsafe { LoadIconW(ptr::null_mut(), IDI_APPLICATION) },
+        ..Default::default()
+    };
+    let paused = TRAY.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|tray| lock(&tray.listener).is_paused())
+    });
+    copy_wide(&mut icon.szTip, tip(paused));
+    icon
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == WM_TRAY && matches!(lparam as u32, WM_LBUTTONUP | WM_RBUTTONUP) {
+        // A panic must not unwind into the OS.
+        if let Err(panic) = supervisor::catch(|| show_menu(window)) {
+            log::error!("The tray menu crashed ({}).", supervisor::describe(&*panic));
+        }
+        return 0;
+    }
+    DefWindowProcW(window, message, wparam, lparam)
+}
+
+fn show_menu(window: HWND) {
+    let paused = TRAY.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|tray| lock(&tray.listener).is_paused())
+    });
+    let chosen = unsafe {
+        let menu = CreatePopupMenu();
+        let pause_flags = match paused {
+            true => MF_STRING | MF_CHECKED,
+            false => MF_STRING,
+        };
+        append(menu, pause_flags, PAUSE, "Paused");
+        append(menu, MF_STRING, NEXT_PROFILE, "Next Profile");
+        append(menu, MF_STRING, OPEN_CONFIG, "Open Config");
+        append(menu, MF_STRING, RECENT_ACTIVITY, "Recent Activity");
+        AppendMenuW(menu, MF_SEPARATOR, 0, ptr::null());
+        append(menu, MF_STRING, QUIT, "Quit");
+
+        let mut point = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut point);
+        // Otherwise the menu stays open when clicking elsewhere.
+        SetForegroundWindow(window);
+        let chosen = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON | TPM_NONOTIFY,
+            point.x,
+            point.y,
+            0,
+            window,
+            ptr::null(),
+        );
+        PostMessageW(window, WM_NULL, 0, 0);
+        DestroyMenu(menu);
+        chosen as usize
+    };
+
+    TRAY.with(|cell| {
+        let tray = cell.borrow();
+        let Some(tray) = tray.as_ref() else {
+            return;
+        };
+        match chosen {
+            PAUSE => {
+                lock(&tray.listener).set_paused(!paused);
+                match paused {
+                    true => println!("Resumed from the tray."),
+                    false => println!("Paused from the tray. Hotkeys do nothing until resumed."),
+                }
+            }
+            NEXT_PROFILE => {
+                let _ = tray.requests.send(Request::NextProfile);
+            }
+            OPEN_CONFIG => {
+                if let Err(e) = open_config(tray.config_path.as_deref()) {
+                    log::error!("{:?}", e);
+                }
+            }
+            RECENT_ACTIVITY => show_recent(window),
+            QUIT => {
+                unsafe { Shell_NotifyIconW(NIM_DELETE, &icon_data(window)) };
+                println!("Quitting from the tray.");
+                process::exit(0);
+            }
+            _ => (), // Dismissed
+        }
+    });
+}
+
+unsafe fn append(menu: HMENU, flags: u32, id: usize, text: &str) {
+    let text = wide(text);
+    AppendMenuW(menu, flags, id, text.as_ptr());
+}
+
+// Opens the config file in its associated editor, or Notepad if .toml has none.
+fn open_config(path: Option<&Path>) -> Result<()> {
+    let path = path.ok_or_else(|| anyhow!("There's no config file location to open"))?;
+    if !path.exists() {
+        config::init(path, false)?;
+    }
+    let file = wide(&path.to_string_lossy());
+    let open = wide("open");
+    // ShellExecute returns a value over 32 on success.
+    let opened = unsafe {
+        ShellExecuteW(
+            ptr::null_mut(),
+            open.as_ptr(),
+            file.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    } as isize
+        > 32;
+    if !opened {
+        let notepad = wide("notepad.exe");
+        let result = unsafe {
+            ShellExecuteW(
+                ptr::null_mut(),
+                open.as_ptr(),
+                notepad.as_ptr(),
+                file.as_ptr(),
+                ptr::null(),
+                SW_SHOWNORMAL,
+            )
+        } as isize;
+        if result <= 32 {
+            return Err(anyhow!("Failed to open {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn show_recent(window: HWND) {
+    let recent = activity::recent();
+    let text = match recent.is_empty() {
+        true => "Nothing has happened yet.".to_string(),
+        false => recent
+            .iter()
+            .map(activity::Entry::describe)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    let text = wide(&text);
+    let caption = wide("flatten-string: Recent Activity");
+    unsafe {
+        MessageBoxW(
+            window,
+            text.as_ptr(),
+            caption.as_ptr(),
+            MB_OK | MB_ICONINFORMATION,
+        )
+    };
+}
+
+/// `text` as a NUL-terminated UTF-16 string.
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Copies `text` into the fixed-size buffer `dst`, cutting it short to keep the final NUL.
+fn copy_wide(dst: &mut [u16], text: &str) {
+    let units: Vec<u16> = text.encode_utf16().take(dst.len() - 1).collect();
+    dst[..units.len()].copy_from_slice(&units);
+    dst[units.len()] = 0;
+}
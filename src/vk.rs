@@ -0,0 +1,113 @@
+//! Windows virtual-key codes for rdev keys. rdev has the same table but keeps it private.
+
+use rdev::Key;
+
+/// The `VK_*` code for `key`, as used by `RegisterHotKey` and `SendInput`.
+pub fn virtual_key(key: Key) -> Option<u16> {
+    Some(match key {
+        Key::Alt => 164,
+        Key::AltGr => 165,
+        Key::Backspace => 0x08,
+        Key::CapsLock => 20,
+        Key::ControlLeft => 162,
+        Key::ControlRight => 163,
+        Key::Delete => 46,
+        Key::DownArrow => 40,
+        Key::End => 35,
+        Key::Escape => 27,
+        Key::F1 => 112,
+        Key::F10 => 121,
+        Key::F11 => 122,
+        Key::F12 => 123,
+        Key::F2 => 113,
+        Key::F3 => 114,
+        Key::F4 => 115,
+        Key::F5 => 116,
+        Key::F6 => 117,
+        Key::F7 => 118,
+        Key::F8 => 119,
+        Key::F9 => 120,
+        Key::Home => 36,
+        Key::LeftArrow => 37,
+        Key::MetaLeft => 91,
+        Key::PageDown => 34,
+        Key::PageUp => 33,
+        Key::Return => 0x0D,
+        Key::RightArrow => 39,
+        Key::ShiftLeft => 160,
+        Key::ShiftRight => 161,
+        Key::Space => 32,
+        Key::Tab => 0x09,
+        Key::UpArrow => 38,
+        Key::PrintScreen => 44,
+        Key::ScrollLock => 145,
+        Key::Pause => 19,
+        Key::NumLock => 144,
+        Key::BackQuote => 192,
+        Key::Num1 => 49,
+        Key::Num2 => 50,
+        Key::Num3 => 51,
+        Key::Num4 => 52,
+        Key::Num5 => 53,
+        Key::Num6 => 54,
+        Key::Num7 => 55,
+        Key::Num8 => 56,
+        Key::Num9 => 57,
+        Key::Num0 => 48,
+        Key::Minus => 189,
+        Key::Equal => 187,
+        Key::KeyQ => 81,
+        Key::KeyW => 87,
+        Key::KeyE => 69,
+        Key::KeyR => 82,
+        Key::KeyT => 84,
+        Key::KeyY => 89,
+        Key::KeyU => 85,
+        Key::KeyI => 73,
+        Key::KeyO => 79,
+        Key::KeyP => 80,
+        Key::LeftBracket => 219,
+        Key::RightBracket => 221,
+        Key::KeyA => 65,
+        Key::KeyS => 83,
+        Key::KeyD => 68,
+        Key::KeyF => 70,
+        Key::KeyG => 71,
+        Key::KeyH => 72,
+        Key::KeyJ => 74,
+        Key::KeyK => 75,
+        Key::KeyL => 76,
+        Key::SemiColon => 186,
+        Key::Quote => 222,
+        Key::BackSlash => 220,
+        Key::IntlBackslash => 226,
+        Key::KeyZ => 90,
+        Key::KeyX => 88,
+        Key::KeyC => 67,
+        Key::KeyV => 86,
+        Key::KeyB => 66,
+        Key::KeyN => 78,
+        Key::KeyM => 77,
+        Key::Comma => 188,
+        Key::Dot => 190,
+        Key::Slash => 191,
+        Key::Insert => 45,
+        Key::KpMinus => 109,
+        Key::KpPlus => 107,
+        Key::KpMultiply => 106,
+        Key::KpDivide => 111,
+        Key::Kp0 => 96,
+        Key::Kp1 => 97,
+        Key::Kp2 => 98,
+        Key::Kp3 => 99,
+        Key::Kp4 => 100,
+        Key::Kp5 => 101,
+        Key::Kp6 => 102,
+        Key::Kp7 => 103,
+        Key::Kp8 => 104,
+        Key::Kp9 => 105,
+        Key::KpDelete => 110,
+        Key::Unknown(code) => u16::try_from(code).ok()?,
+        _ => return None,
+    })
+}